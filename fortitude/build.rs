@@ -18,6 +18,25 @@ fn main() {
     #[allow(clippy::disallowed_methods)]
     let target = std::env::var("TARGET").unwrap();
     println!("cargo::rustc-env=RUST_HOST_TARGET={target}");
+
+    rustc_version();
+}
+
+/// Plugins are only compatible with executables built by the same compiler
+fn rustc_version() {
+    #[allow(clippy::disallowed_methods)]
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .unwrap_or_default();
+    println!(
+        "cargo::rustc-env=FORTITUDE_RUSTC_VERSION={}",
+        version.trim()
+    );
 }
 
 fn commit_info(workspace_root: &Path) {
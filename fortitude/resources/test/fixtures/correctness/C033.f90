@@ -0,0 +1,23 @@
+program test
+  implicit none
+  integer, parameter :: NUM_POINTS = 54
+  integer :: i, x(NUM_POINTS)
+  real :: energy, mass, velocity
+
+  mass = 2.5
+  velocity = 3.0
+  energy = 0.5 * mass * velocity**2
+  x(10) = 1
+  do i = 1, 20
+    x(i) = i * 100
+  end do
+  if (mass > 10.0) energy = 2.0 * energy
+  call report(energy, 7)
+  stop 5
+contains
+  subroutine report(value, code)
+    real, intent(in) :: value
+    integer, intent(in) :: code
+    print *, value, code
+  end subroutine report
+end program test
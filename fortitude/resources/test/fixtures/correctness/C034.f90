@@ -0,0 +1,14 @@
+program test
+  implicit none
+  integer :: status, count
+  real :: tolerance
+
+  status = 0
+  count = 0
+  tolerance = 0.0
+  if (status == 17) print *, "converged"
+  if (count > 1) print *, "more than one"
+  if (tolerance < 1.0e-6) print *, "small"
+  if (status .ne. 99) print *, "not missing"
+  if (10 <= count) print *, "many"
+end program test
@@ -0,0 +1,13 @@
+program test
+  implicit none
+  integer, parameter :: NUM_SAMPLES = 100
+  real, allocatable :: x(:), y(:, :), z(:)
+  integer :: n, stat
+
+  n = 10
+  allocate(x(NUM_SAMPLES))
+  allocate(y(3, 50), stat=stat)
+  allocate(z(0:n))
+  deallocate(x, y, z)
+  allocate(x(200))
+end program test
@@ -0,0 +1,114 @@
+//! Insert allow comments for every current violation, for `check --add-allow`
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use ruff_diagnostics::Edit;
+use ruff_text_size::Ranged;
+use tree_sitter::Parser;
+
+use crate::allow_comments::{allow_statement_edit, enclosing_statement};
+use crate::check::parse_source;
+use crate::message::DiagnosticMessage;
+use crate::resolver::Resolver;
+use crate::rules::Rule;
+use crate::settings::Settings;
+
+/// Rules that can't sensibly be allowed: violations of these are either about the
+/// file as a whole, or about allow comments themselves
+const UNALLOWABLE_RULES: &[Rule] = &[
+    Rule::IoError,
+    Rule::SyntaxError,
+    Rule::InvalidRuleCodeOrName,
+    Rule::UnusedAllowComment,
+    Rule::RedirectedAllowComment,
+    Rule::DuplicatedAllowComment,
+    Rule::DisabledAllowComment,
+];
+
+/// The name to allow a violation by. Custom rules are allowed by their own name.
+fn allow_name<'a>(message: &'a DiagnosticMessage, settings: &'a Settings) -> Option<&'a str> {
+    let rule = message.rule()?;
+    if UNALLOWABLE_RULES.contains(&rule) {
+        return None;
+    }
+    if rule == Rule::CustomRule {
+        return settings
+            .check
+            .custom_rules
+            .get(message.code())
+            .map(|custom| custom.name.as_str());
+    }
+    Some(rule.as_ref())
+}
+
+/// Add or extend allow comments so that each of `messages` is allowed, and return the
+/// number of comments that were changed
+pub(crate) fn add_allow(messages: &[DiagnosticMessage], resolver: &Resolver) -> Result<usize> {
+    let mut files: BTreeMap<&str, Vec<&DiagnosticMessage>> = BTreeMap::new();
+    for message in messages {
+        files.entry(message.filename()).or_default().push(message);
+    }
+
+    let mut changed = 0;
+    for (filename, messages) in files {
+        let file = messages[0].source_file();
+        let source = file.source_text();
+        let path = Path::new(filename);
+        let settings = &resolver.resolve(path).settings;
+
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_fortran::LANGUAGE.into())
+            .context("Error loading Fortran grammar")?;
+        let Some(tree) = parser.parse(parse_source(path, source, &settings.check).as_ref(), None)
+        else {
+            continue;
+        };
+        let root = tree.root_node();
+
+        // Violations on the same statement share a single comment
+        let mut statements = BTreeMap::new();
+        for message in messages {
+            let Some(name) = allow_name(message, settings) else {
+                continue;
+            };
+            let Some(statement) = enclosing_statement(&root, message.range(), file) else {
+                continue;
+            };
+            statements
+                .entry(statement.start_byte())
+                .or_insert_with(|| (statement, Vec::new()))
+                .1
+                .push(name);
+        }
+
+        let edits = statements
+            .values()
+            .filter_map(|(statement, names)| allow_statement_edit(statement, names, file))
+            .collect_vec();
+        if edits.is_empty() {
+            continue;
+        }
+        changed += edits.len();
+
+        std::fs::write(path, apply_edits(source, &edits))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(changed)
+}
+
+/// Apply non-overlapping edits to `source`
+fn apply_edits(source: &str, edits: &[Edit]) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut last = 0;
+    for edit in edits.iter().sorted_by_key(|edit| edit.start()) {
+        output.push_str(&source[last..edit.start().to_usize()]);
+        output.push_str(edit.content().unwrap_or_default());
+        last = edit.end().to_usize();
+    }
+    output.push_str(&source[last..]);
+    output
+}
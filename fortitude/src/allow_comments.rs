@@ -1,4 +1,7 @@
 use crate::ast::FortitudeNode;
+use crate::custom_rules::CustomRules;
+use crate::diagnostics::Violation;
+use crate::project::PROJECT_RULES;
 use crate::registry::AsRule;
 use crate::rule_redirects::get_redirect_target;
 use crate::rule_table::RuleTable;
@@ -9,11 +12,11 @@ use crate::rules::fortitude::allow_comments::{
 use crate::rules::Rule;
 
 use itertools::Itertools;
-use lazy_regex::{regex, regex_captures};
+use lazy_regex::regex;
 use ruff_diagnostics::{Diagnostic, Edit, Fix};
 use ruff_source_file::SourceFile;
 use ruff_text_size::{TextRange, TextSize};
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
 use std::str::FromStr;
 use tree_sitter::Node;
 
@@ -25,6 +28,36 @@ pub struct Code<'a> {
     pub rule: Option<Rule>,
     // The location of the code
     pub loc: TextRange,
+    // The range the code applies to. For `enable` comments, this is the region that
+    // the code closes, if any.
+    pub range: Option<TextRange>,
+}
+
+/// The forms that allow comments can take
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AllowCommentKind {
+    /// `! allow(...)` on its own line, applying to the next statement
+    Allow,
+    /// `! allow(...)` at the end of a line, applying to the statement on that line
+    TrailingAllow,
+    /// `! fortitude: disable-file(...)`, applying to the whole file
+    DisableFile,
+    /// `! fortitude: disable(...)`, applying until a matching `enable` comment, or
+    /// until the end of the file
+    Disable,
+    /// `! fortitude: enable(...)`, ending the regions started by `disable` comments
+    Enable,
+}
+
+impl AllowCommentKind {
+    fn directive(self) -> &'static str {
+        match self {
+            Self::Allow | Self::TrailingAllow => "allow",
+            Self::DisableFile => "fortitude: disable-file",
+            Self::Disable => "fortitude: disable",
+            Self::Enable => "fortitude: enable",
+        }
+    }
 }
 
 /// A single allowed rule and the range it applies to
@@ -32,13 +65,12 @@ pub struct Code<'a> {
 pub struct AllowComment<'a, 'b> {
     // Codes in the allow comment
     pub codes: Vec<Code<'a>>,
-    // The range the comment applies to
-    pub range: TextRange,
+    pub kind: AllowCommentKind,
     // The comment node
     pub node: Node<'b>,
 }
 
-/// If this node is an `allow` comment, get all the rules allowed on the next line
+/// If this node is an allow comment, get all the rules allowed by it
 pub fn gather_allow_comments<'a, 'b>(
     node: &Node<'b>,
     file: &'a SourceFile,
@@ -47,13 +79,34 @@ pub fn gather_allow_comments<'a, 'b>(
         return None;
     }
 
-    let mut codes = Vec::new();
+    let text = node.to_text(file.source_text()).unwrap();
+    let captures =
+        regex!(r#"^(?:[cC*]|!)\s*(allow|fortitude:\s*(?:disable-file|disable|enable))\((.*)\)\s*"#)
+            .captures(text)?;
+    let directive = captures.get(1).unwrap().as_str();
+    let allow_comment = captures.get(2).unwrap();
+
+    let src = file.to_source_code();
+    let line_start = src.line_start(src.line_index(node.start_textsize()));
+    let line_end = src.line_end(src.line_index(node.start_textsize()));
+    let trailing = !src
+        .slice(TextRange::new(line_start, node.start_textsize()))
+        .trim()
+        .is_empty();
 
-    let (_, allow_comment) = regex_captures!(
-        r#"! allow\((.*)\)\s*"#,
-        node.to_text(file.source_text()).unwrap()
-    )?;
-    let range = if let Some(next_node) = node.next_named_sibling() {
+    let (kind, range) = if directive == "allow" && trailing {
+        // Cover the whole of the statement that ends on this line
+        let start = node
+            .prev_sibling()
+            .filter(|sibling| sibling.end_position().row == node.start_position().row)
+            .map(|sibling| src.line_start(src.line_index(sibling.start_textsize())))
+            .unwrap_or(line_start);
+        (
+            AllowCommentKind::TrailingAllow,
+            Some(TextRange::new(start, line_end)),
+        )
+    } else if directive == "allow" {
+        let next_node = node.next_named_sibling()?;
         let start_byte = next_node.start_textsize();
         let end_byte = next_node.end_textsize();
 
@@ -61,74 +114,155 @@ pub fn gather_allow_comments<'a, 'b>(
         // line that it _ends_ on -- i.e. including trailing
         // whitespace and other statements. This might have weird
         // edge cases.
-        let src = file.to_source_code();
         let start_index = src.line_index(start_byte);
         let end_index = src.line_index(end_byte);
         let start_line = src.line_start(start_index);
         let end_line = src.line_end(end_index);
 
-        TextRange::new(start_line, end_line)
+        (
+            AllowCommentKind::Allow,
+            Some(TextRange::new(start_line, end_line)),
+        )
+    } else if directive.ends_with("disable-file") {
+        (
+            AllowCommentKind::DisableFile,
+            Some(TextRange::up_to(TextSize::of(file.source_text()))),
+        )
+    } else if directive.ends_with("disable") {
+        // Until we find the matching `enable` comment, this applies to the rest of
+        // the file
+        (
+            AllowCommentKind::Disable,
+            Some(TextRange::new(
+                node.start_textsize(),
+                TextSize::of(file.source_text()),
+            )),
+        )
     } else {
-        return None;
+        (AllowCommentKind::Enable, None)
     };
 
     // Partition the found selectors into valid and invalid
     let rule_regex = regex!(r#"\w[-\w\d]*"#);
-    // 8 from length of "! allow("
-    let comment_start_offset = node.start_textsize() + TextSize::new(8);
-    for rule in rule_regex.find_iter(allow_comment) {
-        let start = comment_start_offset + TextSize::try_from(rule.start()).unwrap();
-        let end = comment_start_offset + TextSize::try_from(rule.end()).unwrap();
-        let loc = TextRange::new(start, end);
-        let code = rule.as_str();
-        let redirect = get_redirect_target(code).unwrap_or(code);
-        let rule = Rule::from_code(redirect).or(Rule::from_str(redirect)).ok();
-
-        codes.push(Code { code, rule, loc });
-    }
+    let comment_start_offset =
+        node.start_textsize() + TextSize::try_from(allow_comment.start()).unwrap();
+    let codes = rule_regex
+        .find_iter(allow_comment.as_str())
+        .map(|rule| {
+            let start = comment_start_offset + TextSize::try_from(rule.start()).unwrap();
+            let end = comment_start_offset + TextSize::try_from(rule.end()).unwrap();
+            let loc = TextRange::new(start, end);
+            let code = rule.as_str();
+            let redirect = get_redirect_target(code).unwrap_or(code);
+            let rule = Rule::from_code(redirect).or(Rule::from_str(redirect)).ok();
+
+            Code {
+                code,
+                rule,
+                loc,
+                range,
+            }
+        })
+        .collect();
 
     Some(AllowComment {
         codes,
-        range,
+        kind,
         node: *node,
     })
 }
 
+/// Match up `disable` and `enable` comments, so that each `disable` only applies until
+/// the next `enable` for the same rule. `allow_comments` must be in the order that
+/// they appear in the file.
+pub fn resolve_disabled_regions(allow_comments: &mut [AllowComment]) {
+    // Built-in rules are matched however they're referred to, while custom rules must
+    // use the same code or name in both comments
+    let key = |code: &Code| match code.rule {
+        Some(rule) => rule.noqa_code().to_string(),
+        None => code.code.to_string(),
+    };
+
+    let mut open: FxHashMap<String, Vec<(usize, usize)>> = FxHashMap::default();
+    let mut regions = Vec::new();
+    for (comment_index, comment) in allow_comments.iter().enumerate() {
+        for (code_index, code) in comment.codes.iter().enumerate() {
+            match comment.kind {
+                AllowCommentKind::Disable => open
+                    .entry(key(code))
+                    .or_default()
+                    .push((comment_index, code_index)),
+                AllowCommentKind::Enable => {
+                    if let Some(disable) = open.get_mut(&key(code)).and_then(Vec::pop) {
+                        regions.push((disable, (comment_index, code_index)));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for ((disable_comment, disable_code), (enable_comment, enable_code)) in regions {
+        let range = TextRange::new(
+            allow_comments[disable_comment].node.start_textsize(),
+            allow_comments[enable_comment].node.end_textsize(),
+        );
+        allow_comments[disable_comment].codes[disable_code].range = Some(range);
+        allow_comments[enable_comment].codes[enable_code].range = Some(range);
+    }
+}
+
 /// Check allow comments, raise applicable violations, and ignore allowed diagnostics
-pub fn check_allow_comments(
-    diagnostics: &mut Vec<Diagnostic>,
+pub(crate) fn check_allow_comments(
+    violations: &mut Vec<Violation>,
     allow_comments: &[AllowComment],
     rules: &RuleTable,
+    custom_rules: &CustomRules,
     file: &SourceFile,
 ) -> Vec<usize> {
     // Indices of diagnostics that were ignored by a `noqa` directive.
     let mut ignored_diagnostics = vec![];
 
+    // The comments and codes within them that were used
     let mut used_codes = FxHashSet::default();
 
     // Remove any ignored diagnostics
-    'outer: for (index, diagnostic) in diagnostics.iter().enumerate() {
-        for allow in allow_comments {
-            for code in &allow.codes {
-                if let Some(rule) = code.rule {
-                    if rule == diagnostic.kind.rule()
-                        && allow.range.contains_range(diagnostic.range)
-                    {
-                        used_codes.insert(rule);
-                        ignored_diagnostics.push(index);
-                        // We've ignored this diagnostic, so no point
-                        // checking the other allow comments!
-                        continue 'outer;
-                    };
+    'outer: for (index, violation) in violations.iter().enumerate() {
+        let diagnostic = &violation.diagnostic;
+        for (comment_index, allow) in allow_comments.iter().enumerate() {
+            // `enable` comments don't allow anything themselves
+            if allow.kind == AllowCommentKind::Enable {
+                continue;
+            }
+            for (code_index, code) in allow.codes.iter().enumerate() {
+                let allowed = match code.rule {
+                    Some(rule) => rule == diagnostic.kind.rule(),
+                    None => custom_rules.get(code.code).is_some_and(|custom| {
+                        violation
+                            .custom_rule
+                            .as_ref()
+                            .is_some_and(|custom_rule| custom_rule.code == custom.code)
+                    }),
+                };
+                if allowed
+                    && code
+                        .range
+                        .is_some_and(|range| range.contains_range(diagnostic.range))
+                {
+                    used_codes.insert((comment_index, code_index));
+                    ignored_diagnostics.push(index);
+                    // We've ignored this diagnostic, so no point
+                    // checking the other allow comments!
+                    continue 'outer;
                 }
             }
         }
     }
 
-    for comment in allow_comments {
+    for (comment_index, comment) in allow_comments.iter().enumerate() {
         let mut seen_codes = FxHashSet::default();
 
-        for code in &comment.codes {
+        for (code_index, code) in comment.codes.iter().enumerate() {
             let redirect = get_redirect_target(code.code);
             if rules.enabled(Rule::RedirectedAllowComment) {
                 if let Some(redirect) = redirect {
@@ -137,7 +271,7 @@ pub fn check_allow_comments(
                     let new_name = rule.as_ref().to_string();
                     let edit =
                         Edit::replacement(new_name.clone(), code.loc.start(), code.loc.end());
-                    diagnostics.push(
+                    violations.push(
                         Diagnostic::new(
                             RedirectedAllowComment {
                                 original: code.code.to_string(),
@@ -147,7 +281,8 @@ pub fn check_allow_comments(
                             },
                             code.loc,
                         )
-                        .with_fix(Fix::safe_edit(edit)),
+                        .with_fix(Fix::safe_edit(edit))
+                        .into(),
                     );
                 }
             }
@@ -155,32 +290,60 @@ pub fn check_allow_comments(
             let rule_str = code.code.to_string();
             let edit = remove_code_from_allow_comment(comment, code, file);
 
-            match code.rule {
+            // Resolve the code to either a built-in rule or a custom rule
+            let resolved = match code.rule {
+                Some(rule) => Some((
+                    rule.noqa_code().to_string(),
+                    rules.enabled(rule),
+                    // Project rules are checked after all files, so aren't seen here
+                    PROJECT_RULES.contains(&rule),
+                )),
+                None => custom_rules
+                    .get(code.code)
+                    .map(|custom| (custom.code.clone(), custom.enabled, false)),
+            };
+
+            // An `enable` comment is used if it ends a region, whether or not anything
+            // in that region was allowed
+            let used = match comment.kind {
+                AllowCommentKind::Enable => code.range.is_some(),
+                _ => used_codes.contains(&(comment_index, code_index)),
+            };
+
+            match resolved {
                 None => {
                     if rules.enabled(Rule::InvalidRuleCodeOrName) {
-                        diagnostics.push(
+                        violations.push(
                             Diagnostic::new(InvalidRuleCodeOrName { rule: rule_str }, code.loc)
-                                .with_fix(Fix::safe_edit(edit)),
+                                .with_fix(Fix::safe_edit(edit))
+                                .into(),
                         );
                     }
                 }
-                Some(rule) => {
-                    let used = used_codes.contains(&rule);
-                    let enabled = rules.enabled(rule);
-                    if !seen_codes.insert(rule) && rules.enabled(Rule::DuplicatedAllowComment) {
-                        diagnostics.push(
+                Some((resolved_code, enabled, is_project_rule)) => {
+                    if !seen_codes.insert(resolved_code)
+                        && rules.enabled(Rule::DuplicatedAllowComment)
+                    {
+                        violations.push(
                             Diagnostic::new(DuplicatedAllowComment { rule: rule_str }, code.loc)
-                                .with_fix(Fix::safe_edit(edit)),
+                                .with_fix(Fix::safe_edit(edit))
+                                .into(),
                         );
                     } else if !enabled && rules.enabled(Rule::DisabledAllowComment) {
-                        diagnostics.push(
+                        violations.push(
                             Diagnostic::new(DisabledAllowComment { rule: rule_str }, code.loc)
-                                .with_fix(Fix::safe_edit(edit)),
+                                .with_fix(Fix::safe_edit(edit))
+                                .into(),
                         );
-                    } else if !used && enabled && rules.enabled(Rule::UnusedAllowComment) {
-                        diagnostics.push(
+                    } else if !used
+                        && enabled
+                        && !is_project_rule
+                        && rules.enabled(Rule::UnusedAllowComment)
+                    {
+                        violations.push(
                             Diagnostic::new(UnusedAllowComment { rule: rule_str }, code.loc)
-                                .with_fix(Fix::safe_edit(edit)),
+                                .with_fix(Fix::safe_edit(edit))
+                                .into(),
                         );
                     }
                 }
@@ -204,11 +367,199 @@ fn remove_code_from_allow_comment(
         .map(|code| code.code)
         .join(", ");
 
-    if remaining_codes.is_empty() {
-        comment.node.edit_delete(file)
-    } else {
-        comment
-            .node
-            .edit_replacement(file, format!("! allow({})", remaining_codes))
+    if !remaining_codes.is_empty() {
+        return comment.node.edit_replacement(
+            file,
+            format!("! {}({})", comment.kind.directive(), remaining_codes),
+        );
+    }
+
+    if comment.kind == AllowCommentKind::TrailingAllow {
+        // Also remove the whitespace between the statement and the comment
+        let src = file.to_source_code();
+        let line_start = src.line_start(src.line_index(comment.node.start_textsize()));
+        let before = src.slice(TextRange::new(line_start, comment.node.start_textsize()));
+        let start = line_start + TextSize::of(before.trim_end());
+        let text = comment.node.to_text(file.source_text()).unwrap();
+        let end = comment.node.start_textsize() + TextSize::of(text.trim_end());
+        return Edit::range_deletion(TextRange::new(start, end));
+    }
+    comment.node.edit_delete(file)
+}
+
+/// Create an edit that allows `names` for the statement containing `range`, either by
+/// adding them to an existing allow comment for that statement, or by inserting a new
+/// allow comment on the line above it. Returns `None` if the rules are already allowed,
+/// or if there is no statement to attach an allow comment to.
+pub fn allow_comment_edit(
+    names: &[&str],
+    range: TextRange,
+    root: &Node,
+    file: &SourceFile,
+) -> Option<Edit> {
+    let statement = enclosing_statement(root, range, file)?;
+    allow_statement_edit(&statement, names, file)
+}
+
+/// Create an edit that allows `names` for `statement`, merging them into any existing
+/// allow comment so that no rule is allowed twice
+pub(crate) fn allow_statement_edit(
+    statement: &Node,
+    names: &[&str],
+    file: &SourceFile,
+) -> Option<Edit> {
+    let existing = statement
+        .prev_named_sibling()
+        .and_then(|sibling| gather_allow_comments(&sibling, file))
+        .filter(|comment| comment.kind == AllowCommentKind::Allow);
+
+    // Rules may already be allowed by either their code or their name
+    let is_allowed = |name: &str| {
+        let rule = Rule::from_str(name).ok();
+        existing
+            .iter()
+            .flat_map(|comment| &comment.codes)
+            .any(|code| code.code == name || (rule.is_some() && code.rule == rule))
+    };
+    let names = names
+        .iter()
+        .copied()
+        .filter(|name| !is_allowed(name))
+        .unique()
+        .collect_vec();
+    if names.is_empty() {
+        return None;
+    }
+
+    if let Some(comment) = existing {
+        let codes = comment
+            .codes
+            .iter()
+            .map(|code| code.code)
+            .chain(names)
+            .join(", ");
+        return Some(
+            comment
+                .node
+                .edit_replacement(file, format!("! allow({codes})")),
+        );
+    }
+
+    let src = file.to_source_code();
+    let line_start = src.line_start(src.line_index(statement.start_textsize()));
+    let indentation = statement.indentation(file);
+    Some(Edit::insertion(
+        format!("{indentation}! allow({})\n", names.join(", ")),
+        line_start,
+    ))
+}
+
+/// Find the outermost node that starts on the same line as `range`, and which begins
+/// that line. This is the node that an allow comment on the line above would apply to.
+pub(crate) fn enclosing_statement<'a>(
+    root: &Node<'a>,
+    range: TextRange,
+    file: &SourceFile,
+) -> Option<Node<'a>> {
+    let start = range.start().to_usize();
+    let mut node = root.named_descendant_for_byte_range(start, start)?;
+
+    let starts_line = |node: &Node| node.indentation(file).len() == node.start_position().column;
+
+    while let Some(parent) = node.parent() {
+        // Allow comments can't go outside the root node
+        if parent.parent().is_none() {
+            break;
+        }
+        if starts_line(&node) && parent.start_position().row != node.start_position().row {
+            break;
+        }
+        node = parent;
+    }
+
+    // Either the range was outside any statement, or we couldn't find the start of it
+    if node.parent().is_none() || !starts_line(&node) {
+        return None;
+    }
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use ruff_source_file::SourceFileBuilder;
+    use textwrap::dedent;
+    use tree_sitter::Parser;
+
+    use super::*;
+    use crate::fixed_form::to_free_form;
+
+    /// The kind and codes of each allow comment in `source`
+    fn allow_comments(source: &str, fixed_form: bool) -> Vec<(AllowCommentKind, Vec<&str>)> {
+        let file = SourceFileBuilder::new("test.f90", source).finish();
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_fortran::LANGUAGE.into())
+            .unwrap();
+        let parsed = if fixed_form {
+            to_free_form(source)
+        } else {
+            source.to_string()
+        };
+        let tree = parser.parse(parsed, None).unwrap();
+        tree.root_node()
+            .descendants()
+            .filter_map(|node| gather_allow_comments(&node, &file))
+            .map(|comment| {
+                let codes = comment
+                    .codes
+                    .iter()
+                    .map(|code| &source[code.loc.start().to_usize()..code.loc.end().to_usize()])
+                    .collect();
+                (comment.kind, codes)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn directive_starts_comment() {
+        let source = dedent(
+            "
+            program test
+              ! allow(star-kind)
+              integer*4 :: a  ! allow(literal-kind)
+              ! see note ! allow(star-kind) for details
+              ! this doesn't allow(star-kind)
+              integer*4 :: b  ! note ! allow(star-kind)
+              ! an example: ! fortitude: disable(star-kind)
+              !allow(implicit-typing)
+            end program test
+            ",
+        );
+        assert_eq!(
+            allow_comments(&source, false),
+            [
+                (AllowCommentKind::Allow, vec!["star-kind"]),
+                (AllowCommentKind::TrailingAllow, vec!["literal-kind"]),
+                (AllowCommentKind::Allow, vec!["implicit-typing"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn fixed_form_directives() {
+        let source = [
+            "      PROGRAM TEST",
+            "C     allow(star-kind)",
+            "      INTEGER*4 A",
+            "*     see note ! allow(star-kind)",
+            "      INTEGER*4 B",
+            "      END",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(
+            allow_comments(&source, true),
+            [(AllowCommentKind::Allow, vec!["star-kind"])]
+        );
     }
 }
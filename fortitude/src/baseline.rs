@@ -0,0 +1,281 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use itertools::Itertools;
+use ruff_text_size::{Ranged, TextRange};
+use rustc_hash::FxHashSet;
+use serde::{Deserialize, Serialize};
+
+use crate::message::DiagnosticMessage;
+
+/// Version of the baseline file format.
+const BASELINE_VERSION: u32 = 1;
+
+/// Identifies a violation independently of its exact location, so that it still
+/// matches after unrelated lines are added or removed.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub(crate) struct Fingerprint {
+    /// Path of the file, relative to the project root.
+    file: String,
+    /// Code of the rule that was violated.
+    code: String,
+    /// The lines containing the violation, with whitespace collapsed and lowercased.
+    snippet: String,
+}
+
+impl Fingerprint {
+    fn new(message: &DiagnosticMessage, project_root: &Path) -> Self {
+        let path = Path::new(message.filename());
+        let file = path
+            .strip_prefix(project_root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let code = message.code().to_string();
+        Self {
+            file,
+            code,
+            snippet: normalized_snippet(message),
+        }
+    }
+}
+
+/// The source lines containing a violation, normalised so that changes in indentation,
+/// spacing, or case don't affect the fingerprint.
+pub(crate) fn normalized_snippet(message: &DiagnosticMessage) -> String {
+    if message.source_file().source_text().is_empty() {
+        return String::new();
+    }
+    let source = message.source_file().to_source_code();
+    let start = source.line_start(source.line_index(message.start()));
+    let end = source.line_end(source.line_index(message.end()));
+    source
+        .slice(TextRange::new(start, end))
+        .split_whitespace()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// A baseline entry as stored on disk.
+#[derive(Debug, Serialize, Deserialize)]
+struct BaselineEntry {
+    #[serde(flatten)]
+    fingerprint: Fingerprint,
+    /// Number of identical violations.
+    count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BaselineFile {
+    version: u32,
+    entries: Vec<BaselineEntry>,
+}
+
+/// A set of pre-existing violations that should not be reported.
+#[derive(Debug)]
+pub(crate) struct Baseline {
+    entries: BTreeMap<Fingerprint, usize>,
+    project_root: PathBuf,
+}
+
+impl Baseline {
+    /// Create a baseline containing all of `messages`.
+    pub(crate) fn from_messages(messages: &[DiagnosticMessage], project_root: &Path) -> Self {
+        let mut entries = BTreeMap::new();
+        for message in messages {
+            *entries
+                .entry(Fingerprint::new(message, project_root))
+                .or_default() += 1;
+        }
+        Self {
+            entries,
+            project_root: project_root.to_path_buf(),
+        }
+    }
+
+    /// Read a baseline file. File paths in the baseline are relative to `project_root`.
+    pub(crate) fn read(path: &Path, project_root: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open baseline file {}", path.display()))?;
+        let baseline: BaselineFile = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Failed to parse baseline file {}", path.display()))?;
+        if baseline.version != BASELINE_VERSION {
+            anyhow::bail!(
+                "Unsupported baseline file version {} in {}, expected {BASELINE_VERSION}",
+                baseline.version,
+                path.display()
+            );
+        }
+        let mut entries = BTreeMap::new();
+        for entry in baseline.entries {
+            *entries.entry(entry.fingerprint).or_default() += entry.count;
+        }
+        Ok(Self {
+            entries,
+            project_root: project_root.to_path_buf(),
+        })
+    }
+
+    /// Write the baseline to `path`, sorted so that it can be diffed easily.
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create baseline file {}", path.display()))?;
+        let baseline = BaselineFile {
+            version: BASELINE_VERSION,
+            entries: self
+                .entries
+                .iter()
+                .map(|(fingerprint, count)| BaselineEntry {
+                    fingerprint: fingerprint.clone(),
+                    count: *count,
+                })
+                .collect(),
+        };
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, &baseline)?;
+        writeln!(writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Total number of violations in the baseline.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.values().sum()
+    }
+
+    /// Remove any messages that are in the baseline. Returns the baseline entries for
+    /// `checked_files` that didn't match any message, as these have since been fixed.
+    pub(crate) fn apply(
+        &self,
+        messages: &mut Vec<DiagnosticMessage>,
+        checked_files: &[PathBuf],
+    ) -> Vec<(Fingerprint, usize)> {
+        let mut remaining = self.entries.clone();
+        messages.retain(|message| {
+            match remaining.get_mut(&Fingerprint::new(message, &self.project_root)) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            }
+        });
+
+        let checked_files: FxHashSet<_> = checked_files
+            .iter()
+            .map(|path| {
+                path.strip_prefix(&self.project_root)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+        remaining
+            .into_iter()
+            .filter(|(fingerprint, count)| *count > 0 && checked_files.contains(&fingerprint.file))
+            .collect()
+    }
+}
+
+/// Report baseline entries that no longer match a violation.
+pub(crate) fn write_fixed_entries(
+    fixed: &[(Fingerprint, usize)],
+    writer: &mut dyn Write,
+) -> Result<()> {
+    for (fingerprint, count) in fixed {
+        write!(
+            writer,
+            "{}{} {} `{}`",
+            fingerprint.file.bold(),
+            ":".cyan(),
+            fingerprint.code.red().bold(),
+            fingerprint.snippet
+        )?;
+        if *count > 1 {
+            write!(writer, " (x{count})")?;
+        }
+        writeln!(writer)?;
+    }
+    let total: usize = fixed.iter().map(|(_, count)| count).sum();
+    match total {
+        0 => writeln!(writer, "No fixed baseline entries found.")?,
+        1 => writeln!(writer, "Found 1 fixed baseline entry.")?,
+        _ => writeln!(writer, "Found {total} fixed baseline entries.")?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use ruff_diagnostics::Diagnostic;
+    use ruff_source_file::SourceFileBuilder;
+    use ruff_text_size::{TextRange, TextSize};
+
+    use super::Baseline;
+    use crate::message::DiagnosticMessage;
+    use crate::rules::correctness::use_statements::UseAll;
+
+    fn message(path: &str, source: &str, offset: u32) -> DiagnosticMessage {
+        let file = SourceFileBuilder::new(path, source).finish();
+        let start = TextSize::new(offset);
+        let diagnostic =
+            Diagnostic::new(UseAll {}, TextRange::new(start, start + TextSize::new(7)));
+        DiagnosticMessage::from_ruff(&file, diagnostic)
+    }
+
+    #[test]
+    fn matches_moved_violations() {
+        let root = Path::new("/project");
+        let original = [message(
+            "/project/test.f90",
+            "program test\nend program test\n",
+            0,
+        )];
+        let baseline = Baseline::from_messages(&original, root);
+
+        // Moving the violation and changing its spacing and case doesn't affect it
+        let mut moved = vec![
+            message(
+                "/project/test.f90",
+                "! comment\n  PROGRAM   test\nend program test\n",
+                12,
+            ),
+            message("/project/other.f90", "program test\nend program test\n", 0),
+        ];
+        let fixed = baseline.apply(
+            &mut moved,
+            &[
+                PathBuf::from("/project/test.f90"),
+                PathBuf::from("/project/other.f90"),
+            ],
+        );
+        assert!(fixed.is_empty());
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].filename(), "/project/other.f90");
+    }
+
+    #[test]
+    fn reports_fixed_entries() {
+        let root = Path::new("/project");
+        let original = [
+            message("/project/test.f90", "program test\nend program test\n", 0),
+            message("/project/other.f90", "program test\nend program test\n", 0),
+        ];
+        let baseline = Baseline::from_messages(&original, root);
+
+        // Entries for files that weren't checked aren't reported as fixed
+        let fixed = baseline.apply(&mut vec![], &[PathBuf::from("/project/test.f90")]);
+        assert_eq!(fixed.len(), 1);
+        assert_eq!(fixed[0].0.file, "test.f90");
+        assert_eq!(fixed[0].1, 1);
+    }
+}
@@ -0,0 +1,293 @@
+// Adapted from ruff
+// Copyright 2022 Charles Marsh
+// SPDX-License-Identifier: MIT
+
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use log::debug;
+use ruff_cache::{CacheKey, CacheKeyHasher};
+use ruff_diagnostics::{Diagnostic, DiagnosticKind, Fix};
+use ruff_source_file::SourceFile;
+use ruff_text_size::{Ranged, TextRange};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::custom_rules::CustomRuleId;
+use crate::message::DiagnosticMessage;
+use crate::project::FileIndex;
+use crate::resolver::Resolver;
+use crate::VERSION;
+
+/// Name of the default cache directory, relative to the project root.
+pub const DEFAULT_CACHE_DIR: &str = ".fortitude_cache";
+
+/// Entries that haven't been used for this long are dropped when the cache is persisted.
+const MAX_LAST_SEEN: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// On-disk cache of the diagnostics raised for each file, for a given set of settings.
+///
+/// Each combination of Fortitude version and [`CheckSettings`](crate::settings::CheckSettings)
+/// for the project and any nested configuration files gets its own cache file, so
+/// changing the configuration or upgrading invalidates all entries at once. Within a
+/// cache, entries are keyed by absolute file path and are only reused if the hash of
+/// the file contents is unchanged.
+///
+/// Lookups and updates can happen in parallel. Updates are queued up and only written
+/// to disk by [`Cache::persist`].
+pub(crate) struct Cache {
+    /// Location of the cache file.
+    path: PathBuf,
+    /// The cache as it was read from disk.
+    package: PackageCache,
+    /// Changes made to the cache during this run.
+    changes: Mutex<Vec<Change>>,
+    /// Timestamp used to mark entries seen during this run, in seconds since the epoch.
+    now: u64,
+}
+
+impl Cache {
+    /// Open the cache for the settings in use by `resolver`, stored under `cache_dir`. A
+    /// missing or unreadable cache file results in an empty cache.
+    pub(crate) fn open(cache_dir: &Path, resolver: &Resolver) -> Self {
+        let mut hasher = CacheKeyHasher::new();
+        for settings in resolver.settings() {
+            settings.check.cache_key(&mut hasher);
+        }
+        let path = cache_dir
+            .join(VERSION)
+            .join(format!("{:x}", hasher.finish()));
+
+        let package = match read_package(&path) {
+            Ok(package) => package,
+            Err(err) => {
+                debug!("Failed to read cache file {}: {err:#}", path.display());
+                PackageCache::default()
+            }
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        Self {
+            path,
+            package,
+            changes: Mutex::new(Vec::new()),
+            now,
+        }
+    }
+
+    /// Return the cached diagnostics for `path`, and its index for project rules if one
+    /// was stored, provided its contents haven't changed since they were cached.
+    pub(crate) fn get(
+        &self,
+        path: &Path,
+        file: &SourceFile,
+    ) -> Option<(Vec<DiagnosticMessage>, Option<FileIndex>)> {
+        let key = file_key(path)?;
+        let entry = self.package.files.get(&key)?;
+        if entry.content_hash != content_hash(file) {
+            return None;
+        }
+
+        let messages = entry
+            .messages
+            .iter()
+            .map(|message| message.to_message(file))
+            .collect();
+        self.changes.lock().unwrap().push(Change::Touch(key));
+        Some((messages, entry.index.clone()))
+    }
+
+    /// Store the diagnostics found for `path`, along with its index for project rules.
+    pub(crate) fn update(
+        &self,
+        path: &Path,
+        file: &SourceFile,
+        messages: &[DiagnosticMessage],
+        index: Option<&FileIndex>,
+    ) {
+        let Some(key) = file_key(path) else {
+            return;
+        };
+        let entry = FileCache {
+            content_hash: content_hash(file),
+            last_seen: self.now,
+            messages: messages.iter().map(CacheMessage::from).collect(),
+            index: index.cloned(),
+        };
+        self.changes
+            .lock()
+            .unwrap()
+            .push(Change::Update(key, entry));
+    }
+
+    /// Drop any cached diagnostics for `path`, for example because the file has been
+    /// modified by a fix.
+    pub(crate) fn remove(&self, path: &Path) {
+        if let Some(key) = file_key(path) {
+            self.changes.lock().unwrap().push(Change::Remove(key));
+        }
+    }
+
+    /// Apply all changes made during this run and write the cache back to disk. Entries
+    /// that haven't been used for a while are pruned.
+    pub(crate) fn persist(self) -> Result<()> {
+        let changes = self.changes.into_inner().unwrap();
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let mut package = self.package;
+        for change in changes {
+            match change {
+                Change::Touch(key) => {
+                    if let Some(entry) = package.files.get_mut(&key) {
+                        entry.last_seen = self.now;
+                    }
+                }
+                Change::Update(key, entry) => {
+                    package.files.insert(key, entry);
+                }
+                Change::Remove(key) => {
+                    package.files.remove(&key);
+                }
+            }
+        }
+
+        let max_age = MAX_LAST_SEEN.as_secs();
+        package
+            .files
+            .retain(|_, entry| self.now.saturating_sub(entry.last_seen) < max_age);
+
+        let version_dir = self.path.parent().context("Cache file has no parent")?;
+        if let Some(cache_dir) = version_dir.parent() {
+            init_cache_dir(cache_dir)?;
+        }
+        fs::create_dir_all(version_dir)
+            .with_context(|| format!("Failed to create {}", version_dir.display()))?;
+
+        // Write to a temporary file first so that concurrent runs never see a partially
+        // written cache.
+        let temp_path = self
+            .path
+            .with_extension(format!("{}.tmp", std::process::id()));
+        {
+            let mut writer = BufWriter::new(
+                File::create(&temp_path)
+                    .with_context(|| format!("Failed to create {}", temp_path.display()))?,
+            );
+            serde_json::to_writer(&mut writer, &package)?;
+            writer.flush()?;
+        }
+        fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Create the cache directory, marking it as one that shouldn't be committed or backed up.
+pub(crate) fn init_cache_dir(cache_dir: &Path) -> Result<()> {
+    fs::create_dir_all(cache_dir)
+        .with_context(|| format!("Failed to create cache directory {}", cache_dir.display()))?;
+
+    // Add a .gitignore so the cache isn't accidentally committed.
+    let gitignore = cache_dir.join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(&gitignore, "# Automatically created by fortitude.\n*\n")?;
+    }
+
+    // Add a CACHEDIR.TAG so backup tools skip the cache.
+    // See https://bford.info/cachedir/
+    let tag = cache_dir.join("CACHEDIR.TAG");
+    if !tag.exists() {
+        fs::write(&tag, "Signature: 8a477f597d28d172789f06886806bc55")?;
+    }
+    Ok(())
+}
+
+fn read_package(path: &Path) -> Result<PackageCache> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(PackageCache::default()),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+/// Key used to identify a file in the cache.
+fn file_key(path: &Path) -> Option<String> {
+    std::path::absolute(path)
+        .ok()
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+fn content_hash(file: &SourceFile) -> u64 {
+    let mut hasher = CacheKeyHasher::new();
+    file.source_text().cache_key(&mut hasher);
+    hasher.finish()
+}
+
+enum Change {
+    /// The cached entry was reused.
+    Touch(String),
+    /// The file was checked and its entry should be replaced.
+    Update(String, FileCache),
+    /// The entry is no longer valid.
+    Remove(String),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PackageCache {
+    files: FxHashMap<String, FileCache>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileCache {
+    content_hash: u64,
+    last_seen: u64,
+    messages: Vec<CacheMessage>,
+    /// Only stored when project rules are enabled.
+    #[serde(default)]
+    index: Option<FileIndex>,
+}
+
+/// A [`DiagnosticMessage`] without its source file, which is restored on lookup.
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheMessage {
+    kind: DiagnosticKind,
+    range: TextRange,
+    fix: Option<Fix>,
+    #[serde(default)]
+    custom_rule: Option<CustomRuleId>,
+}
+
+impl CacheMessage {
+    fn to_message(&self, file: &SourceFile) -> DiagnosticMessage {
+        let mut diagnostic = Diagnostic::new(self.kind.clone(), self.range);
+        if let Some(fix) = &self.fix {
+            diagnostic.set_fix(fix.clone());
+        }
+        let mut message = DiagnosticMessage::from_ruff(file, diagnostic);
+        message.set_custom_rule(self.custom_rule.clone());
+        message
+    }
+}
+
+impl From<&DiagnosticMessage> for CacheMessage {
+    fn from(message: &DiagnosticMessage) -> Self {
+        Self {
+            kind: message.kind().clone(),
+            range: message.range(),
+            fix: message.fix().cloned(),
+            custom_rule: message.custom_rule().cloned(),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! The lines changed since a git revision, used by `--diff-base` to only report
+//! violations in new or modified code
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::fs::normalize_path;
+use crate::message::DiagnosticMessage;
+
+/// The lines of each file that have been added or modified since a git revision,
+/// as ranges of one-indexed line numbers.
+#[derive(Debug, Default)]
+pub(crate) struct ChangedLines {
+    files: FxHashMap<PathBuf, Vec<Range<usize>>>,
+}
+
+impl ChangedLines {
+    /// Compare the working tree of the git repository containing `root` against
+    /// `revision`. Files that aren't tracked by git, and aren't ignored, are
+    /// treated as entirely new.
+    pub(crate) fn from_git(revision: &str, root: &Path) -> Result<Self> {
+        // `--relative` gives paths relative to `root`, rather than to the top of the
+        // repository
+        let diff = git(
+            root,
+            &[
+                "diff",
+                "--no-color",
+                "--no-ext-diff",
+                "--no-prefix",
+                "--relative",
+                "--unified=0",
+                revision,
+                "--",
+            ],
+        )?;
+        let mut changed = Self::from_diff(&diff, root);
+
+        let untracked = git(root, &["ls-files", "--others", "--exclude-standard"])?;
+        for path in untracked.lines().filter(|line| !line.is_empty()) {
+            changed
+                .files
+                .insert(normalize_path(root.join(path)), vec![1..usize::MAX]);
+        }
+        Ok(changed)
+    }
+
+    /// Read the changed lines from the output of `git diff --no-prefix --unified=0`,
+    /// with paths relative to `root`.
+    fn from_diff(diff: &str, root: &Path) -> Self {
+        let mut files: FxHashMap<PathBuf, Vec<Range<usize>>> = FxHashMap::default();
+        let mut current = None;
+        let mut previous = "";
+        for line in diff.lines() {
+            // Added lines can also start with `+++`, but only follow other lines of the
+            // hunk, rather than the name of the old file
+            let new_file = line
+                .strip_prefix("+++ ")
+                .filter(|_| previous.starts_with("--- "));
+            previous = line;
+            if let Some(path) = new_file {
+                // Names containing spaces are followed by a tab
+                let path = path.trim_end_matches('\t');
+                // Deleted files have no lines left to check
+                current = (path != "/dev/null").then(|| {
+                    let path = normalize_path(root.join(path));
+                    files.entry(path.clone()).or_default();
+                    path
+                });
+            } else if let Some(header) = line.strip_prefix("@@ ") {
+                let (Some(path), Some(lines)) = (&current, added_lines(header)) else {
+                    continue;
+                };
+                if !lines.is_empty() {
+                    files.entry(path.clone()).or_default().push(lines);
+                }
+            }
+        }
+        Self { files }
+    }
+
+    /// The files with any changes, including those with only deleted lines
+    pub(crate) fn files(&self) -> FxHashSet<PathBuf> {
+        self.files.keys().cloned().collect()
+    }
+
+    /// Does the violation overlap any changed lines?
+    pub(crate) fn contains(&self, message: &DiagnosticMessage) -> bool {
+        let Some(ranges) = self.files.get(&normalize_path(message.filename())) else {
+            return false;
+        };
+        let start = message.compute_start_location().row.get();
+        let end = message.compute_end_location().row.get();
+        ranges
+            .iter()
+            .any(|range| range.start <= end && start < range.end)
+    }
+}
+
+/// The lines added by a hunk, from a header like `@@ -10,2 +12,3 @@`. The count is
+/// omitted for a single line, and is zero if lines were only deleted.
+fn added_lines(header: &str) -> Option<Range<usize>> {
+    let added = header
+        .split_whitespace()
+        .find_map(|s| s.strip_prefix('+'))?;
+    let (start, count) = match added.split_once(',') {
+        Some((start, count)) => (start.parse().ok()?, count.parse().ok()?),
+        None => (added.parse().ok()?, 1),
+    };
+    Some(start..start + count)
+}
+
+/// Run a git command in `dir`, returning its output
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-c")
+        .arg("core.quotePath=false")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("Failed to run `git`")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{added_lines, ChangedLines};
+    use crate::fs::normalize_path;
+
+    #[test]
+    fn hunk_headers() {
+        assert_eq!(added_lines("-10,2 +12,3 @@"), Some(12..15));
+        assert_eq!(added_lines("-10 +12 @@ subroutine foo"), Some(12..13));
+        assert_eq!(added_lines("-10,2 +9,0 @@"), Some(9..9));
+        assert_eq!(added_lines("-10,2 @@"), None);
+    }
+
+    #[test]
+    fn parse_diff() {
+        let diff = r#"diff --git src/a.f90 src/a.f90
+index 1234567..89abcde 100644
+--- src/a.f90
++++ src/a.f90
+@@ -3 +3 @@ program a
+-  x = 1
++  x = 2
+@@ -10,2 +9,0 @@ program a
+-  print *, x
+-  print *, y
+diff --git b.f90 b.f90
+deleted file mode 100644
+index 1234567..0000000
+--- b.f90
++++ /dev/null
+@@ -1,2 +0,0 @@
+-program b
+-end program b
+diff --git c.f90 c.f90
+new file mode 100644
+index 0000000..1234567
+--- /dev/null
++++ c.f90
+@@ -0,0 +1,2 @@
++program c
++end program c
+"#;
+        let root = Path::new("/project");
+        let changed = ChangedLines::from_diff(diff, root);
+        let mut files: Vec<_> = changed.files().into_iter().collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                normalize_path("/project/c.f90"),
+                normalize_path("/project/src/a.f90")
+            ]
+        );
+        assert_eq!(
+            changed.files[&normalize_path("/project/src/a.f90")],
+            vec![3..4]
+        );
+        assert_eq!(changed.files[&normalize_path("/project/c.f90")], vec![1..3]);
+    }
+}
@@ -1,24 +1,38 @@
-use crate::allow_comments::{check_allow_comments, gather_allow_comments};
+use crate::add_allow::add_allow;
+use crate::allow_comments::{
+    check_allow_comments, gather_allow_comments, resolve_disabled_regions,
+};
 use crate::ast::FortitudeNode;
+use crate::baseline::{write_fixed_entries, Baseline};
+use crate::cache::Cache;
+use crate::changed_lines::ChangedLines;
 use crate::cli::{CheckArgs, GlobalConfigArgs};
 use crate::configuration::{self, parse_config_file, Configuration};
-use crate::diagnostics::{Diagnostics, FixMap};
+use crate::diagnostics::{Diagnostics, FixMap, Violation};
 use crate::fix::{fix_file, FixResult};
+use crate::fixed_form::{continuation_breaks, fix_spans_continuation, is_fixed_form, to_free_form};
 use crate::fs::get_files;
-use crate::message::DiagnosticMessage;
-use crate::printer::{Flags as PrinterFlags, Printer};
+use crate::logging::LogLevel;
+use crate::message::{DiagnosticMessage, SourceDiff};
+use crate::preprocessor::active_source;
+use crate::printer::{create_output_file, Flags as PrinterFlags, Printer};
+use crate::project::{check_project, project_rule_codes, FileIndex, IndexedFile, PROJECT_RULES};
 use crate::registry::AsRule;
+use crate::resolver::{ResolvedSettings, Resolver};
 use crate::rule_table::RuleTable;
 use crate::rules::error::syntax_error::SyntaxError;
 #[cfg(any(feature = "test-rules", test))]
 use crate::rules::testing::test_rules::{self, TestRule, TEST_RULES};
 use crate::rules::Rule;
 use crate::rules::{error::ioerror::IoError, AstRuleEnum, PathRuleEnum, TextRuleEnum};
+use crate::semantic::LazySemanticModel;
 use crate::settings::{self, CheckSettings, FixMode, ProgressBar, Settings};
+use crate::severity::Severity;
 use crate::show_files::show_files;
 use crate::show_settings::show_settings;
 use crate::stdin::read_from_stdin;
-use crate::{fs, locator::Locator, warn_user_once};
+use crate::watch::watch;
+use crate::{fs, locator::Locator, warn_user, warn_user_once};
 use crate::{warn_user_once_by_message, FromAstNode};
 
 use anyhow::{anyhow, Context, Result};
@@ -43,7 +57,7 @@ use std::time::Instant;
 use tree_sitter::{Parser, Tree};
 
 /// Returns true if the command should read from standard input.
-fn is_stdin(files: &[PathBuf], stdin_filename: Option<&Path>) -> bool {
+pub(crate) fn is_stdin(files: &[PathBuf], stdin_filename: Option<&Path>) -> bool {
     // If the user provided a `--stdin-filename`, always read from standard input.
     if stdin_filename.is_some() {
         if let Some(file) = files.iter().find(|file| file.as_path() != Path::new("-")) {
@@ -62,7 +76,8 @@ fn is_stdin(files: &[PathBuf], stdin_filename: Option<&Path>) -> bool {
     file == Path::new("-")
 }
 
-/// Parse a file, check it for issues, and return the report.
+/// Parse a file, check it for issues, and return the report. If `index_project` is
+/// set, the checked file is also indexed for project rules.
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn check_file(
     rules: &RuleTable,
@@ -74,12 +89,36 @@ pub(crate) fn check_file(
     settings: &Settings,
     fix_mode: FixMode,
     ignore_allow_comments: settings::IgnoreAllowComments,
+    index_project: bool,
 ) -> anyhow::Result<Diagnostics> {
-    let (mut messages, fixed) = if matches!(fix_mode, FixMode::Apply | FixMode::Diff) {
+    let check_only = || -> anyhow::Result<(Vec<DiagnosticMessage>, Tree)> {
+        let tree = parse_file(path, file, settings)?;
+        let violations = check_path(
+            rules,
+            path_rules,
+            text_rules,
+            ast_entrypoints,
+            path,
+            file,
+            settings,
+            &tree,
+            ignore_allow_comments,
+        );
+        let messages = violations
+            .into_iter()
+            .map(|v| DiagnosticMessage::from_violation(file, v))
+            .collect_vec();
+        Ok((messages, tree))
+    };
+
+    let mut diffs = Vec::new();
+    let (mut messages, fixed, checked, tree) = if matches!(fix_mode, FixMode::Apply | FixMode::Diff)
+    {
         if let Ok(FixerResult {
             result,
             transformed,
             fixed,
+            tree,
         }) = check_and_fix_file(
             rules,
             path_rules,
@@ -96,45 +135,54 @@ pub(crate) fn check_file(
                         let mut out_file = File::create(path)?;
                         out_file.write_all(transformed.source_text().as_bytes())?;
                     }
-                    // TODO: diff
-                    FixMode::Diff => {}
+                    FixMode::Diff => {
+                        // Diffs are printed once all files have been checked, so that
+                        // they're in a consistent order
+                        diffs.push(SourceDiff::new(
+                            path,
+                            file.source_text(),
+                            transformed.source_text(),
+                        ));
+                    }
                     FixMode::Generate => {}
                 }
             }
 
-            (result, fixed)
+            (result, fixed, transformed.into_owned(), tree)
         } else {
             // Failed to fix, so just lint the original source
-            let result = check_only_file(
-                rules,
-                path_rules,
-                text_rules,
-                ast_entrypoints,
-                path,
-                file,
-                settings,
-                ignore_allow_comments,
-            )?;
-            let fixed = FxHashMap::default();
-            (result, fixed)
+            let (result, tree) = check_only()?;
+            (result, FxHashMap::default(), file.clone(), tree)
         }
     } else {
-        let result = check_only_file(
-            rules,
-            path_rules,
-            text_rules,
-            ast_entrypoints,
-            path,
-            file,
-            settings,
-            ignore_allow_comments,
-        )?;
-        let fixed = FxHashMap::default();
-        (result, fixed)
+        let (result, tree) = check_only()?;
+        (result, FxHashMap::default(), file.clone(), tree)
     };
 
-    // Ignore based on per-file-ignores.
-    // If the DiagnosticMessage is discarded, its fix will also be ignored.
+    apply_per_file_ignores(&mut messages, path, settings);
+
+    let indexed = if index_project {
+        let index = FileIndex::new(&tree, &checked);
+        vec![IndexedFile::new(path, checked, index)]
+    } else {
+        Vec::new()
+    };
+
+    Ok(Diagnostics {
+        messages,
+        fixed: FixMap::from_iter([(fs::relativize_path(path), fixed)]),
+        diffs,
+        indexed,
+    })
+}
+
+/// Ignore messages based on per-file-ignores.
+/// If the DiagnosticMessage is discarded, its fix will also be ignored.
+pub(crate) fn apply_per_file_ignores(
+    messages: &mut Vec<DiagnosticMessage>,
+    path: &Path,
+    settings: &Settings,
+) {
     let per_file_ignores = &settings.check.per_file_ignores;
     let per_file_ignores = if !messages.is_empty() && !per_file_ignores.is_empty() {
         fs::ignores_from_path(path, per_file_ignores)
@@ -150,11 +198,19 @@ pub(crate) fn check_file(
             }
         });
     }
+}
 
-    Ok(Diagnostics {
-        messages,
-        fixed: FixMap::from_iter([(fs::relativize_path(path), fixed)]),
-    })
+/// Set the severity of each message from the settings for the file it was found in.
+pub(crate) fn apply_severity(messages: &mut [DiagnosticMessage], resolver: &Resolver) {
+    for message in messages {
+        let severity = resolver
+            .resolve(Path::new(message.filename()))
+            .settings
+            .check
+            .severity
+            .get(message);
+        message.set_severity(severity);
+    }
 }
 
 /// Parse a file, check it for issues, and return the report.
@@ -169,13 +225,7 @@ pub(crate) fn check_only_file(
     settings: &Settings,
     ignore_allow_comments: settings::IgnoreAllowComments,
 ) -> anyhow::Result<Vec<DiagnosticMessage>> {
-    let mut parser = Parser::new();
-    parser
-        .set_language(&tree_sitter_fortran::LANGUAGE.into())
-        .context("Error loading Fortran grammar")?;
-    let tree = parser
-        .parse(file.source_text(), None)
-        .context("Failed to parse")?;
+    let tree = parse_file(path, file, settings)?;
 
     let violations = check_path(
         rules,
@@ -191,10 +241,40 @@ pub(crate) fn check_only_file(
 
     Ok(violations
         .into_iter()
-        .map(|v| DiagnosticMessage::from_ruff(file, v))
+        .map(|v| DiagnosticMessage::from_violation(file, v))
         .collect_vec())
 }
 
+/// Parse the file at `path` into a syntax tree.
+fn parse_file(path: &Path, file: &SourceFile, settings: &Settings) -> anyhow::Result<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_fortran::LANGUAGE.into())
+        .context("Error loading Fortran grammar")?;
+    parser
+        .parse(
+            parse_source(path, file.source_text(), &settings.check).as_ref(),
+            None,
+        )
+        .context("Failed to parse")
+}
+
+/// The text that should be parsed for the file at `path`. Preprocessing and conversion
+/// from fixed-form keep every byte at the same offset, so the resulting tree can be
+/// used with the original source.
+pub(crate) fn parse_source<'a>(
+    path: &Path,
+    source: &'a str,
+    settings: &CheckSettings,
+) -> Cow<'a, str> {
+    let source = active_source(source, settings);
+    if is_fixed_form(path, settings) {
+        Cow::Owned(to_free_form(&source))
+    } else {
+        source
+    }
+}
+
 /// Check an already parsed file. This actually does all the checking,
 /// `check_only_file`/`check_and_fix_file` wrap this
 #[allow(clippy::too_many_arguments)]
@@ -208,34 +288,35 @@ pub(crate) fn check_path(
     settings: &Settings,
     tree: &Tree,
     ignore_allow_comments: settings::IgnoreAllowComments,
-) -> Vec<Diagnostic> {
-    let mut violations = Vec::new();
+) -> Vec<Violation> {
+    let mut diagnostics = Vec::new();
     let mut allow_comments = Vec::new();
 
     // Check file paths directly
     for rule in path_rules {
         if let Some(violation) = rule.check(settings, path) {
-            violations.push(violation);
+            diagnostics.push(violation);
         }
     }
 
     // Perform plain text analysis
     for rule in text_rules {
-        violations.extend(rule.check(settings, file));
+        diagnostics.extend(rule.check(settings, file));
     }
 
     // Perform AST analysis
     let root = tree.root_node();
+    let semantic = LazySemanticModel::new(tree, file.source_text());
     for node in once(root).chain(root.descendants()) {
         if rules.enabled(Rule::SyntaxError) && node.is_missing() {
-            violations.push(Diagnostic::from_node(SyntaxError {}, &node));
+            diagnostics.push(Diagnostic::from_node(SyntaxError {}, &node));
         }
 
         if let Some(rules) = ast_entrypoints.get(node.kind()) {
             for rule in rules {
-                if let Some(violation) = rule.check(settings, &node, file) {
+                if let Some(violation) = rule.check(settings, &node, file, &semantic) {
                     for v in violation {
-                        violations.push(v);
+                        diagnostics.push(v);
                     }
                 }
             }
@@ -245,6 +326,16 @@ pub(crate) fn check_path(
         };
     }
 
+    let mut violations = diagnostics.into_iter().map(Violation::from).collect_vec();
+
+    // Run custom rules from the config file and plugins over the whole tree
+    violations.extend(
+        settings
+            .check
+            .custom_rules
+            .check(settings, path, &root, file),
+    );
+
     // Raise violations for internal test rules
     #[cfg(any(feature = "test-rules", test))]
     {
@@ -272,7 +363,23 @@ pub(crate) fn check_path(
                 _ => unreachable!("All test rules must have an implementation"),
             };
             if let Some(diagnostic) = diagnostic {
-                violations.push(diagnostic);
+                violations.push(diagnostic.into());
+            }
+        }
+    }
+
+    // Fixes across continuation lines in fixed-form files would be written with the
+    // layout of the free-form text that was parsed, so they're skipped
+    if is_fixed_form(path, &settings.check) {
+        let breaks = continuation_breaks(file.source_text());
+        for violation in &mut violations {
+            let diagnostic = &mut violation.diagnostic;
+            if diagnostic
+                .fix
+                .as_ref()
+                .is_some_and(|fix| fix_spans_continuation(fix, &breaks))
+            {
+                diagnostic.fix = None;
             }
         }
     }
@@ -286,7 +393,14 @@ pub(crate) fn check_path(
             Rule::DisabledAllowComment,
         ])
     {
-        let ignored = check_allow_comments(&mut violations, &allow_comments, rules, file);
+        resolve_disabled_regions(&mut allow_comments);
+        let ignored = check_allow_comments(
+            &mut violations,
+            &allow_comments,
+            rules,
+            &settings.check.custom_rules,
+            file,
+        );
         if ignore_allow_comments.is_disabled() {
             for index in ignored.iter().rev() {
                 violations.swap_remove(*index);
@@ -302,19 +416,22 @@ pub(crate) fn check_path(
             path.to_string_lossy()
         );
         // Sort by byte-offset in the file
-        violations.sort_by_key(|diagnostic| diagnostic.range.start());
+        violations.sort_by_key(|violation| violation.diagnostic.range.start());
         // Retain all violations up to the first syntax error, inclusive.
         // Text and path rules can be safely retained.
         let syntax_error_idx = violations
             .iter()
-            .position(|diagnostic| diagnostic.kind.rule() == Rule::SyntaxError);
+            .position(|violation| violation.diagnostic.kind.rule() == Rule::SyntaxError);
         if let Some(syntax_error_idx) = syntax_error_idx {
             violations = violations
                 .into_iter()
                 .enumerate()
-                .filter_map(|(idx, diagnostic)| {
-                    if idx <= syntax_error_idx || !diagnostic.kind.rule().is_ast_rule() {
-                        Some(diagnostic)
+                .filter_map(|(idx, violation)| {
+                    let rule = violation.diagnostic.kind.rule();
+                    // Custom rules are also derived from the AST
+                    let from_ast = rule.is_ast_rule() || rule == Rule::CustomRule;
+                    if idx <= syntax_error_idx || !from_ast {
+                        Some(violation)
                     } else {
                         None
                     }
@@ -328,15 +445,17 @@ pub(crate) fn check_path(
 
 const MAX_ITERATIONS: usize = 100;
 
-pub type FixTable = FxHashMap<Rule, usize>;
+pub use crate::fix::{FixTable, FixedRule};
 
 pub struct FixerResult<'a> {
     /// The result returned by the linter, after applying any fixes.
     pub result: Vec<DiagnosticMessage>,
     /// The resulting source code, after applying any fixes.
     pub transformed: Cow<'a, SourceFile>,
-    /// The number of fixes applied for each [`Rule`].
+    /// The number of fixes applied for each rule.
     pub fixed: FixTable,
+    /// The syntax tree of the resulting source code.
+    pub tree: Tree,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -366,12 +485,15 @@ pub(crate) fn check_and_fix_file<'a>(
         .set_language(&tree_sitter_fortran::LANGUAGE.into())
         .context("Error loading Fortran grammar")?;
 
+    let mut tree = parser
+        .parse(
+            parse_source(path, file.source_text(), &settings.check).as_ref(),
+            None,
+        )
+        .context("Failed to parse")?;
+
     // Continuously fix until the source code stabilizes.
     loop {
-        let tree = parser
-            .parse(transformed.source_text(), None)
-            .context("Failed to parse")?;
-
         // Map row and column locations to byte slices (lazily).
         let locator = Locator::new(transformed.source_text());
 
@@ -399,7 +521,11 @@ pub(crate) fn check_and_fix_file<'a>(
                 ));
             }
         } else if is_valid_syntax && tree.root_node().has_error() {
-            report_fix_syntax_error(path, transformed.source_text(), fixed.keys().copied());
+            report_fix_syntax_error(
+                path,
+                transformed.source_text(),
+                fixed.keys().map(|rule| rule.code().to_string()),
+            );
             return Err(anyhow!("Fix introduced a syntax error"));
         }
 
@@ -407,6 +533,7 @@ pub(crate) fn check_and_fix_file<'a>(
         if let Some(FixResult {
             code: fixed_contents,
             fixes: applied,
+            input_edits,
             ..
         }) = fix_file(
             &violations,
@@ -422,6 +549,28 @@ pub(crate) fn check_and_fix_file<'a>(
 
                 transformed = Cow::Owned(fixed_contents);
 
+                // Only re-parse the regions that have changed. `parse_source` doesn't
+                // change any offsets, so the edits also apply to the text it returns.
+                // The exceptions are fixed-form source, where an edit can change how
+                // lines outside of it are converted, and preprocessed source, where an
+                // edit can change which branches of conditionals are blanked out. These
+                // are always parsed from scratch.
+                let old_tree = if is_fixed_form(path, &settings.check) || settings.check.preprocess
+                {
+                    None
+                } else {
+                    for edit in &input_edits {
+                        tree.edit(edit);
+                    }
+                    Some(&tree)
+                };
+                tree = parser
+                    .parse(
+                        parse_source(path, transformed.source_text(), &settings.check).as_ref(),
+                        old_tree,
+                    )
+                    .context("Failed to parse")?;
+
                 iterations += 1;
 
                 // Re-run the linter pass
@@ -434,26 +583,22 @@ pub(crate) fn check_and_fix_file<'a>(
         return Ok(FixerResult {
             result: violations
                 .into_iter()
-                .map(|v| DiagnosticMessage::from_ruff(&transformed, v))
+                .map(|v| DiagnosticMessage::from_violation(&transformed, v))
                 .collect_vec(),
             transformed,
             fixed,
+            tree,
         });
     }
 }
 
-fn collect_rule_codes(rules: impl IntoIterator<Item = Rule>) -> String {
-    rules
-        .into_iter()
-        .map(|rule| rule.noqa_code().to_string())
-        .sorted_unstable()
-        .dedup()
-        .join(", ")
+fn collect_rule_codes(codes: impl IntoIterator<Item = String>) -> String {
+    codes.into_iter().sorted_unstable().dedup().join(", ")
 }
 
 #[allow(clippy::print_stderr)]
-fn report_failed_to_converge_error(path: &Path, transformed: &str, diagnostics: &[Diagnostic]) {
-    let codes = collect_rule_codes(diagnostics.iter().map(|diagnostic| diagnostic.kind.rule()));
+fn report_failed_to_converge_error(path: &Path, transformed: &str, violations: &[Violation]) {
+    let codes = collect_rule_codes(violations.iter().map(Violation::code));
     if cfg!(debug_assertions) {
         eprintln!(
             "{}{} Failed to converge after {} iterations in `{}` with rule codes {}:---\n{}\n---",
@@ -485,9 +630,13 @@ This indicates a bug in fortitude. If you could open an issue at:
 }
 
 #[allow(clippy::print_stderr)]
-fn report_fix_syntax_error(path: &Path, transformed: &str, rules: impl IntoIterator<Item = Rule>) {
+fn report_fix_syntax_error(
+    path: &Path,
+    transformed: &str,
+    codes: impl IntoIterator<Item = String>,
+) {
     // TODO: include syntax error
-    let codes = collect_rule_codes(rules);
+    let codes = collect_rule_codes(codes);
     if cfg!(debug_assertions) {
         eprintln!(
             "{}{} Fix introduced a syntax error in `{}` with rule codes {codes}: \n---\n{transformed}\n---",
@@ -514,6 +663,31 @@ This indicates a bug in Fortitude. If you could open an issue at:
     }
 }
 
+/// Write a unified diff of the changes made to a file to stdout.
+pub(crate) fn write_diff(path: &Path, original: &str, transformed: &str) -> io::Result<()> {
+    write_renamed_diff(path, path, original, transformed)
+}
+
+/// Write a unified diff to stdout of a file that has been changed and moved to
+/// `new_path`.
+pub(crate) fn write_renamed_diff(
+    path: &Path,
+    new_path: &Path,
+    original: &str,
+    transformed: &str,
+) -> io::Result<()> {
+    write_diffs(&[SourceDiff::renamed(path, new_path, original, transformed)])
+}
+
+/// Write unified diffs to stdout, in the order given.
+pub(crate) fn write_diffs(diffs: &[SourceDiff]) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    for diff in diffs {
+        write!(stdout, "{diff}")?;
+    }
+    stdout.flush()
+}
+
 /// Wrapper around `std::fs::read_to_string` with some extra error
 /// checking.
 ///
@@ -624,6 +798,12 @@ impl CheckResults {
 
     fn sort(&mut self) {
         self.diagnostics.messages.par_sort_unstable();
+        self.diagnostics
+            .diffs
+            .sort_unstable_by(|a, b| a.path().cmp(b.path()));
+        self.diagnostics
+            .indexed
+            .sort_unstable_by(|a, b| a.path().cmp(b.path()));
     }
 }
 
@@ -639,8 +819,11 @@ enum CheckStatus {
     SkippedNoDiagnostic,
 }
 
-/// Check all files, report issues found, and return error code.
-pub fn check(args: CheckArgs, global_options: &GlobalConfigArgs) -> Result<ExitCode> {
+/// Find and read any config file, and combine it with the command line arguments.
+pub(crate) fn resolve_settings(
+    args: &CheckArgs,
+    global_options: &GlobalConfigArgs,
+) -> Result<Settings> {
     // First we need to find and read any config file
     let project_root = configuration::project_root(path_absolutize::path_dedot::CWD.as_path())?;
     let file_configuration = Configuration::from_options(
@@ -650,18 +833,68 @@ pub fn check(args: CheckArgs, global_options: &GlobalConfigArgs) -> Result<ExitC
 
     // Now, we can override settings from the config file with options
     // from the CLI
-    let settings = file_configuration.into_settings(&project_root, &args)?;
+    file_configuration.into_settings(&project_root, args)
+}
+
+/// Determine how fixes should be handled.
+pub(crate) fn fix_mode(args: &CheckArgs, settings: &CheckSettings) -> FixMode {
+    // Fix rules are as follows:
+    // - By default, generate all fixes, but don't apply them to the filesystem.
+    // - If `--fix` or `--fix-only` is set, apply applicable fixes to the filesystem (or
+    //   print them to stdout, if we're reading from stdin).
+    // - If `--diff` or `--fix-only` are set, don't print any violations (only applicable fixes)
+    // - By default, applicable fixes only include [`Applicablility::Automatic`], but if
+    //   `--unsafe-fixes` is set, then [`Applicablility::Suggested`] fixes are included.
+    if args.diff {
+        FixMode::Diff
+    } else if settings.fix || settings.fix_only {
+        FixMode::Apply
+    } else {
+        FixMode::Generate
+    }
+}
+
+/// Create the printer used to report the results of a check.
+pub(crate) fn printer(
+    args: &CheckArgs,
+    settings: &CheckSettings,
+    fix_mode: FixMode,
+    global_options: &GlobalConfigArgs,
+) -> Printer {
+    let mut printer_flags = PrinterFlags::empty();
+    if !(args.diff || settings.fix_only) {
+        printer_flags |= PrinterFlags::SHOW_VIOLATIONS;
+    }
+    if settings.show_fixes {
+        printer_flags |= PrinterFlags::SHOW_FIX_SUMMARY;
+    }
+
+    Printer::new(
+        settings.output_format,
+        global_options.log_level(),
+        printer_flags,
+        fix_mode,
+        settings.unsafe_fixes,
+    )
+}
 
-    let stdin_filename = args.stdin_filename;
+/// Check all files, report issues found, and return error code.
+pub fn check(args: CheckArgs, global_options: &GlobalConfigArgs) -> Result<ExitCode> {
+    if args.watch {
+        return watch(&args, global_options);
+    }
+
+    let mut resolver = Resolver::new(&args, global_options)?;
+    let settings = &resolver.root().settings;
+    let fix_mode = fix_mode(&args, &settings.check);
+    let printer = printer(&args, &settings.check, fix_mode, global_options);
+
+    let stdin_filename = args.stdin_filename.map(fs::normalize_path);
 
     let mut writer: Box<dyn Write> = match args.output_file {
         Some(path) => {
             colored::control::set_override(false);
-            if let Some(parent) = path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
-            let file = File::create(path)?;
-            Box::new(BufWriter::new(file))
+            Box::new(create_output_file(&path)?)
         }
         _ => Box::new(BufWriter::new(io::stdout())),
     };
@@ -669,111 +902,183 @@ pub fn check(args: CheckArgs, global_options: &GlobalConfigArgs) -> Result<ExitC
 
     let is_stdin = is_stdin(&args.files.unwrap_or_default(), stdin_filename.as_deref());
 
-    if args.show_settings {
-        show_settings(&settings, &mut writer)?;
-        return Ok(ExitCode::SUCCESS);
-    }
+    let changed_lines = match &args.diff_base {
+        Some(_) if is_stdin => {
+            return Err(anyhow!("`--diff-base` can't be used with standard input"));
+        }
+        Some(revision) => Some(ChangedLines::from_git(
+            revision,
+            &settings.check.project_root,
+        )?),
+        None => None,
+    };
+    let changed_files = changed_lines.as_ref().map(ChangedLines::files);
 
     if args.show_files {
-        show_files(&settings.file_resolver, is_stdin, &mut writer)?;
+        show_files(
+            &settings.file_resolver,
+            is_stdin,
+            changed_files.as_ref(),
+            &mut writer,
+        )?;
         return Ok(ExitCode::SUCCESS);
     }
 
-    let CheckSettings {
-        fix,
-        fix_only,
-        ref rules,
-        unsafe_fixes,
-        show_fixes,
-        output_format,
-        ignore_allow_comments,
-        ..
-    } = settings.check;
-
-    // Fix rules are as follows:
-    // - By default, generate all fixes, but don't apply them to the filesystem.
-    // - If `--fix` or `--fix-only` is set, apply applicable fixes to the filesystem (or
-    //   print them to stdout, if we're reading from stdin).
-    // - If `--diff` or `--fix-only` are set, don't print any violations (only applicable fixes)
-    // - By default, applicable fixes only include [`Applicablility::Automatic`], but if
-    //   `--unsafe-fixes` is set, then [`Applicablility::Suggested`] fixes are included.
+    let start = Instant::now();
 
-    let fix_mode = if fix || fix_only {
-        FixMode::Apply
-    } else {
-        FixMode::Generate
-    };
+    let files = get_files(&settings.file_resolver, is_stdin, changed_files.as_ref())?;
+    debug!("Identified files to lint in: {:?}", start.elapsed());
+    resolver.discover(files.iter().chain(&stdin_filename))?;
+    let settings = &resolver.root().settings;
 
-    // At this point, we've assembled all our settings, and we're
-    // ready to check the project
+    if args.show_settings {
+        // Show the settings for the first file that would be checked
+        let path = stdin_filename
+            .as_ref()
+            .or(files.first())
+            .unwrap_or(&settings.check.project_root);
+        show_settings(path, resolver.resolve(path), &mut writer)?;
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    let path_rules = rules_to_path_rules(rules);
-    let text_rules = rules_to_text_rules(rules);
-    let ast_entrypoints = ast_entrypoint_map(rules);
+    if args.add_allow && is_stdin {
+        return Err(anyhow!("`--add-allow` can't be used with standard input"));
+    }
 
-    let start = Instant::now();
+    if args.show_fixed_baseline && settings.check.baseline.is_none() {
+        return Err(anyhow!(
+            "No baseline file set. Use the `baseline` setting to specify one."
+        ));
+    }
 
-    let files = get_files(&settings.file_resolver, is_stdin)?;
-    debug!("Identified files to lint in: {:?}", start.elapsed());
+    // At this point, we've assembled all our settings, and we're
+    // ready to check the project
 
-    let results = if is_stdin {
+    let mut results = if is_stdin {
+        let path = stdin_filename.as_deref();
         check_stdin(
-            stdin_filename.map(fs::normalize_path).as_deref(),
-            rules,
-            &path_rules,
-            &text_rules,
-            &ast_entrypoints,
-            &settings,
+            path,
+            resolver.resolve(path.unwrap_or(Path::new("-"))),
             fix_mode,
-            ignore_allow_comments,
         )?
     } else {
-        check_files(
-            &files,
-            rules,
-            &path_rules,
-            &text_rules,
-            &ast_entrypoints,
-            &settings,
-            fix_mode,
-            ignore_allow_comments,
-        )?
+        let cache = (!args.no_cache).then(|| Cache::open(&settings.check.cache_dir, &resolver));
+        let mut index_project = resolver
+            .settings()
+            .any(|settings| settings.check.rules.any_enabled(PROJECT_RULES));
+        // Only changed files are checked, so modules defined and used elsewhere in the
+        // project would be missing
+        if index_project && changed_lines.is_some() {
+            warn_user!(
+                "Project rules ({}) aren't checked with `--diff-base`",
+                project_rule_codes()
+            );
+            index_project = false;
+        }
+        let mut results = check_files(&files, &resolver, fix_mode, cache.as_ref(), index_project)?;
+        if let Some(cache) = cache {
+            if let Err(err) = cache.persist() {
+                warn_user!("Failed to write cache: {err:#}");
+            }
+        }
+        if index_project {
+            let start = Instant::now();
+            let indexed = std::mem::take(&mut results.diagnostics.indexed);
+            results
+                .diagnostics
+                .messages
+                .extend(check_project(&indexed, |path| {
+                    &resolver.resolve(path).settings
+                }));
+            results.sort();
+            debug!("Checked project in: {:?}", start.elapsed());
+        }
+        results
     };
+    write_diffs(&results.diagnostics.diffs)?;
+    apply_severity(&mut results.diagnostics.messages, &resolver);
+
+    // Only report violations on lines that have changed
+    if let Some(changed_lines) = &changed_lines {
+        results
+            .diagnostics
+            .messages
+            .retain(|message| changed_lines.contains(message));
+    }
+
+    if args.add_allow {
+        let changed = add_allow(&results.diagnostics.messages, &resolver)?;
+        if global_options.log_level() >= LogLevel::Default {
+            writeln!(
+                io::stderr().lock(),
+                "Added or updated {changed} allow comment(s)"
+            )?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(path) = &args.generate_baseline {
+        let baseline =
+            Baseline::from_messages(&results.diagnostics.messages, &settings.check.project_root);
+        baseline.write(path)?;
+        if global_options.log_level() >= LogLevel::Default {
+            writeln!(
+                io::stderr().lock(),
+                "Wrote {} violation(s) to baseline file {}",
+                baseline.len(),
+                fs::relativize_path(path).bold()
+            )?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Only report violations that aren't already in the baseline
+    if let Some(path) = &settings.check.baseline {
+        let baseline = Baseline::read(path, &settings.check.project_root)?;
+        let fixed = baseline.apply(&mut results.diagnostics.messages, &files);
+        if args.show_fixed_baseline {
+            write_fixed_entries(&fixed, &mut writer)?;
+            writer.flush()?;
+            return Ok(if fixed.is_empty() || args.exit_zero {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            });
+        }
+    }
 
     // Always try to print violations (though the printer itself may suppress output)
-    // If we're writing fixes via stdin, the transformed source code goes to the writer
-    // so send the summary to stderr instead
-    let mut summary_writer = if is_stdin && matches!(fix_mode, FixMode::Apply | FixMode::Diff) {
+    // If we're writing fixes via stdin, or printing diffs, the transformed source code
+    // goes to stdout so send the summary to stderr instead
+    let mut summary_writer = if fix_mode.is_diff() || (is_stdin && fix_mode.is_apply()) {
         stderr_writer
     } else {
         writer
     };
 
-    let mut printer_flags = PrinterFlags::empty();
-    if !fix_only {
-        printer_flags |= PrinterFlags::SHOW_VIOLATIONS;
-    }
-    if show_fixes {
-        printer_flags |= PrinterFlags::SHOW_FIX_SUMMARY;
-    }
-
-    let printer = Printer::new(
-        output_format,
-        global_options.log_level(),
-        printer_flags,
-        fix_mode,
-        unsafe_fixes,
-    );
-
     if args.statistics {
         printer.write_statistics(&results, &mut summary_writer)?;
     } else {
         printer.write_once(&results, &mut summary_writer)?;
     }
+    printer.write_output_files(&results, &settings.check.output_files)?;
 
     let diagnostics = results.diagnostics;
+    // Only violations of at least this severity cause a non-zero exit status
+    let exit_on = args.exit_on.unwrap_or(Severity::Info);
+    let has_violations = diagnostics
+        .messages
+        .iter()
+        .any(|message| message.severity() >= exit_on);
     if !args.exit_zero {
-        if fix_only {
+        if args.diff {
+            // If we're printing a diff, we always want to exit non-zero if there are
+            // any fixable violations (since we've printed the diff, but not applied the
+            // fixes).
+            if !diagnostics.fixed.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        } else if settings.check.fix_only {
             // If we're only fixing, we want to exit zero (since we've fixed all fixable
             // violations), unless we're explicitly asked to exit non-zero on fix.
             if args.exit_non_zero_on_fix && !diagnostics.fixed.is_empty() {
@@ -784,10 +1089,10 @@ pub fn check(args: CheckArgs, global_options: &GlobalConfigArgs) -> Result<ExitC
             // there are any violations, unless we're explicitly asked to exit zero on
             // fix.
             if args.exit_non_zero_on_fix {
-                if !diagnostics.fixed.is_empty() || !diagnostics.messages.is_empty() {
+                if !diagnostics.fixed.is_empty() || has_violations {
                     return Ok(ExitCode::FAILURE);
                 }
-            } else if !diagnostics.messages.is_empty() {
+            } else if has_violations {
                 return Ok(ExitCode::FAILURE);
             }
         }
@@ -795,19 +1100,15 @@ pub fn check(args: CheckArgs, global_options: &GlobalConfigArgs) -> Result<ExitC
     Ok(ExitCode::SUCCESS)
 }
 
-#[allow(clippy::too_many_arguments)]
-fn check_files(
+pub(crate) fn check_files(
     files: &[PathBuf],
-    rules: &RuleTable,
-    path_rules: &Vec<PathRuleEnum>,
-    text_rules: &Vec<TextRuleEnum>,
-    ast_entrypoints: &BTreeMap<&str, Vec<AstRuleEnum>>,
-    settings: &Settings,
+    resolver: &Resolver,
     fix_mode: FixMode,
-    ignore_allow_comments: settings::IgnoreAllowComments,
+    cache: Option<&Cache>,
+    index_project: bool,
 ) -> Result<CheckResults> {
     let file_digits = files.len().to_string().len();
-    let progress_bar_style = match settings.check.progress_bar {
+    let progress_bar_style = match resolver.root().settings.check.progress_bar {
         ProgressBar::Fancy => {
             // Make progress bar with 60 char width, bright cyan colour (51)
             // Colours use some 8-bit representation
@@ -838,6 +1139,14 @@ fn check_files(
         .with_prefix("Checking file:")
         .map(|path| {
             let filename = path.to_string_lossy();
+            let ResolvedSettings {
+                settings,
+                path_rules,
+                text_rules,
+                ast_entrypoints,
+                ..
+            } = resolver.resolve(path);
+            let rules = &settings.check.rules;
 
             let source = match read_to_string(path) {
                 Ok(source) => source,
@@ -863,6 +1172,30 @@ fn check_files(
 
             let file = SourceFileBuilder::new(filename.as_ref(), source.as_str()).finish();
 
+            // Reuse cached results for unchanged files, unless we're fixing and there
+            // are fixes that would be applied, or the file needs indexing and the
+            // cache doesn't have it
+            if let Some((messages, index)) = cache.and_then(|cache| cache.get(path, &file)) {
+                let applicability = settings.check.unsafe_fixes.required_applicability();
+                let has_applicable_fix = messages
+                    .iter()
+                    .any(|message| message.fix().is_some_and(|fix| fix.applies(applicability)));
+                let has_index = index.is_some() || !index_project;
+                if (fix_mode.is_generate() || !has_applicable_fix) && has_index {
+                    let mut diagnostics = Diagnostics::new(messages);
+                    if index_project {
+                        diagnostics
+                            .indexed
+                            .extend(index.map(|index| IndexedFile::new(path, file.clone(), index)));
+                    }
+                    return if diagnostics.is_empty() && diagnostics.indexed.is_empty() {
+                        CheckStatus::Ok
+                    } else {
+                        CheckStatus::Violations(diagnostics)
+                    };
+                }
+            }
+
             match check_file(
                 rules,
                 path_rules,
@@ -872,10 +1205,22 @@ fn check_files(
                 &file,
                 settings,
                 fix_mode,
-                ignore_allow_comments,
+                settings.check.ignore_allow_comments,
+                index_project,
             ) {
                 Ok(violations) => {
-                    if violations.is_empty() {
+                    if let Some(cache) = cache {
+                        // Fixed files have been modified since they were read, so any
+                        // cached results are stale. When only showing a diff, the file
+                        // is untouched and its existing entry is still valid
+                        if violations.fixed.is_empty() {
+                            let index = violations.indexed.first().map(IndexedFile::index);
+                            cache.update(path, &file, &violations.messages, index);
+                        } else if fix_mode.is_apply() {
+                            cache.remove(path);
+                        }
+                    }
+                    if violations.is_empty() && violations.indexed.is_empty() {
                         CheckStatus::Ok
                     } else {
                         CheckStatus::Violations(violations)
@@ -915,27 +1260,32 @@ fn check_files(
     Ok(results)
 }
 
-#[allow(clippy::too_many_arguments)]
 fn check_stdin(
     filename: Option<&Path>,
-    rules: &RuleTable,
-    path_rules: &Vec<PathRuleEnum>,
-    text_rules: &Vec<TextRuleEnum>,
-    ast_entrypoints: &BTreeMap<&str, Vec<AstRuleEnum>>,
-    settings: &Settings,
+    resolved: &ResolvedSettings,
     fix_mode: FixMode,
-    ignore_allow_comments: settings::IgnoreAllowComments,
 ) -> Result<CheckResults> {
+    let ResolvedSettings {
+        settings,
+        path_rules,
+        text_rules,
+        ast_entrypoints,
+        ..
+    } = resolved;
+    let rules = &settings.check.rules;
+    let ignore_allow_comments = settings.check.ignore_allow_comments;
     let stdin = read_from_stdin()?;
 
     let path = filename.unwrap_or_else(|| Path::new("-"));
     let source_file = SourceFileBuilder::new(path.to_str().unwrap_or("-"), stdin.as_str()).finish();
 
+    let mut diffs = Vec::new();
     let (mut messages, fixed) = if matches!(fix_mode, FixMode::Apply | FixMode::Diff) {
         if let Ok(FixerResult {
             result,
             transformed,
             fixed,
+            ..
         }) = check_and_fix_file(
             rules,
             path_rules,
@@ -952,9 +1302,14 @@ fn check_stdin(
                     let out_file = &mut io::stdout().lock();
                     out_file.write_all(transformed.source_text().as_bytes())?;
                 }
-                // TODO: diff
                 FixMode::Diff => {
-                    warn_user_once_by_message!("Diff mode is not yet supported for stdin");
+                    if !fixed.is_empty() {
+                        diffs.push(SourceDiff::new(
+                            path,
+                            source_file.source_text(),
+                            transformed.source_text(),
+                        ));
+                    }
                 }
                 FixMode::Generate => {}
             }
@@ -1000,27 +1355,13 @@ fn check_stdin(
         (result, fixed)
     };
 
-    let per_file_ignores = &settings.check.per_file_ignores;
-    // Ignore based on per-file-ignores.
-    // If the DiagnosticMessage is discarded, its fix will also be ignored.
-    let per_file_ignores = if !messages.is_empty() && !per_file_ignores.is_empty() {
-        fs::ignores_from_path(path, per_file_ignores)
-    } else {
-        vec![]
-    };
-    if !per_file_ignores.is_empty() {
-        messages.retain(|message| {
-            if let Some(rule) = message.rule() {
-                !per_file_ignores.contains(&rule)
-            } else {
-                true
-            }
-        });
-    }
+    apply_per_file_ignores(&mut messages, path, settings);
 
     let diagnostics = Diagnostics {
         messages,
         fixed: FixMap::from_iter([(fs::relativize_path(path), fixed)]),
+        diffs,
+        indexed: Vec::new(),
     };
     Ok(CheckResults::from_stdin(diagnostics))
 }
@@ -0,0 +1,70 @@
+// Adapted from ruff
+// Copyright 2022 Charles Marsh
+// SPDX-License-Identifier: MIT
+
+use std::fs::remove_dir_all;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use ignore::WalkBuilder;
+use path_absolutize::path_dedot;
+
+use crate::cache::DEFAULT_CACHE_DIR;
+use crate::cli::GlobalConfigArgs;
+use crate::configuration::{self, parse_config_file, Configuration};
+use crate::fs;
+use crate::logging::LogLevel;
+
+/// Clear any caches in the current directory and any subdirectories, as well as the
+/// cache directory set in the configuration file or environment.
+pub fn clean(global_options: &GlobalConfigArgs) -> Result<ExitCode> {
+    let cwd = path_dedot::CWD.as_path();
+    let project_root = configuration::project_root(cwd)?;
+    let configuration = Configuration::from_options(
+        parse_config_file(&global_options.config_file)?,
+        &project_root,
+    );
+
+    let mut caches: Vec<PathBuf> = WalkBuilder::new(cwd)
+        .standard_filters(false)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_name() == DEFAULT_CACHE_DIR
+                && entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_dir())
+        })
+        .map(|entry| entry.into_path())
+        .collect();
+
+    let configured_cache = std::env::var_os("FORTITUDE_CACHE_DIR")
+        .map(fs::normalize_path)
+        .or(configuration.cache_dir);
+    if let Some(cache_dir) = configured_cache {
+        caches.push(cache_dir);
+    }
+
+    let mut stderr = BufWriter::new(io::stderr().lock());
+    for cache in caches {
+        remove_cache(&cache, global_options.log_level(), &mut stderr)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn remove_cache(cache: &Path, log_level: LogLevel, writer: &mut impl Write) -> Result<()> {
+    if !cache.is_dir() {
+        return Ok(());
+    }
+    if log_level >= LogLevel::Default {
+        writeln!(
+            writer,
+            "Removing cache at: {}",
+            fs::relativize_path(cache).bold()
+        )?;
+    }
+    remove_dir_all(cache).with_context(|| format!("Failed to remove {}", cache.display()))
+}
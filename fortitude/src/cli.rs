@@ -6,7 +6,8 @@ use crate::{
     fs::FilePattern,
     logging::LogLevel,
     rule_selector::RuleSelector,
-    settings::{OutputFormat, PatternPrefixPair, ProgressBar},
+    settings::{OutputTarget, PatternPrefixPair, ProgressBar},
+    severity::Severity,
     RuleSelectorParser,
 };
 
@@ -95,6 +96,14 @@ impl From<&LogLevelArgs> for LogLevel {
 pub enum SubCommands {
     Check(CheckArgs),
     Explain(ExplainArgs),
+    /// Clear any caches in the current directory and any subdirectories.
+    Clean,
+    /// Convert fixed-form files to free-form.
+    Convert(ConvertArgs),
+    /// Format files, normalising indentation, spacing, and keyword case.
+    Format(FormatArgs),
+    /// Run the language server.
+    Server,
     /// Generate shell completion.
     #[clap(hide = true)]
     GenerateShellCompletion {
@@ -121,6 +130,39 @@ pub struct ExplainArgs {
     pub rules: Vec<RuleSelector>,
 }
 
+/// Convert fixed-form files to free-form, and rename them with a free-form extension.
+#[derive(Debug, clap::Parser, Clone, PartialEq)]
+pub struct ConvertArgs {
+    /// List of files or directories to convert. Directories are searched recursively for
+    /// fixed-form files, as set by the `fixed-form-extensions` option.
+    #[arg(default_value = ".")]
+    pub files: Vec<PathBuf>,
+
+    /// Avoid writing any converted files; instead, output a diff for each file to stdout.
+    #[arg(long)]
+    pub diff: bool,
+}
+
+/// Format files, normalising indentation, spacing, and keyword case.
+#[derive(Debug, clap::Parser, Clone, PartialEq)]
+pub struct FormatArgs {
+    /// List of files or directories to format. Directories are searched recursively for
+    /// Fortran files.
+    #[arg(default_value = ".")]
+    pub files: Vec<PathBuf>,
+
+    /// Avoid writing any formatted files back; instead, exit with a non-zero status code
+    /// if any files would have been modified, and zero otherwise.
+    #[arg(long)]
+    pub check: bool,
+
+    /// Avoid writing any formatted files back; instead, exit with a non-zero status code
+    /// and the difference between the current file and how the formatted file would
+    /// look like.
+    #[arg(long)]
+    pub diff: bool,
+}
+
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
 pub enum HelpFormat {
     Text,
@@ -128,7 +170,7 @@ pub enum HelpFormat {
 }
 
 /// Perform static analysis on files and report issues.
-#[derive(Debug, clap::Parser, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Default, clap::Parser, Deserialize, Clone, PartialEq, Eq)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct CheckArgs {
     /// List of files or directories to check. Directories are searched recursively for
@@ -165,14 +207,19 @@ pub struct CheckArgs {
     #[clap(long, overrides_with("fix_only"), hide = true, action = SetTrue)]
     pub no_fix_only: Option<bool>,
 
+    /// Avoid writing any fixed files back; instead, output a diff for each changed file to stdout. Implies `--fix-only`.
+    #[arg(long, conflicts_with = "show_fixes")]
+    pub diff: bool,
+
     /// Ignore any `allow` comments.
     #[arg(long)]
     pub ignore_allow_comments: bool,
 
     /// Output serialization format for violations.
     /// The default serialization format is "full".
-    #[arg(long, value_enum, env = "FORTITUDE_OUTPUT_FORMAT")]
-    pub output_format: Option<OutputFormat>,
+    /// May be repeated, as `FORMAT:PATH`, to also write other formats to other files.
+    #[arg(long, value_name = "FORMAT[:PATH]", env = "FORTITUDE_OUTPUT_FORMAT")]
+    pub output_format: Option<Vec<OutputTarget>>,
 
     /// Specify file to write the linter output to (default: stdout).
     #[arg(short, long, env = "FORTITUDE_OUTPUT_FILE")]
@@ -205,6 +252,41 @@ pub struct CheckArgs {
     )]
     pub show_files: bool,
 
+    /// Run in watch mode by re-checking files whenever they change.
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["diff", "output_file", "show_settings", "show_files"]
+    )]
+    pub watch: bool,
+
+    /// Write all current violations to a baseline file. When set as the `baseline`
+    /// setting, violations in the baseline are not reported.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["fix", "fix_only", "diff", "watch", "show_fixed_baseline"]
+    )]
+    pub generate_baseline: Option<PathBuf>,
+
+    /// List entries in the baseline file that no longer match a violation, so that they
+    /// can be removed.
+    #[arg(long, conflicts_with_all = ["fix", "fix_only", "diff", "watch"])]
+    pub show_fixed_baseline: bool,
+
+    /// Add `allow` comments for every current violation, so that only new violations
+    /// are reported. Existing allow comments are extended rather than duplicated.
+    #[arg(
+        long,
+        conflicts_with_all = ["fix", "fix_only", "diff", "watch", "generate_baseline", "show_fixed_baseline"]
+    )]
+    pub add_allow: bool,
+
+    /// Only report violations on lines that have changed since this git revision.
+    /// When no paths are given, only files that have changed are checked.
+    #[arg(long, value_name = "REV", conflicts_with = "watch")]
+    pub diff_base: Option<String>,
+
     // Rule selection
     /// Comma-separated list of rules to ignore.
     #[arg(
@@ -299,12 +381,40 @@ pub struct CheckArgs {
     #[clap(long, overrides_with("respect_gitignore"), hide = true, action = SetTrue)]
     pub no_respect_gitignore: Option<bool>,
 
+    // Preprocessing
+    /// Evaluate C preprocessor conditionals, and only check the active branches of `#if` blocks.
+    /// Use `--no-preprocess` to disable.
+    #[arg(long, overrides_with("no_preprocess"), help_heading = "Preprocessing", action = SetTrue)]
+    pub preprocess: Option<bool>,
+    #[clap(long, overrides_with("preprocess"), hide = true, action = SetTrue)]
+    pub no_preprocess: Option<bool>,
+
+    /// Define a preprocessor macro, in addition to those in the `define` setting. May be repeated.
+    #[arg(
+        short = 'D',
+        long,
+        value_name = "NAME[=VALUE]",
+        help_heading = "Preprocessing"
+    )]
+    pub define: Option<Vec<String>>,
+
     // Options for individual rules
     /// Set the maximum allowable line length.
     #[arg(long, help_heading = "Per-Rule Options")]
     pub line_length: Option<usize>,
 
     // Miscellaneous
+    /// Disable the cache (no reads or writes).
+    #[arg(
+        short,
+        long,
+        env = "FORTITUDE_NO_CACHE",
+        help_heading = "Miscellaneous"
+    )]
+    pub no_cache: bool,
+    /// Path to the cache directory.
+    #[arg(long, env = "FORTITUDE_CACHE_DIR", help_heading = "Miscellaneous")]
+    pub cache_dir: Option<PathBuf>,
     /// The name of the file when passing it through stdin.
     #[arg(long, help_heading = "Miscellaneous")]
     pub stdin_filename: Option<PathBuf>,
@@ -325,6 +435,14 @@ pub struct CheckArgs {
         conflicts_with = "statistics"
     )]
     pub exit_non_zero_on_fix: bool,
+    /// Exit with a non-zero status code only for violations of at least this severity. Violations of every severity are still reported.
+    #[arg(
+        long,
+        value_name = "SEVERITY",
+        help_heading = "Miscellaneous",
+        conflicts_with = "exit_zero"
+    )]
+    pub exit_on: Option<Severity>,
     /// Show counts for every rule with at least one violation.
     #[arg(long)]
     pub statistics: bool,
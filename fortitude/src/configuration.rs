@@ -1,8 +1,12 @@
+use crate::cache::DEFAULT_CACHE_DIR;
 use crate::cli::CheckArgs;
-use crate::fs::{FilePattern, FilePatternSet, EXCLUDE_BUILTINS, FORTRAN_EXTS};
+use crate::custom_rules::CustomRules;
+use crate::fs::{FilePattern, FilePatternSet, EXCLUDE_BUILTINS, FIXED_FORM_EXTS};
 use crate::options::{
-    ExitUnlabelledLoopOptions, KeywordWhitespaceOptions, Options, PortabilityOptions, StringOptions,
+    CheckOptions, CustomRuleOptions, ExitUnlabelledLoopOptions, FormatOptions,
+    KeywordWhitespaceOptions, MagicNumberOptions, Options, PortabilityOptions, StringOptions,
 };
+use crate::preprocessor;
 use crate::registry::RuleNamespace;
 use crate::rule_redirects::get_redirect;
 use crate::rule_selector::{
@@ -12,17 +16,18 @@ use crate::rule_selector::{
 use crate::rule_table::RuleTable;
 use crate::rules::Rule;
 use crate::settings::{
-    CheckSettings, ExcludeMode, FileResolverSettings, GitignoreMode, OutputFormat, PreviewMode,
-    ProgressBar, Settings, UnsafeFixes, DEFAULT_SELECTORS,
+    CheckSettings, ExcludeMode, FileResolverSettings, GitignoreMode, OutputFormat, OutputTarget,
+    PreviewMode, ProgressBar, Settings, UnsafeFixes, DEFAULT_SELECTORS,
 };
+use crate::severity::{Severity, SeverityTable};
 use crate::{fs, warn_user_once_by_id, warn_user_once_by_message};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use itertools::Itertools;
 use log::warn;
 use rustc_hash::{FxHashMap, FxHashSet};
 use serde::Deserialize;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::{Path, PathBuf};
 use strum::IntoEnumIterator;
 
@@ -106,14 +111,90 @@ pub fn project_root<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
 
 /// Read either the "extra.fortitude" table from "fpm.toml", or the
 /// whole "fortitude.toml" file
-fn load_options<P: AsRef<Path>>(path: P) -> Result<Options> {
-    if path.as_ref().ends_with("fpm.toml") {
-        let config = parse_fpm_toml(&path)?;
-        // Unwrap should be ok here because we've already checked this
-        // file has these tables
-        Ok(config.extra.unwrap().fortitude.unwrap())
+fn read_options(path: &Path) -> Result<Options> {
+    if path.ends_with("fpm.toml") {
+        parse_fpm_toml(path)?
+            .extra
+            .and_then(|extra| extra.fortitude)
+            .with_context(|| format!("{} has no `extra.fortitude` table", path.display()))
     } else {
-        parse_fortitude_toml(&path)
+        parse_fortitude_toml(path)
+    }
+}
+
+/// Read the options from a configuration file, combined with those from the chain of
+/// files it extends
+pub fn load_options<P: AsRef<Path>>(path: P) -> Result<Options> {
+    let mut path = fs::normalize_path(path);
+    let mut options = read_options(&path)?;
+    let mut chain = vec![path.clone()];
+
+    while let Some(extend) = options.extend.take() {
+        let directory = path.parent().context("Settings file has no parent")?;
+        path = fs::normalize_path_to(extend, directory);
+        if chain.contains(&path) {
+            bail!(
+                "Circular `extend` in configuration files: {} -> {}",
+                chain.iter().map(|path| path.display()).join(" -> "),
+                path.display()
+            );
+        }
+
+        let mut base = read_options(&path)?;
+        if let Some(check) = &mut base.check {
+            let directory = path.parent().context("Settings file has no parent")?;
+            resolve_extended_paths(check, directory);
+        }
+        // The base's own `extend`, if any, is carried over and followed next
+        options = options.combine(base);
+        chain.push(path.clone());
+    }
+    Ok(options)
+}
+
+/// Resolve relative paths in the options of an extended file against the directory
+/// containing it. Otherwise they would be resolved against the project root, which
+/// is where the file that extends it lives.
+fn resolve_extended_paths(check: &mut CheckOptions, directory: &Path) {
+    let resolve = |pattern: &str| {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => ("!", pattern),
+            None => ("", pattern),
+        };
+        let absolute = fs::normalize_path_to(pattern, directory);
+        format!("{negated}{}", absolute.to_string_lossy())
+    };
+
+    for patterns in [&mut check.exclude, &mut check.extend_exclude]
+        .into_iter()
+        .flatten()
+    {
+        for pattern in patterns.iter_mut() {
+            *pattern = resolve(pattern);
+        }
+    }
+    check.per_file_ignores = check.per_file_ignores.take().map(|per_file_ignores| {
+        per_file_ignores
+            .into_iter()
+            .map(|(pattern, selectors)| (resolve(&pattern), selectors))
+            .collect()
+    });
+    for path in [&mut check.cache_dir, &mut check.baseline]
+        .into_iter()
+        .flatten()
+    {
+        *path = fs::normalize_path_to(&*path, directory);
+    }
+    for plugin in check.plugins.iter_mut().flatten() {
+        *plugin = fs::normalize_path_to(&*plugin, directory);
+    }
+    for path in check
+        .extend_output_format
+        .iter_mut()
+        .flatten()
+        .filter_map(|target| target.path.as_mut())
+    {
+        *path = fs::normalize_path_to(&*path, directory);
     }
 }
 
@@ -129,20 +210,22 @@ pub fn resolve_bool_arg(yes: Option<bool>, no: Option<bool>) -> Option<bool> {
     }
 }
 
+/// Return the configuration file given on the command line, or else the nearest one to
+/// the working directory, if any
+pub fn config_file_path(config_file: &Option<PathBuf>) -> Result<Option<PathBuf>> {
+    match config_file {
+        Some(filename) => Ok(Some(fs::normalize_path(filename))),
+        None => find_settings_toml(path_absolutize::path_dedot::CWD.as_path()),
+    }
+}
+
 /// Read either fpm.toml or fortitude.toml into our "known good" file
 /// settings struct
 pub fn parse_config_file(config_file: &Option<PathBuf>) -> Result<Options> {
-    let filename = match config_file {
-        Some(filename) => filename.clone(),
-        None => match find_settings_toml(path_absolutize::path_dedot::CWD.as_path())? {
-            Some(filename) => filename,
-            None => {
-                return Ok(Options::default());
-            }
-        },
-    };
-
-    load_options(filename)
+    match config_file_path(config_file)? {
+        Some(filename) => load_options(filename),
+        None => Ok(Options::default()),
+    }
 }
 
 // This is our "known good" intermediate settings struct after we've
@@ -154,24 +237,36 @@ pub struct Configuration {
     pub select: Option<Vec<RuleSelector>>,
     pub extend_select: Vec<RuleSelector>,
     pub per_file_ignores: Option<Vec<PerFileIgnore>>,
+    pub severity: BTreeMap<RuleSelector, Severity>,
     pub line_length: usize,
-    pub file_extensions: Vec<String>,
+    pub file_extensions: Option<Vec<String>>,
+    pub fixed_form_extensions: Vec<String>,
     pub fix: bool,
     pub fix_only: bool,
     pub show_fixes: bool,
     pub unsafe_fixes: UnsafeFixes,
     pub output_format: OutputFormat,
+    pub extend_output_format: Vec<OutputTarget>,
     pub progress_bar: ProgressBar,
     pub preview: PreviewMode,
     pub exclude: Option<Vec<FilePattern>>,
     pub extend_exclude: Vec<FilePattern>,
     pub exclude_mode: ExcludeMode,
     pub gitignore_mode: GitignoreMode,
+    pub cache_dir: Option<PathBuf>,
+    pub baseline: Option<PathBuf>,
+    pub preprocess: bool,
+    pub define: Vec<String>,
+    pub custom_rules: BTreeMap<String, CustomRuleOptions>,
+    pub plugins: Vec<PathBuf>,
     // Individual rules
     pub exit_unlabelled_loops: Option<ExitUnlabelledLoopOptions>,
     pub keyword_whitespace: Option<KeywordWhitespaceOptions>,
+    pub magic_numbers: Option<MagicNumberOptions>,
     pub strings: Option<StringOptions>,
     pub portability: Option<PortabilityOptions>,
+    // Formatter
+    pub format: Option<FormatOptions>,
 }
 
 impl Default for Configuration {
@@ -182,23 +277,34 @@ impl Default for Configuration {
             select: Default::default(),
             extend_select: Default::default(),
             per_file_ignores: Default::default(),
+            severity: Default::default(),
             line_length: Settings::default().check.line_length,
-            file_extensions: FORTRAN_EXTS.iter().map(|ext| ext.to_string()).collect(),
+            file_extensions: Default::default(),
+            fixed_form_extensions: FIXED_FORM_EXTS.iter().map(|ext| ext.to_string()).collect(),
             fix: Default::default(),
             fix_only: Default::default(),
             show_fixes: Default::default(),
             unsafe_fixes: Default::default(),
             output_format: Default::default(),
+            extend_output_format: Default::default(),
             progress_bar: Default::default(),
             preview: Default::default(),
             exclude: Default::default(),
             extend_exclude: Default::default(),
             exclude_mode: Default::default(),
             gitignore_mode: Default::default(),
+            cache_dir: Default::default(),
+            baseline: Default::default(),
+            preprocess: Default::default(),
+            define: Default::default(),
+            custom_rules: Default::default(),
+            plugins: Default::default(),
             exit_unlabelled_loops: Default::default(),
             keyword_whitespace: Default::default(),
+            magic_numbers: Default::default(),
             strings: Default::default(),
             portability: Default::default(),
+            format: Default::default(),
         }
     }
 }
@@ -221,12 +327,17 @@ impl Configuration {
                     })
                     .collect()
             }),
+            severity: check.severity.unwrap_or_default(),
             line_length: check
                 .line_length
                 .unwrap_or(Settings::default().check.line_length),
-            file_extensions: check
-                .file_extensions
-                .unwrap_or(FORTRAN_EXTS.iter().map(|ext| ext.to_string()).collect_vec()),
+            file_extensions: check.file_extensions,
+            fixed_form_extensions: check.fixed_form_extensions.unwrap_or(
+                FIXED_FORM_EXTS
+                    .iter()
+                    .map(|ext| ext.to_string())
+                    .collect_vec(),
+            ),
             fix: check.fix.unwrap_or_default(),
             fix_only: check.fix_only.unwrap_or_default(),
             show_fixes: check.show_fixes.unwrap_or_default(),
@@ -235,6 +346,17 @@ impl Configuration {
                 .map(UnsafeFixes::from)
                 .unwrap_or_default(),
             output_format: check.output_format.unwrap_or_default(),
+            extend_output_format: check
+                .extend_output_format
+                .unwrap_or_default()
+                .into_iter()
+                .map(|target| OutputTarget {
+                    path: target
+                        .path
+                        .map(|path| fs::normalize_path_to(path, project_root)),
+                    ..target
+                })
+                .collect(),
             progress_bar: check.progress_bar.unwrap_or_default(),
             preview: check.preview.map(PreviewMode::from).unwrap_or_default(),
             exclude: check.exclude.map(|paths| {
@@ -266,20 +388,46 @@ impl Configuration {
                 .respect_gitignore
                 .map(GitignoreMode::from)
                 .unwrap_or_default(),
+            cache_dir: check
+                .cache_dir
+                .map(|cache_dir| fs::normalize_path_to(cache_dir, project_root)),
+            baseline: check
+                .baseline
+                .map(|baseline| fs::normalize_path_to(baseline, project_root)),
+            preprocess: check.preprocess.unwrap_or_default(),
+            define: check.define.unwrap_or_default(),
+            custom_rules: check.custom_rules.unwrap_or_default(),
+            plugins: check
+                .plugins
+                .unwrap_or_default()
+                .into_iter()
+                .map(|plugin| fs::normalize_path_to(plugin, project_root))
+                .collect(),
 
             // Individual rules
             exit_unlabelled_loops: check.exit_unlabelled_loops,
             keyword_whitespace: check.keyword_whitespace,
+            magic_numbers: check.magic_numbers,
             strings: check.strings,
             portability: check.portability,
+
+            // Formatter
+            format: options.format,
         }
     }
 
     pub fn into_settings(self, project_root: &Path, args: &CheckArgs) -> Result<Settings> {
         let args = args.clone();
 
+        let preview = resolve_bool_arg(args.preview, args.no_preview)
+            .map(PreviewMode::from)
+            .unwrap_or(self.preview);
+
         let files = args.files.unwrap_or(self.files);
-        let file_extensions = args.file_extensions.unwrap_or(self.file_extensions);
+        let file_extensions = args
+            .file_extensions
+            .or(self.file_extensions)
+            .unwrap_or_else(|| fs::default_file_extensions(preview));
 
         let per_file_ignores = if let Some(per_file_ignores) = args.per_file_ignores {
             Some(collect_per_file_ignores(per_file_ignores))
@@ -320,10 +468,6 @@ impl Configuration {
             .map(GitignoreMode::from)
             .unwrap_or(self.gitignore_mode);
 
-        let preview = resolve_bool_arg(args.preview, args.no_preview)
-            .map(PreviewMode::from)
-            .unwrap_or(self.preview);
-
         let rule_selection = RuleSelection {
             select: args.select.or(self.select),
             // TODO: CLI ignore should _extend_ file ignore
@@ -333,7 +477,9 @@ impl Configuration {
             unfixable: vec![],
             extend_fixable: vec![],
         };
-        let rules = to_rule_table(rule_selection, &preview)?;
+        let rules = to_rule_table(rule_selection.clone(), &preview)?;
+        let custom_rules = CustomRules::resolve(self.custom_rules, &self.plugins, &rule_selection)?;
+        let severity = SeverityTable::resolve(self.severity, &custom_rules);
 
         let mut progress_bar = args.progress_bar.unwrap_or(self.progress_bar);
         // Override progress bar settings if not using colour terminal
@@ -343,11 +489,52 @@ impl Configuration {
             progress_bar = ProgressBar::Ascii;
         }
 
-        let output_format = args.output_format.unwrap_or(self.output_format);
+        // Formats on the command line without a path replace `output-format`, while
+        // those with a path are written to that file as well
+        let (output_files, output_formats): (Vec<_>, Vec<_>) = args
+            .output_format
+            .iter()
+            .flatten()
+            .cloned()
+            .partition(|target| target.path.is_some());
+        let output_format = match output_formats.as_slice() {
+            [] => self.output_format,
+            [target] => target.format,
+            _ => bail!("Only one `--output-format` can be given without a path"),
+        };
+        if let Some(target) = self
+            .extend_output_format
+            .iter()
+            .find(|target| target.path.is_none())
+        {
+            bail!("`extend-output-format` entries must include a path, as `{target}:<path>`");
+        }
+        let output_files = output_files
+            .into_iter()
+            .map(|target| OutputTarget {
+                path: target.path.map(fs::normalize_path),
+                ..target
+            })
+            .chain(self.extend_output_format)
+            .collect();
 
         let show_fixes =
             resolve_bool_arg(args.show_fixes, args.no_show_fixes).unwrap_or(self.show_fixes);
 
+        let cache_dir = args
+            .cache_dir
+            .map(fs::normalize_path)
+            .or(self.cache_dir)
+            .unwrap_or_else(|| project_root.join(DEFAULT_CACHE_DIR));
+
+        let preprocess =
+            resolve_bool_arg(args.preprocess, args.no_preprocess).unwrap_or(self.preprocess);
+        // Definitions on the command line are added to those from the config file, with
+        // later definitions of the same macro taking priority
+        let define = preprocessor::parse_definitions(
+            self.define.iter().chain(args.define.iter().flatten()),
+        )?;
+
         Ok(Settings {
             check: CheckSettings {
                 project_root: project_root.to_path_buf(),
@@ -362,9 +549,17 @@ impl Configuration {
                 preview,
                 progress_bar,
                 output_format,
+                output_files,
                 show_fixes,
                 per_file_ignores,
+                severity,
                 ignore_allow_comments: args.ignore_allow_comments.into(),
+                cache_dir,
+                baseline: self.baseline,
+                fixed_form_extensions: self.fixed_form_extensions,
+                preprocess,
+                define,
+                custom_rules,
 
                 // Individual rules
                 exit_unlabelled_loops: self
@@ -375,6 +570,10 @@ impl Configuration {
                     .keyword_whitespace
                     .map(KeywordWhitespaceOptions::into_settings)
                     .unwrap_or_default(),
+                magic_numbers: self
+                    .magic_numbers
+                    .map(MagicNumberOptions::into_settings)
+                    .unwrap_or_default(),
                 strings: self
                     .strings
                     .map(StringOptions::into_settings)
@@ -392,6 +591,10 @@ impl Configuration {
                 respect_gitignore: respect_gitignore.is_respect_gitignore(),
                 force_exclude: force_exclude.is_force(),
             },
+            format: self
+                .format
+                .map(FormatOptions::into_settings)
+                .unwrap_or_default(),
         })
     }
 }
@@ -654,9 +857,8 @@ impl RuleSelection {
     }
 }
 
-// This is required by the `options_group` macro, but we don't make
-// use of it anywhere yet
-#[allow(dead_code)]
+/// Combine two sets of options, with those in `self` taking priority. This is
+/// required by the `CombineOptions` macro, and used to implement `extend`.
 pub(crate) trait CombinePluginOptions {
     #[must_use]
     fn combine(self, other: Self) -> Self;
@@ -851,4 +1053,85 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn extend_config_file() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        let base_dir = tempdir.path().join("base");
+        fs::create_dir(&base_dir)?;
+        fs::write(
+            base_dir.join("fortitude.toml"),
+            dedent(
+                r#"
+                [check]
+                select = ["C"]
+                line-length = 100
+                baseline = "baseline.json"
+                per-file-ignores = { "!src/*.f90" = ["C001"] }
+                extend-output-format = ["sarif:fortitude.sarif"]
+                "#,
+            ),
+        )?;
+        let config_file = tempdir.path().join("fortitude.toml");
+        fs::write(
+            &config_file,
+            dedent(
+                r#"
+                extend = "base/fortitude.toml"
+
+                [check]
+                line-length = 132
+                "#,
+            ),
+        )?;
+
+        let check = load_options(&config_file)?.check.unwrap();
+        assert_eq!(check.select, Some(vec![RuleSelector::from_str("C")?]));
+        assert_eq!(check.line_length, Some(132));
+        assert_eq!(check.baseline, Some(base_dir.join("baseline.json")));
+        let per_file_ignores = check.per_file_ignores.unwrap();
+        let pattern = format!("!{}", base_dir.join("src/*.f90").display());
+        assert!(per_file_ignores.contains_key(&pattern));
+        let output = &check.extend_output_format.unwrap()[0];
+        assert_eq!(output.path, Some(base_dir.join("fortitude.sarif")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn extend_config_file_cycle() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        let first = tempdir.path().join("first.toml");
+        let second = tempdir.path().join("second.toml");
+        fs::write(&first, "extend = \"second.toml\"")?;
+        fs::write(&second, "extend = \"first.toml\"")?;
+
+        let err = load_options(&first).unwrap_err();
+        assert!(err.to_string().starts_with("Circular `extend`"));
+
+        Ok(())
+    }
+    #[test]
+    fn fixed_form_extensions_in_preview() -> Result<()> {
+        let extensions = |args: CheckArgs| -> Result<Vec<String>> {
+            let settings = Configuration::default().into_settings(Path::new("."), &args)?;
+            Ok(settings.file_resolver.file_extensions)
+        };
+        let fixed_form = String::from("f");
+
+        assert!(!extensions(CheckArgs::default())?.contains(&fixed_form));
+        let preview = CheckArgs {
+            preview: Some(true),
+            ..CheckArgs::default()
+        };
+        assert!(extensions(preview.clone())?.contains(&fixed_form));
+        // Extensions that are set explicitly are used as they are
+        let explicit = CheckArgs {
+            file_extensions: Some(vec!["f90".to_string()]),
+            ..preview
+        };
+        assert_eq!(extensions(explicit)?, ["f90"]);
+
+        Ok(())
+    }
 }
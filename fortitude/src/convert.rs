@@ -0,0 +1,147 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use ruff_diagnostics::{Diagnostic, Fix};
+use ruff_text_size::TextRange;
+
+use crate::check::{read_to_string, resolve_settings, write_renamed_diff};
+use crate::cli::{CheckArgs, ConvertArgs, GlobalConfigArgs};
+use crate::diagnostics::Violation;
+use crate::fix::fix_file;
+use crate::fixed_form::{free_form_edits, is_fixed_form};
+use crate::fs::{get_files, relativize_path};
+use crate::locator::Locator;
+use crate::logging::LogLevel;
+use crate::rules::obsolescent::fixed_form::FixedFormSource;
+use crate::settings::UnsafeFixes;
+use crate::warn_user;
+
+/// Convert fixed-form files to free-form, and rename them with a free-form extension.
+pub fn convert(args: ConvertArgs, global_options: &GlobalConfigArgs) -> Result<ExitCode> {
+    let check_args = CheckArgs {
+        files: Some(args.files),
+        ..CheckArgs::default()
+    };
+    let settings = resolve_settings(&check_args, global_options)?;
+    let files = get_files(&settings.file_resolver, false, None)?;
+
+    let mut converted = 0;
+    for path in files
+        .iter()
+        .filter(|path| is_fixed_form(path, &settings.check))
+    {
+        let source =
+            read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let new_path = free_form_path(path);
+        let free_form = convert_source(path, &source);
+
+        if args.diff {
+            write_renamed_diff(path, &new_path, &source, &free_form)?;
+        } else {
+            if new_path.exists() {
+                warn_user!(
+                    "Not converting {}, as {} already exists",
+                    relativize_path(path),
+                    relativize_path(&new_path)
+                );
+                continue;
+            }
+            std::fs::write(&new_path, free_form)
+                .with_context(|| format!("Failed to write {}", new_path.display()))?;
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        converted += 1;
+    }
+
+    if global_options.log_level() >= LogLevel::Default {
+        let summary = if args.diff {
+            format!("{converted} file(s) would be converted")
+        } else {
+            format!("Converted {converted} file(s)")
+        };
+        eprintln!("{}", summary.bold());
+    }
+
+    // As with `check --diff`, exit non-zero if there are any changes to be made
+    if args.diff && converted > 0 {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Apply the edits converting `source` to free-form.
+fn convert_source(path: &Path, source: &str) -> String {
+    let mut edits = free_form_edits(source).into_iter();
+    let Some(first) = edits.next() else {
+        return source.to_string();
+    };
+    let diagnostic = Diagnostic::new(FixedFormSource {}, TextRange::default())
+        .with_fix(Fix::unsafe_edits(first, edits));
+
+    let locator = Locator::new(source);
+    match fix_file(
+        &[Violation::from(diagnostic)],
+        &locator,
+        UnsafeFixes::Enabled,
+        path.to_string_lossy().as_ref(),
+    ) {
+        Some(result) => result.code.source_text().to_string(),
+        None => source.to_string(),
+    }
+}
+
+/// The path of the converted file. Upper case extensions conventionally mean that a
+/// file should be preprocessed, so these become `.F90`.
+fn free_form_path(path: &Path) -> PathBuf {
+    let preprocessed = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|extension| extension.chars().any(|c| c.is_ascii_uppercase()));
+    path.with_extension(if preprocessed { "F90" } else { "f90" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_to_free_form() {
+        let source = [
+            "C     Sum some numbers",
+            "      PROGRAM TEST",
+            "      X = 1 +    ! trailing comment",
+            "*     comment between continuations",
+            "     &    2",
+            "      PRINT *, 'a long string ",
+            "     1that is continued'",
+            "      END                                                               00000010",
+            "",
+        ]
+        .join("\n");
+        let expected = [
+            "!     Sum some numbers",
+            "      PROGRAM TEST",
+            "      X = 1 +    & ! trailing comment",
+            "!     comment between continuations",
+            "     &    2",
+            "      PRINT *, 'a long string &",
+            "     &that is continued'",
+            "      END",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(convert_source(Path::new("test.f"), &source), expected);
+    }
+
+    #[test]
+    fn free_form_extensions() {
+        assert_eq!(free_form_path(Path::new("a/b.f")), Path::new("a/b.f90"));
+        assert_eq!(free_form_path(Path::new("b.FOR")), Path::new("b.F90"));
+        assert_eq!(free_form_path(Path::new("b.F")), Path::new("b.F90"));
+    }
+}
@@ -0,0 +1,765 @@
+//! Custom rules defined by tree-sitter queries in the `check.custom-rules` table, or
+//! provided by the plugins listed in `check.plugins`
+
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::iter::once;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{anyhow, bail, Context, Result};
+use lazy_regex::{regex, Captures};
+use libloading::Library;
+use ruff_cache::{CacheKey, CacheKeyHasher};
+use ruff_diagnostics::{Diagnostic, Fix};
+use ruff_source_file::SourceFile;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Query, QueryCursor, StreamingIterator};
+
+use crate::ast::FortitudeNode;
+use crate::configuration::RuleSelection;
+use crate::diagnostics::Violation;
+use crate::options::CustomRuleOptions;
+use crate::plugin::{load_plugin, Plugin, PluginViolation, RuleCheck};
+use crate::registry::{Category, RuleNamespace};
+use crate::rule_selector::{is_custom_rule_identifier, ParseError, RuleSelector};
+use crate::rules::fortitude::custom_rules::CustomRule;
+use crate::settings::Settings;
+
+/// The capture that violations are reported on, if present in the query
+const VIOLATION_CAPTURE: &str = "violation";
+
+/// The custom rule that raised a violation, which is reported under the custom rule's
+/// own code and name
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CustomRuleId {
+    pub(crate) code: String,
+    pub(crate) name: String,
+}
+
+/// A custom rule, either with its query compiled or provided by a plugin
+#[derive(Debug)]
+pub struct CompiledCustomRule {
+    /// The name of the rule, from its key in the `check.custom-rules` table or from
+    /// the plugin
+    pub name: String,
+    pub code: String,
+    /// Whether the rule has been selected
+    pub enabled: bool,
+    check: CustomCheck,
+}
+
+#[derive(Debug)]
+enum CustomCheck {
+    Query(QueryCheck),
+    Plugin(PluginCheck),
+}
+
+#[derive(Debug)]
+struct QueryCheck {
+    query: Query,
+    query_source: String,
+    /// Index of the capture to report violations on
+    target: u32,
+    message: String,
+    replacement: Option<String>,
+}
+
+struct PluginCheck {
+    check: RuleCheck,
+    explanation: String,
+    /// The library the rule was loaded from, and when it was last modified in
+    /// nanoseconds, so that rebuilding the plugin invalidates the cache
+    path: PathBuf,
+    modified: Option<u128>,
+}
+
+impl Debug for PluginCheck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginCheck")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Check that a custom rule's name and code are valid, and don't clash with built-in
+/// rules
+fn validate_identifiers(name: &str, code: &str) -> Result<()> {
+    if !is_custom_rule_identifier(name) {
+        bail!(
+            "Invalid name `{name}` for custom rule: names must be letters, digits, underscores, and hyphens"
+        );
+    }
+    if !regex!(r"^[A-Z]+[0-9]+$").is_match(code) {
+        bail!(
+            "Invalid code `{code}` for custom rule `{name}`: codes must be capital letters followed by digits"
+        );
+    }
+    for identifier in [name, code] {
+        if Category::parse_code(identifier).is_some() {
+            bail!("Custom rule `{name}` clashes with built-in rules: `{identifier}` is already in use");
+        }
+    }
+    Ok(())
+}
+
+impl CompiledCustomRule {
+    fn new(name: String, options: CustomRuleOptions) -> Result<Self> {
+        validate_identifiers(&name, &options.code)?;
+
+        let query = Query::new(&tree_sitter_fortran::LANGUAGE.into(), &options.query)
+            .with_context(|| format!("Invalid query for custom rule `{name}`"))?;
+
+        let captures = query.capture_names();
+        let target = captures
+            .iter()
+            .position(|capture| *capture == VIOLATION_CAPTURE)
+            .or((!captures.is_empty()).then_some(0))
+            .ok_or_else(|| anyhow!("Query for custom rule `{name}` has no captures"))?;
+
+        for template in once(&options.message).chain(options.replacement.iter()) {
+            for placeholder in regex!(r"\{([\w.-]+)\}").captures_iter(template) {
+                let capture = &placeholder[1];
+                if !captures.contains(&capture) {
+                    bail!("Unknown capture `{capture}` in template for custom rule `{name}`");
+                }
+            }
+        }
+
+        Ok(Self {
+            name,
+            code: options.code,
+            enabled: true,
+            check: CustomCheck::Query(QueryCheck {
+                query,
+                query_source: options.query,
+                target: target as u32,
+                message: options.message,
+                replacement: options.replacement,
+            }),
+        })
+    }
+
+    /// Collect the rules provided by a plugin, loaded from the library at `path`
+    fn from_plugin(plugin: &dyn Plugin, path: &Path) -> Result<Vec<Self>> {
+        let prefix = plugin.prefix();
+        if !regex!(r"^[A-Z]+$").is_match(prefix) {
+            bail!(
+                "Invalid prefix `{prefix}` for plugin {}: prefixes must be capital letters",
+                path.display()
+            );
+        }
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_nanos());
+
+        plugin
+            .rules()
+            .into_iter()
+            .map(|rule| -> Result<Self> {
+                let code = format!("{prefix}{}", rule.code);
+                validate_identifiers(&rule.name, &code)
+                    .with_context(|| format!("Invalid rule in plugin {}", path.display()))?;
+                Ok(Self {
+                    name: rule.name,
+                    code,
+                    enabled: true,
+                    check: CustomCheck::Plugin(PluginCheck {
+                        check: rule.check,
+                        explanation: rule.explanation,
+                        path: path.to_path_buf(),
+                        modified,
+                    }),
+                })
+            })
+            .collect()
+    }
+
+    /// How specifically `selector` refers to this rule, if it does at all
+    fn specificity(&self, selector: &RuleSelector) -> Option<usize> {
+        match selector {
+            RuleSelector::All => Some(0),
+            RuleSelector::Custom(name) if *name == self.name => Some(self.code.len()),
+            RuleSelector::Custom(prefix) if self.code.starts_with(prefix.as_str()) => {
+                Some(prefix.len())
+            }
+            _ => None,
+        }
+    }
+
+    /// Custom rules are selected by default, like the default built-in rules. Otherwise,
+    /// the most specific selector that refers to the rule wins, with `ignore` taking
+    /// priority over `select` when they're equally specific.
+    fn is_selected(&self, selection: &RuleSelection) -> bool {
+        let default = selection.select.is_none().then_some(0);
+        let selected = selection
+            .select
+            .iter()
+            .flatten()
+            .chain(selection.extend_select.iter())
+            .filter_map(|selector| self.specificity(selector))
+            .max()
+            .max(default);
+        let ignored = selection
+            .ignore
+            .iter()
+            .filter_map(|selector| self.specificity(selector))
+            .max();
+        selected > ignored
+    }
+
+    /// Describe the rule for `fortitude explain`
+    pub fn explanation(&self) -> String {
+        match &self.check {
+            CustomCheck::Query(check) => {
+                let mut explanation = String::new();
+                if check.replacement.is_some() {
+                    explanation.push_str("Fix is sometimes available.\n\n");
+                }
+                explanation.push_str(&format!(
+                    "Custom rule defined by the query:\n```\n{}\n```\n\nMessage format:\n* {}\n",
+                    check.query_source.trim(),
+                    check.message
+                ));
+                explanation
+            }
+            CustomCheck::Plugin(check) => format!(
+                "Provided by the plugin {}.\n\n{}\n",
+                check.path.display(),
+                check.explanation.trim_end()
+            ),
+        }
+    }
+
+    fn check(
+        &self,
+        settings: &Settings,
+        path: &Path,
+        root: &Node,
+        file: &SourceFile,
+    ) -> Vec<Violation> {
+        let diagnostics = match &self.check {
+            CustomCheck::Query(check) => check.check(root, file),
+            CustomCheck::Plugin(check) => check
+                .check(settings, path, root, file)
+                .into_iter()
+                .map(|violation| {
+                    let mut diagnostic = Diagnostic::new(
+                        CustomRule {
+                            message: violation.message,
+                            suggestion: violation.suggestion,
+                        },
+                        violation.range,
+                    );
+                    diagnostic.fix = violation.fix;
+                    diagnostic
+                })
+                .collect(),
+        };
+
+        let custom_rule = CustomRuleId {
+            code: self.code.clone(),
+            name: self.name.clone(),
+        };
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| Violation {
+                diagnostic,
+                custom_rule: Some(custom_rule.clone()),
+            })
+            .collect()
+    }
+}
+
+impl QueryCheck {
+    fn check(&self, root: &Node, file: &SourceFile) -> Vec<Diagnostic> {
+        let source = file.source_text();
+        let names = self.query.capture_names();
+
+        let mut diagnostics = Vec::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&self.query, *root, source.as_bytes());
+        while let Some(query_match) = matches.next() {
+            let Some(node) = query_match.nodes_for_capture_index(self.target).next() else {
+                continue;
+            };
+
+            // Captures that match more than once are interpolated as their first match
+            let mut captures = FxHashMap::default();
+            for capture in query_match.captures {
+                captures
+                    .entry(names[capture.index as usize])
+                    .or_insert_with(|| capture.node.to_text(source).unwrap_or_default());
+            }
+
+            let replacement = self
+                .replacement
+                .as_ref()
+                .map(|replacement| interpolate(replacement, &captures));
+            let mut diagnostic = Diagnostic::new(
+                CustomRule {
+                    message: interpolate(&self.message, &captures),
+                    suggestion: replacement
+                        .as_ref()
+                        .map(|replacement| format!("Replace with `{replacement}`")),
+                },
+                node.textrange(),
+            );
+            if let Some(replacement) = replacement {
+                diagnostic.set_fix(Fix::unsafe_edit(node.edit_replacement(file, replacement)));
+            }
+            diagnostics.push(diagnostic);
+        }
+        diagnostics
+    }
+}
+
+impl PluginCheck {
+    fn check(
+        &self,
+        settings: &Settings,
+        path: &Path,
+        root: &Node,
+        file: &SourceFile,
+    ) -> Vec<PluginViolation> {
+        match &self.check {
+            RuleCheck::Path(rule) => rule.check(settings, path).into_iter().collect(),
+            RuleCheck::Text(rule) => rule.check(settings, file),
+            RuleCheck::Ast(rule) => {
+                let entrypoints = rule.entrypoints();
+                once(*root)
+                    .chain(root.descendants())
+                    .filter(|node| entrypoints.contains(&node.kind()))
+                    .flat_map(|node| rule.check(settings, &node, file))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl CacheKey for CompiledCustomRule {
+    fn cache_key(&self, state: &mut CacheKeyHasher) {
+        self.name.cache_key(state);
+        self.code.cache_key(state);
+        self.enabled.cache_key(state);
+        match &self.check {
+            CustomCheck::Query(check) => {
+                check.query_source.cache_key(state);
+                check.message.cache_key(state);
+                check.replacement.cache_key(state);
+            }
+            CustomCheck::Plugin(check) => {
+                check.path.cache_key(state);
+                check.modified.cache_key(state);
+            }
+        }
+    }
+}
+
+impl Display for CompiledCustomRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.code, self.name)?;
+        if let CustomCheck::Plugin(check) = &self.check {
+            write!(f, " from {}", check.path.display())?;
+        }
+        if !self.enabled {
+            write!(f, " [disabled]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Replace `{capture}` placeholders in `template` with the text of the capture
+fn interpolate(template: &str, captures: &FxHashMap<&str, &str>) -> String {
+    regex!(r"\{([\w.-]+)\}")
+        .replace_all(template, |placeholder: &Captures| {
+            captures
+                .get(&placeholder[1])
+                .copied()
+                .unwrap_or_default()
+                .to_string()
+        })
+        .into_owned()
+}
+
+#[derive(Debug, Default)]
+pub struct CustomRules {
+    rules: Vec<CompiledCustomRule>,
+    /// Libraries that plugin rules were loaded from. These must outlive the rules, so
+    /// are dropped after them.
+    libraries: Vec<Library>,
+}
+
+impl CustomRules {
+    /// Compile the custom rules and load the plugins, and work out which rules have
+    /// been selected
+    pub fn resolve(
+        options: BTreeMap<String, CustomRuleOptions>,
+        plugins: &[PathBuf],
+        selection: &RuleSelection,
+    ) -> Result<Self> {
+        let mut rules = options
+            .into_iter()
+            .map(|(name, options)| CompiledCustomRule::new(name, options))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut libraries = Vec::new();
+        for path in plugins {
+            let loaded = load_plugin(path)?;
+            rules.extend(CompiledCustomRule::from_plugin(
+                loaded.plugin.as_ref(),
+                path,
+            )?);
+            libraries.push(loaded.library);
+        }
+
+        Self::new(rules, libraries, selection)
+    }
+
+    fn new(
+        mut rules: Vec<CompiledCustomRule>,
+        libraries: Vec<Library>,
+        selection: &RuleSelection,
+    ) -> Result<Self> {
+        for (index, rule) in rules.iter().enumerate() {
+            if let Some(other) = rules[..index].iter().find(|other| other.code == rule.code) {
+                bail!(
+                    "Custom rules `{}` and `{}` both use the code `{}`",
+                    other.name,
+                    rule.name,
+                    rule.code
+                );
+            }
+            if let Some(other) = rules[..index].iter().find(|other| other.name == rule.name) {
+                bail!(
+                    "Custom rules `{}` and `{}` both use the name `{}`",
+                    other.code,
+                    rule.code,
+                    rule.name
+                );
+            }
+        }
+
+        // Selectors that aren't built-in rules must refer to at least one custom rule
+        for (_, selector) in selection.selectors_by_kind() {
+            if let RuleSelector::Custom(custom) = selector {
+                if !rules
+                    .iter()
+                    .any(|rule| rule.specificity(selector).is_some())
+                {
+                    bail!(ParseError::Unknown(custom.clone()));
+                }
+            }
+        }
+
+        for rule in rules.iter_mut() {
+            rule.enabled = rule.is_selected(selection);
+        }
+
+        Ok(Self { rules, libraries })
+    }
+
+    /// Find a custom rule by code or name
+    pub fn get(&self, code: &str) -> Option<&CompiledCustomRule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.code == code || rule.name == code)
+    }
+
+    /// All custom rules that `selector` refers to, whether or not they're enabled
+    pub fn matching<'a>(
+        &'a self,
+        selector: &'a RuleSelector,
+    ) -> impl Iterator<Item = &'a CompiledCustomRule> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.specificity(selector).is_some())
+    }
+
+    /// The value paired with the most specific selector that refers to each custom
+    /// rule, for those rules that any of the selectors refer to, keyed by code
+    pub fn most_specific<'a, T: Copy>(
+        &'a self,
+        values: &'a [(RuleSelector, T)],
+    ) -> impl Iterator<Item = (&'a str, T)> + 'a {
+        self.rules.iter().filter_map(|rule| {
+            values
+                .iter()
+                .filter_map(|(selector, value)| Some((rule.specificity(selector)?, *value)))
+                .max_by_key(|(specificity, _)| *specificity)
+                .map(|(_, value)| (rule.code.as_str(), value))
+        })
+    }
+
+    /// Run all enabled custom rules over a file
+    pub(crate) fn check(
+        &self,
+        settings: &Settings,
+        path: &Path,
+        root: &Node,
+        file: &SourceFile,
+    ) -> Vec<Violation> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .flat_map(|rule| rule.check(settings, path, root, file))
+            .collect()
+    }
+}
+
+impl CacheKey for CustomRules {
+    fn cache_key(&self, state: &mut CacheKeyHasher) {
+        self.rules.cache_key(state);
+    }
+}
+
+impl Display for CustomRules {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.rules.is_empty() {
+            write!(f, "[]")?;
+        } else {
+            writeln!(f, "[")?;
+            for rule in &self.rules {
+                writeln!(f, "\t{rule},")?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    use std::path::Path;
+
+    use anyhow::Result;
+    use ruff_source_file::SourceFileBuilder;
+    use tree_sitter::Parser;
+
+    use super::{CompiledCustomRule, CustomRules};
+    use crate::configuration::RuleSelection;
+    use crate::options::CustomRuleOptions;
+    use crate::plugin::{
+        Node, Plugin, PluginAstRule, PluginRule, PluginTextRule, PluginViolation, RuleCheck,
+        SourceFile, TextRange,
+    };
+    use crate::rule_selector::RuleSelector;
+    use crate::settings::Settings;
+
+    fn custom_rules(selection: &RuleSelection) -> Result<CustomRules> {
+        let options = BTreeMap::from([
+            (
+                "no-dble".to_string(),
+                CustomRuleOptions {
+                    code: "ACME001".to_string(),
+                    query: r#"(call_expression (identifier) @name (argument_list (_) @arg)
+                                (#match? @name "(?i)^dble$")) @violation"#
+                        .to_string(),
+                    message: "Use `real({arg}, dp)` instead of `{violation}`".to_string(),
+                    replacement: Some("real({arg}, dp)".to_string()),
+                },
+            ),
+            (
+                "no-print".to_string(),
+                CustomRuleOptions {
+                    code: "ACME002".to_string(),
+                    query: "(print_statement) @print".to_string(),
+                    message: "Don't print".to_string(),
+                    replacement: None,
+                },
+            ),
+        ]);
+        CustomRules::resolve(options, &[], selection)
+    }
+
+    fn selection(select: Option<&[&str]>, ignore: &[&str]) -> Result<RuleSelection> {
+        let parse = |codes: &[&str]| {
+            codes
+                .iter()
+                .map(|code| RuleSelector::from_str(code))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(RuleSelection {
+            select: select.map(parse).transpose()?,
+            ignore: parse(ignore)?,
+            ..RuleSelection::default()
+        })
+    }
+
+    fn enabled(rules: &CustomRules) -> Vec<&str> {
+        rules
+            .rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .map(|rule| rule.code.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn check_custom_rule() -> Result<()> {
+        let rules = custom_rules(&RuleSelection::default())?;
+        let source = "program test\n  x = dble(y)\n  print *, x\nend program test\n";
+        let file = SourceFileBuilder::new("test.f90", source).finish();
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_fortran::LANGUAGE.into())?;
+        let tree = parser.parse(source, None).unwrap();
+
+        let diagnostics = rules.check(
+            &Settings::default(),
+            Path::new("test.f90"),
+            &tree.root_node(),
+            &file,
+        );
+        assert_eq!(diagnostics.len(), 2);
+
+        let dble = &diagnostics[0];
+        assert_eq!(dble.code(), "ACME001");
+        assert_eq!(dble.rule_name(), "no-dble");
+        assert_eq!(dble.diagnostic.kind.name, "CustomRule");
+        assert_eq!(
+            dble.diagnostic.kind.body,
+            "Use `real(y, dp)` instead of `dble(y)`"
+        );
+        assert_eq!(&source[dble.diagnostic.range], "dble(y)");
+        let edits = dble.diagnostic.fix.as_ref().unwrap().edits();
+        assert_eq!(edits[0].content(), Some("real(y, dp)"));
+
+        let print = &diagnostics[1];
+        assert_eq!(print.code(), "ACME002");
+        assert_eq!(print.rule_name(), "no-print");
+        assert!(source[print.diagnostic.range].starts_with("print *, x"));
+        assert!(print.diagnostic.fix.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn select_custom_rules() -> Result<()> {
+        let rules = custom_rules(&selection(None, &[])?)?;
+        assert_eq!(enabled(&rules), ["ACME001", "ACME002"]);
+
+        let rules = custom_rules(&selection(Some(&["C", "E"]), &[])?)?;
+        assert!(enabled(&rules).is_empty());
+
+        let rules = custom_rules(&selection(Some(&["ALL"]), &["ACME"])?)?;
+        assert!(enabled(&rules).is_empty());
+
+        let rules = custom_rules(&selection(Some(&["C", "ACME00"]), &["ACME"])?)?;
+        assert_eq!(enabled(&rules), ["ACME001", "ACME002"]);
+
+        let rules = custom_rules(&selection(None, &["no-print"])?)?;
+        assert_eq!(enabled(&rules), ["ACME001"]);
+
+        assert!(custom_rules(&selection(Some(&["ACME1"]), &[])?).is_err());
+        Ok(())
+    }
+
+    struct NoStop;
+
+    impl PluginAstRule for NoStop {
+        fn check(
+            &self,
+            _settings: &Settings,
+            node: &Node,
+            _source: &SourceFile,
+        ) -> Vec<PluginViolation> {
+            vec![PluginViolation::from_node("Don't stop", node).with_suggestion("Use `error stop`")]
+        }
+
+        fn entrypoints(&self) -> Vec<&'static str> {
+            vec!["stop_statement"]
+        }
+    }
+
+    struct LongFile;
+
+    impl PluginTextRule for LongFile {
+        fn check(&self, settings: &Settings, source: &SourceFile) -> Vec<PluginViolation> {
+            let lines = source.source_text().lines().count();
+            if lines > settings.check.line_length {
+                vec![PluginViolation::new(
+                    format!("File has {lines} lines"),
+                    TextRange::default(),
+                )]
+            } else {
+                vec![]
+            }
+        }
+    }
+
+    struct Acme;
+
+    impl Plugin for Acme {
+        fn prefix(&self) -> &str {
+            "ACME"
+        }
+
+        fn rules(&self) -> Vec<PluginRule> {
+            vec![
+                PluginRule {
+                    code: "101".to_string(),
+                    name: "no-stop".to_string(),
+                    explanation: "## What it does\nChecks for `stop`".to_string(),
+                    check: RuleCheck::Ast(Box::new(NoStop)),
+                },
+                PluginRule {
+                    code: "102".to_string(),
+                    name: "long-file".to_string(),
+                    explanation: "## What it does\nChecks for long files".to_string(),
+                    check: RuleCheck::Text(Box::new(LongFile)),
+                },
+            ]
+        }
+    }
+
+    #[test]
+    fn check_plugin_rules() -> Result<()> {
+        let plugin_rules = CompiledCustomRule::from_plugin(&Acme, Path::new("libacme.so"))?;
+        let rules = CustomRules::new(plugin_rules, vec![], &selection(None, &["ACME102"])?)?;
+        assert_eq!(enabled(&rules), ["ACME101"]);
+        assert!(rules
+            .get("no-stop")
+            .unwrap()
+            .explanation()
+            .contains("Checks for `stop`"));
+
+        let source = "program test\n  stop\nend program test\n";
+        let file = SourceFileBuilder::new("test.f90", source).finish();
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_fortran::LANGUAGE.into())?;
+        let tree = parser.parse(source, None).unwrap();
+
+        let diagnostics = rules.check(
+            &Settings::default(),
+            Path::new("test.f90"),
+            &tree.root_node(),
+            &file,
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), "ACME101");
+        assert_eq!(diagnostics[0].rule_name(), "no-stop");
+        let diagnostic = &diagnostics[0].diagnostic;
+        assert_eq!(diagnostic.kind.body, "Don't stop");
+        assert_eq!(
+            diagnostic.kind.suggestion.as_deref(),
+            Some("Use `error stop`")
+        );
+        assert!(source[diagnostic.range].starts_with("stop"));
+
+        // Rules from different plugins can't share codes
+        let plugin_rules = CompiledCustomRule::from_plugin(&Acme, Path::new("libacme.so"))?
+            .into_iter()
+            .chain(CompiledCustomRule::from_plugin(
+                &Acme,
+                Path::new("libacme2.so"),
+            )?)
+            .collect();
+        assert!(CustomRules::new(plugin_rules, vec![], &RuleSelection::default()).is_err());
+        Ok(())
+    }
+}
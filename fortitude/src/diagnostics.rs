@@ -4,14 +4,59 @@
 
 use std::ops::{Add, AddAssign};
 
+use ruff_diagnostics::Diagnostic;
 use rustc_hash::FxHashMap;
 
-use crate::{fix::FixTable, message::DiagnosticMessage};
+use crate::custom_rules::CustomRuleId;
+use crate::fix::FixTable;
+use crate::message::{DiagnosticMessage, SourceDiff};
+use crate::project::IndexedFile;
+use crate::registry::AsRule;
+
+/// A violation found while checking a file. Custom rules all share
+/// [`Rule::CustomRule`](crate::rules::Rule::CustomRule), so the custom rule that raised
+/// the violation, if any, is kept alongside it.
+#[derive(Debug, Clone)]
+pub(crate) struct Violation {
+    pub(crate) diagnostic: Diagnostic,
+    pub(crate) custom_rule: Option<CustomRuleId>,
+}
+
+impl Violation {
+    /// The code the violation is reported under.
+    pub(crate) fn code(&self) -> String {
+        match &self.custom_rule {
+            Some(custom_rule) => custom_rule.code.clone(),
+            None => self.diagnostic.kind.rule().noqa_code().to_string(),
+        }
+    }
+
+    /// The name of the violated rule.
+    pub(crate) fn rule_name(&self) -> &str {
+        match &self.custom_rule {
+            Some(custom_rule) => &custom_rule.name,
+            None => self.diagnostic.kind.rule().as_ref(),
+        }
+    }
+}
+
+impl From<Diagnostic> for Violation {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self {
+            diagnostic,
+            custom_rule: None,
+        }
+    }
+}
 
 #[derive(Debug, Default, PartialEq)]
 pub(crate) struct Diagnostics {
     pub(crate) messages: Vec<DiagnosticMessage>,
     pub(crate) fixed: FixMap,
+    /// Diffs of the fixes that would be applied, when running with `--diff`
+    pub(crate) diffs: Vec<SourceDiff>,
+    /// Files indexed for project rules
+    pub(crate) indexed: Vec<IndexedFile>,
 }
 
 impl Diagnostics {
@@ -19,6 +64,8 @@ impl Diagnostics {
         Self {
             messages,
             fixed: FixMap::default(),
+            diffs: Vec::new(),
+            indexed: Vec::new(),
         }
     }
 
@@ -40,6 +87,8 @@ impl AddAssign for Diagnostics {
     fn add_assign(&mut self, other: Self) {
         self.messages.extend(other.messages);
         self.fixed += other.fixed;
+        self.diffs.extend(other.diffs);
+        self.indexed.extend(other.indexed);
     }
 }
 
@@ -1,11 +1,12 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::process::ExitCode;
 
-use crate::cli::ExplainArgs;
-use crate::rule_selector::PreviewOptions;
+use crate::check::resolve_settings;
+use crate::cli::{CheckArgs, ExplainArgs, GlobalConfigArgs};
+use crate::rule_selector::{ParseError, PreviewOptions, RuleSelector};
 use crate::rules::Rule;
 use crate::settings::DEFAULT_SELECTORS;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use colored::Colorize;
 use itertools::Itertools;
 use ruff_diagnostics::FixAvailability;
@@ -39,8 +40,31 @@ fn ruleset(args: &ExplainArgs) -> anyhow::Result<Vec<Rule>> {
     Ok(rules)
 }
 
+/// Explain the custom and plugin rules referred to by `selectors`
+fn explain_custom_rules(
+    selectors: &[&RuleSelector],
+    global_options: &GlobalConfigArgs,
+) -> Result<Vec<(String, String)>> {
+    let settings = resolve_settings(&CheckArgs::default(), global_options)?;
+    let custom_rules = &settings.check.custom_rules;
+
+    let mut outputs = BTreeMap::new();
+    for selector in selectors {
+        let mut matching = custom_rules.matching(selector).peekable();
+        if matching.peek().is_none() {
+            let (prefix, code) = selector.prefix_and_code();
+            bail!(ParseError::Unknown(format!("{prefix}{code}")));
+        }
+        for rule in matching {
+            let title = format!("# {}: {}\n", rule.code, rule.name);
+            outputs.insert(title, rule.explanation());
+        }
+    }
+    Ok(outputs.into_iter().collect())
+}
+
 /// Check all files, report issues found, and return error code.
-pub fn explain(args: ExplainArgs) -> Result<ExitCode> {
+pub fn explain(args: ExplainArgs, global_options: &GlobalConfigArgs) -> Result<ExitCode> {
     let rules = ruleset(&args)?;
 
     let mut outputs = Vec::new();
@@ -78,6 +102,18 @@ pub fn explain(args: ExplainArgs) -> Result<ExitCode> {
         let title = format!("# {code}: {name}\n");
         outputs.push((title.bright_red(), dedent(body.as_str())));
     }
+
+    // Custom rules are defined in the config file, so it's only read if they're needed
+    let custom_selectors = args
+        .rules
+        .iter()
+        .filter(|selector| matches!(selector, RuleSelector::Custom(_)))
+        .collect_vec();
+    if !custom_selectors.is_empty() {
+        for (title, body) in explain_custom_rules(&custom_selectors, global_options)? {
+            outputs.push((title.bright_red(), body));
+        }
+    }
     outputs.sort_by(|a, b| {
         let ((a_code, _), (b_code, _)) = (a, b);
         a_code.cmp(b_code)
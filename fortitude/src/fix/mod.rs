@@ -2,22 +2,75 @@
 // Copyright 2022 Charles Marsh
 // SPDX-License-Identifier: MIT
 
+use std::borrow::Borrow;
 use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
 
 use itertools::Itertools;
 use rustc_hash::{FxHashMap, FxHashSet};
 
-use ruff_diagnostics::{Diagnostic, Edit, Fix, IsolationLevel, SourceMap};
+use ruff_diagnostics::{Edit, Fix, IsolationLevel, SourceMap};
 use ruff_source_file::{SourceFile, SourceFileBuilder};
 use ruff_text_size::{Ranged, TextLen, TextRange, TextSize};
+use tree_sitter::{InputEdit, Point};
 
+use crate::diagnostics::Violation;
 use crate::locator::Locator;
 use crate::registry::{AsRule, Rule};
 use crate::settings::UnsafeFixes;
 
 pub(crate) mod snippet;
 
-pub type FixTable = FxHashMap<Rule, usize>;
+pub type FixTable = FxHashMap<FixedRule, usize>;
+
+/// A rule that fixes were applied for. Custom rules all share [`Rule::CustomRule`], so
+/// rules are identified by their codes instead.
+#[derive(Debug, Clone)]
+pub struct FixedRule {
+    code: String,
+    name: String,
+}
+
+impl FixedRule {
+    fn new(violation: &Violation) -> Self {
+        Self {
+            code: violation.code(),
+            name: violation.rule_name().to_string(),
+        }
+    }
+
+    /// The code of the rule.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// The name of the rule.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+// The name follows from the code, so only the code is compared. This also allows
+// tables to be indexed by code.
+impl PartialEq for FixedRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code
+    }
+}
+
+impl Eq for FixedRule {}
+
+impl Hash for FixedRule {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.code.hash(state);
+    }
+}
+
+impl Borrow<str> for FixedRule {
+    fn borrow(&self) -> &str {
+        &self.code
+    }
+}
 
 pub(crate) struct FixResult {
     /// The resulting source code, after applying all fixes.
@@ -27,21 +80,25 @@ pub(crate) struct FixResult {
     /// Source map for the fixed source code.
     #[allow(dead_code)]
     pub(crate) source_map: SourceMap,
+    /// The applied edits, in the form needed to update a syntax tree of the original
+    /// source code, so that it can be incrementally re-parsed.
+    pub(crate) input_edits: Vec<InputEdit>,
 }
 
 /// Fix errors in a file, and write the fixed source code to disk.
 pub(crate) fn fix_file(
-    diagnostics: &[Diagnostic],
+    violations: &[Violation],
     locator: &Locator,
     unsafe_fixes: UnsafeFixes,
     name: &str,
 ) -> Option<FixResult> {
     let required_applicability = unsafe_fixes.required_applicability();
 
-    let mut with_fixes = diagnostics
+    let mut with_fixes = violations
         .iter()
-        .filter(|diagnostic| {
-            diagnostic
+        .filter(|violation| {
+            violation
+                .diagnostic
                 .fix
                 .as_ref()
                 .is_some_and(|fix| fix.applies(required_applicability))
@@ -57,7 +114,7 @@ pub(crate) fn fix_file(
 
 /// Apply a series of fixes.
 fn apply_fixes<'a>(
-    diagnostics: impl Iterator<Item = &'a Diagnostic>,
+    violations: impl Iterator<Item = &'a Violation>,
     locator: &'a Locator<'a>,
     name: &str,
 ) -> FixResult {
@@ -67,15 +124,26 @@ fn apply_fixes<'a>(
     let mut isolated: FxHashSet<u32> = FxHashSet::default();
     let mut fixed = FxHashMap::default();
     let mut source_map = SourceMap::default();
-
-    for (rule, fix) in diagnostics
-        .filter_map(|diagnostic| {
-            diagnostic
+    let mut input_edits = Vec::new();
+    // Position of the end of `output`
+    let mut output_end = Point::default();
+
+    for (violation, fix) in violations
+        .filter_map(|violation| {
+            violation
+                .diagnostic
                 .fix
                 .as_ref()
-                .map(|fix| (diagnostic.kind.rule(), fix))
+                .map(|fix| (violation, fix))
+        })
+        .sorted_by(|(violation1, fix1), (violation2, fix2)| {
+            cmp_fix(
+                violation1.diagnostic.kind.rule(),
+                violation2.diagnostic.kind.rule(),
+                fix1,
+                fix2,
+            )
         })
-        .sorted_by(|(rule1, fix1), (rule2, fix2)| cmp_fix(*rule1, *rule2, fix1, fix2))
     {
         let mut edits = fix
             .edits()
@@ -104,12 +172,28 @@ fn apply_fixes<'a>(
             // Add all contents from `last_pos` to `fix.location`.
             let slice = locator.slice(TextRange::new(last_pos.unwrap_or_default(), edit.start()));
             output.push_str(slice);
+            output_end = advance_point(output_end, slice);
 
             // Add the start source marker for the patch.
             source_map.push_start_marker(edit, output.text_len());
 
+            // Record the patch for the syntax tree. Tree-sitter expects each edit to be
+            // relative to the text with the previous edits already applied, which is
+            // the output so far followed by the rest of the original text.
+            let content = edit.content().unwrap_or_default();
+            let start_byte = output.len();
+            input_edits.push(InputEdit {
+                start_byte,
+                old_end_byte: start_byte + edit.range().len().to_usize(),
+                new_end_byte: start_byte + content.len(),
+                start_position: output_end,
+                old_end_position: advance_point(output_end, locator.slice(edit.range())),
+                new_end_position: advance_point(output_end, content),
+            });
+
             // Add the patch itself.
-            output.push_str(edit.content().unwrap_or_default());
+            output.push_str(content);
+            output_end = advance_point(output_end, content);
 
             // Add the end source marker for the added patch.
             source_map.push_end_marker(edit, output.text_len());
@@ -120,7 +204,7 @@ fn apply_fixes<'a>(
         }
 
         applied.extend(applied_edits.drain(..));
-        *fixed.entry(rule).or_default() += 1;
+        *fixed.entry(FixedRule::new(violation)).or_default() += 1;
     }
 
     // Add the remaining content.
@@ -135,6 +219,22 @@ fn apply_fixes<'a>(
         code: source_file,
         fixes: fixed,
         source_map,
+        input_edits,
+    }
+}
+
+/// Return the position reached by moving through `text` from `point`. Columns are
+/// measured in bytes, as in tree-sitter.
+fn advance_point(point: Point, text: &str) -> Point {
+    match text.rfind('\n') {
+        Some(last_newline) => Point {
+            row: point.row + text.matches('\n').count(),
+            column: text.len() - last_newline - 1,
+        },
+        None => Point {
+            row: point.row,
+            column: point.column + text.len(),
+        },
     }
 }
 
@@ -146,22 +246,29 @@ fn cmp_fix(_rule1: Rule, _rule2: Rule, fix1: &Fix, fix2: &Fix) -> std::cmp::Orde
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
     use ruff_diagnostics::{Diagnostic, Edit, Fix, SourceMarker};
-    use ruff_text_size::{Ranged, TextSize};
+    use ruff_text_size::{Ranged, TextRange, TextSize};
 
-    use crate::fix::{apply_fixes, FixResult};
+    use crate::custom_rules::CustomRuleId;
+    use crate::diagnostics::Violation;
+    use crate::fix::{apply_fixes, FixResult, FixedRule};
     use crate::locator::Locator;
     use crate::rules::correctness::use_statements::UseAll;
+    use crate::rules::fortitude::custom_rules::CustomRule;
+    use tree_sitter::Parser;
 
     #[allow(deprecated)]
-    fn create_diagnostics(edit: impl IntoIterator<Item = Edit>) -> Vec<Diagnostic> {
+    fn create_diagnostics(edit: impl IntoIterator<Item = Edit>) -> Vec<Violation> {
         edit.into_iter()
-            .map(|edit| Diagnostic {
-                // The choice of rule here is arbitrary.
-                kind: UseAll {}.into(),
-                range: edit.range(),
-                fix: Some(Fix::safe_edit(edit)),
-                parent: None,
+            .map(|edit| {
+                Violation::from(Diagnostic {
+                    // The choice of rule here is arbitrary.
+                    kind: UseAll {}.into(),
+                    range: edit.range(),
+                    fix: Some(Fix::safe_edit(edit)),
+                    parent: None,
+                })
             })
             .collect()
     }
@@ -174,12 +281,51 @@ mod tests {
             code,
             fixes,
             source_map,
+            ..
         } = apply_fixes(diagnostics.iter(), &locator, "test.f90");
         assert_eq!(code.source_text(), "");
         assert_eq!(fixes.values().sum::<usize>(), 0);
         assert!(source_map.markers().is_empty());
     }
 
+    #[test]
+    fn count_custom_rules_by_code() {
+        let locator = Locator::new("stop\nstop\nprint *, 1\n");
+        let diagnostics = [
+            ("ACME001", "no-stop", 0..4),
+            ("ACME001", "no-stop", 5..9),
+            ("ACME002", "no-print", 10..15),
+        ]
+        .into_iter()
+        .map(|(code, name, range)| {
+            let edit = Edit::range_deletion(TextRange::new(
+                TextSize::new(range.start),
+                TextSize::new(range.end),
+            ));
+            let diagnostic = Diagnostic::new(
+                CustomRule {
+                    message: String::new(),
+                    suggestion: None,
+                },
+                edit.range(),
+            )
+            .with_fix(Fix::safe_edit(edit));
+            Violation {
+                diagnostic,
+                custom_rule: Some(CustomRuleId {
+                    code: code.to_string(),
+                    name: name.to_string(),
+                }),
+            }
+        })
+        .collect::<Vec<_>>();
+        let FixResult { fixes, .. } = apply_fixes(diagnostics.iter(), &locator, "test.f90");
+        assert_eq!(fixes.get("ACME001"), Some(&2));
+        assert_eq!(fixes.get("ACME002"), Some(&1));
+        let names = fixes.keys().map(FixedRule::name).sorted().collect_vec();
+        assert_eq!(names, ["no-print", "no-stop"]);
+    }
+
     #[test]
     fn apply_one_insertion() {
         let locator = Locator::new(
@@ -198,6 +344,7 @@ print("hello world")
             code,
             fixes,
             source_map,
+            ..
         } = apply_fixes(diagnostics.iter(), &locator, "test.f90");
         assert_eq!(
             code.source_text(),
@@ -237,6 +384,7 @@ class A(object):
             code,
             fixes,
             source_map,
+            ..
         } = apply_fixes(diagnostics.iter(), &locator, "test.f90");
         assert_eq!(
             code.source_text(),
@@ -270,6 +418,7 @@ class A(object):
             code,
             fixes,
             source_map,
+            ..
         } = apply_fixes(diagnostics.iter(), &locator, "test.f90");
         assert_eq!(
             code.source_text(),
@@ -306,6 +455,7 @@ class A(object, object, object):
             code,
             fixes,
             source_map,
+            ..
         } = apply_fixes(diagnostics.iter(), &locator, "test.f90");
 
         assert_eq!(
@@ -345,6 +495,7 @@ class A(object):
             code,
             fixes,
             source_map,
+            ..
         } = apply_fixes(diagnostics.iter(), &locator, "test.f90");
         assert_eq!(
             code.source_text(),
@@ -363,4 +514,45 @@ class A:
             ]
         );
     }
+
+    #[test]
+    fn incremental_reparse() -> anyhow::Result<()> {
+        let source = "program test  \n  integer :: x  \n  x = 1\nend program\n";
+        let locator = Locator::new(source);
+        let diagnostics = create_diagnostics([
+            Edit::deletion(TextSize::from(12), TextSize::from(14)),
+            Edit::replacement(
+                "implicit none\n  integer :: x, y".to_string(),
+                TextSize::from(17),
+                TextSize::from(31),
+            ),
+            Edit::insertion(" test".to_string(), TextSize::from(51)),
+        ]);
+        let FixResult {
+            code, input_edits, ..
+        } = apply_fixes(diagnostics.iter(), &locator, "test.f90");
+        assert_eq!(
+            code.source_text(),
+            "program test\n  implicit none\n  integer :: x, y\n  x = 1\nend program test\n"
+        );
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_fortran::LANGUAGE.into())?;
+        let mut tree = parser.parse(source, None).unwrap();
+        for edit in &input_edits {
+            tree.edit(edit);
+        }
+        let incremental = parser.parse(code.source_text(), Some(&tree)).unwrap();
+        let fresh = parser.parse(code.source_text(), None).unwrap();
+
+        assert_eq!(
+            incremental.root_node().to_sexp(),
+            fresh.root_node().to_sexp()
+        );
+        assert_eq!(
+            incremental.root_node().end_position(),
+            fresh.root_node().end_position()
+        );
+        Ok(())
+    }
 }
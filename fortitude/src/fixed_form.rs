@@ -0,0 +1,454 @@
+//! Support for fixed-form source files.
+//!
+//! tree-sitter-fortran only understands free-form source, so fixed-form files are
+//! rewritten as free-form before they're parsed. Every byte keeps its offset, so that
+//! diagnostics raised on the parsed tree point to the right place in the original
+//! file:
+//!
+//! - Comment lines, starting with `C`, `c`, `*`, `D`, or `d` in the first column,
+//!   start with `!` instead.
+//! - Continuation lines are joined onto the line they continue, by replacing the
+//!   line breaks and continuation character between them with spaces. Any comments in
+//!   between are also replaced.
+//! - Anything after column 72 is replaced with spaces.
+//!
+//! Statement labels in columns 1-5 are also valid free-form, and are left as they
+//! are. Blanks are still significant, as in free-form, so keywords split up with
+//! spaces, such as `GO TO`, must be written in a way that free-form allows.
+
+use std::ffi::OsStr;
+use std::ops::Range;
+use std::path::Path;
+
+use ruff_diagnostics::{Edit, Fix};
+use ruff_text_size::{TextRange, TextSize};
+
+use crate::settings::CheckSettings;
+
+/// Columns after this are ignored in fixed-form source.
+pub(crate) const FIXED_FORM_COLUMNS: usize = 72;
+
+/// Does `path` refer to a fixed-form file?
+pub(crate) fn is_fixed_form(path: &Path, settings: &CheckSettings) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|extension| {
+            settings
+                .fixed_form_extensions
+                .iter()
+                .any(|fixed| fixed == extension)
+        })
+}
+
+/// Is `line` a whole-line comment in fixed-form source?
+pub(crate) fn is_comment_line(line: &str) -> bool {
+    match line.chars().next() {
+        Some('C' | 'c' | '*' | 'D' | 'd' | '!') => true,
+        // `!` starts a comment anywhere except the continuation column
+        _ => line
+            .char_indices()
+            .find(|(_, c)| !c.is_whitespace())
+            .is_some_and(|(index, c)| c == '!' && index != 5),
+    }
+}
+
+/// Rewrite fixed-form `source` as free-form, keeping the same length.
+pub(crate) fn to_free_form(source: &str) -> String {
+    let mut output = source.to_string();
+    // Where the last statement line ends: either its line break, or the start of a
+    // trailing comment
+    let mut statement_end: Option<usize> = None;
+
+    for line in lines(source) {
+        match line.kind {
+            LineKind::Comment => {
+                if !source[line.start..].starts_with(|c: char| c == '!' || c.is_whitespace()) {
+                    output.replace_range(line.start..line.start + 1, "!");
+                }
+            }
+            LineKind::Statement(statement) => {
+                if let Some(marker) = statement.continuation.clone() {
+                    blank(&mut output, marker);
+                    if let Some(end) = statement_end {
+                        blank(&mut output, end..line.start);
+                    }
+                }
+                if let Some(zero) = statement.zero {
+                    blank(&mut output, zero..zero + 1);
+                }
+                if let Some(overflow) = statement.overflow {
+                    blank(&mut output, overflow..line.end);
+                }
+                statement_end = Some(statement.code_end(line.end));
+            }
+            LineKind::Other => {}
+        }
+    }
+
+    output
+}
+
+/// The edits needed to convert fixed-form `source` to free-form, without changing
+/// the layout of the code:
+///
+/// - Comment lines start with `!` instead.
+/// - Lines that are continued end with `&`, and the continuation character on the
+///   following line is replaced with `&`.
+/// - Anything after column 72 is removed, along with any padding before it, unless
+///   it's part of a comment.
+pub(crate) fn free_form_edits(source: &str) -> Vec<Edit> {
+    let lines = lines(source);
+    let mut edits = Vec::new();
+
+    let statements: Vec<_> = lines
+        .iter()
+        .filter_map(|line| match &line.kind {
+            LineKind::Statement(statement) => Some((line, statement)),
+            _ => None,
+        })
+        .collect();
+
+    for line in &lines {
+        if matches!(line.kind, LineKind::Comment)
+            && !source[line.start..].starts_with(|c: char| c == '!' || c.is_whitespace())
+        {
+            edits.push(Edit::range_replacement(
+                "!".to_string(),
+                text_range(line.start..line.start + 1),
+            ));
+        }
+    }
+
+    for (index, (line, statement)) in statements.iter().enumerate() {
+        let is_continued = statements
+            .get(index + 1)
+            .is_some_and(|(_, next)| next.continuation.is_some());
+
+        if let Some(marker) = statement.continuation.clone() {
+            edits.push(Edit::range_replacement("&".to_string(), text_range(marker)));
+        }
+        if let Some(zero) = statement.zero {
+            edits.push(Edit::range_replacement(
+                " ".to_string(),
+                text_range(zero..zero + 1),
+            ));
+        }
+
+        // Character literals carry on directly after the `&` on the next line, so
+        // mustn't have any extra space added to them
+        let ampersand = if statement.open_quote.is_some() {
+            "&"
+        } else {
+            " &"
+        };
+        match (statement.comment, statement.overflow) {
+            (Some(comment), _) => {
+                if is_continued {
+                    edits.push(Edit::insertion("& ".to_string(), text_size(comment)));
+                }
+            }
+            (None, Some(overflow)) => {
+                // Also remove any padding before the ignored columns, unless it's part
+                // of a character literal
+                let code_end = if statement.open_quote.is_some() {
+                    overflow
+                } else {
+                    line.start + source[line.start..overflow].trim_end().len()
+                };
+                let range = text_range(code_end..line.end);
+                if is_continued {
+                    edits.push(Edit::range_replacement(ampersand.to_string(), range));
+                } else {
+                    edits.push(Edit::range_deletion(range));
+                }
+            }
+            (None, None) => {
+                if is_continued {
+                    edits.push(Edit::insertion(ampersand.to_string(), text_size(line.end)));
+                }
+            }
+        }
+    }
+
+    edits.sort_by_key(Edit::start);
+    edits
+}
+
+/// The ranges that join each continuation line onto the statement line it continues,
+/// from the end of that line to the continuation character.
+pub(crate) fn continuation_breaks(source: &str) -> Vec<TextRange> {
+    let mut breaks = Vec::new();
+    let mut statement_end: Option<usize> = None;
+    for line in lines(source) {
+        if let LineKind::Statement(statement) = line.kind {
+            if let (Some(marker), Some(end)) = (statement.continuation, statement_end) {
+                breaks.push(text_range(end..marker.end));
+            }
+            statement_end = Some(line.end);
+        }
+    }
+    breaks
+}
+
+/// Does `fix` replace any text across a continuation line?
+///
+/// Fixes are made to the free-form text that was parsed, so one that spans a
+/// continuation could replace it with free-form layout.
+pub(crate) fn fix_spans_continuation(fix: &Fix, breaks: &[TextRange]) -> bool {
+    fix.edits().iter().any(|edit| {
+        breaks
+            .iter()
+            .any(|range| edit.start() < range.end() && edit.end() > range.start())
+    })
+}
+
+/// A line of fixed-form source.
+struct Line {
+    /// Byte offset of the start of the line.
+    start: usize,
+    /// Byte offset of the end of the line, before any line break.
+    end: usize,
+    kind: LineKind,
+}
+
+enum LineKind {
+    Comment,
+    Statement(Statement),
+    /// Blank lines and preprocessor directives, which may appear between continuation
+    /// lines without ending the statement.
+    Other,
+}
+
+/// The parts of a statement line that need changing for free-form. All are byte
+/// offsets into the source.
+struct Statement {
+    /// The continuation character, on continuation lines.
+    continuation: Option<Range<usize>>,
+    /// A `0` in column 6, which marks an initial line.
+    zero: Option<usize>,
+    /// Column 73, on lines that are longer than that.
+    overflow: Option<usize>,
+    /// The `!` starting a trailing comment.
+    comment: Option<usize>,
+    /// The quote of a character literal left open at the end of the line.
+    open_quote: Option<char>,
+}
+
+impl Statement {
+    /// The end of the code on the line.
+    fn code_end(&self, line_end: usize) -> usize {
+        self.comment.or(self.overflow).unwrap_or(line_end)
+    }
+}
+
+fn lines(source: &str) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut open_quote = None;
+    let mut start = 0;
+
+    for line in source.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let end = start + content.len();
+
+        let kind = if content.trim().is_empty() || content.starts_with('#') {
+            LineKind::Other
+        } else if is_comment_line(content) {
+            LineKind::Comment
+        } else {
+            let statement = statement(content, start, open_quote);
+            open_quote = statement.open_quote;
+            LineKind::Statement(statement)
+        };
+        lines.push(Line { start, end, kind });
+        start += line.len();
+    }
+
+    lines
+}
+
+/// Find the parts of a statement line starting at byte `start`, given the character
+/// literal left open by the previous statement line.
+fn statement(content: &str, start: usize, open_quote: Option<char>) -> Statement {
+    let (continuation, body_start) = continuation_marker(content);
+    let zero = (body_start == 6 && content.as_bytes()[5] == b'0').then_some(start + 5);
+
+    let mut quote = continuation.as_ref().and(open_quote);
+    let mut comment = None;
+    let mut overflow = None;
+    for (column, (index, c)) in content.char_indices().enumerate() {
+        if column >= FIXED_FORM_COLUMNS {
+            overflow = Some(start + index);
+            break;
+        }
+        if index < body_start {
+            continue;
+        }
+        match (quote, c) {
+            (Some(open), _) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '!') => {
+                comment = Some(start + index);
+                break;
+            }
+            (None, _) => {}
+        }
+    }
+
+    Statement {
+        continuation: continuation.map(|marker| start + marker.start..start + marker.end),
+        zero,
+        overflow,
+        comment,
+        open_quote: quote,
+    }
+}
+
+/// Find the byte range of the continuation character in a statement line, if there is
+/// one, and where the statement itself starts.
+fn continuation_marker(line: &str) -> (Option<Range<usize>>, usize) {
+    // In tab format, a tab after the label starts the statement, and is followed by a
+    // non-zero digit on continuation lines
+    if let Some(tab) = line
+        .char_indices()
+        .take(6)
+        .take_while(|(_, c)| c.is_ascii_digit() || *c == ' ' || *c == '\t')
+        .find_map(|(index, c)| (c == '\t').then_some(index))
+    {
+        return match line[tab + 1..].chars().next() {
+            Some('1'..='9') => (Some(tab + 1..tab + 2), tab + 2),
+            _ => (None, tab + 1),
+        };
+    }
+
+    match line.char_indices().nth(5) {
+        Some((index, c)) if c != ' ' && c != '0' => {
+            let end = index + c.len_utf8();
+            (Some(index..end), end)
+        }
+        Some((index, c)) => (None, index + c.len_utf8()),
+        None => (None, line.len()),
+    }
+}
+
+/// Replace a range of `output` with spaces, keeping the same length.
+fn blank(output: &mut String, range: Range<usize>) {
+    output.replace_range(range.clone(), &" ".repeat(range.len()));
+}
+
+fn text_size(offset: usize) -> TextSize {
+    TextSize::try_from(offset).unwrap()
+}
+
+fn text_range(range: Range<usize>) -> TextRange {
+    TextRange::new(text_size(range.start), text_size(range.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use textwrap::dedent;
+
+    #[test]
+    fn comments_and_labels() {
+        let source = dedent(
+            "
+            C     A comment
+            *     Another comment
+                  PROGRAM TEST
+                  INTEGER I
+                  DO 10 I = 1, 10
+               10 CONTINUE
+            d     PRINT *, I
+                  END
+            ",
+        );
+        let expected = dedent(
+            "
+            !     A comment
+            !     Another comment
+                  PROGRAM TEST
+                  INTEGER I
+                  DO 10 I = 1, 10
+               10 CONTINUE
+            !     PRINT *, I
+                  END
+            ",
+        );
+        assert_eq!(to_free_form(&source), expected);
+    }
+
+    #[test]
+    fn continuation_lines() {
+        let source = [
+            "      X = 1 +    ! trailing comment",
+            "C     comment between continuations",
+            "     &    2 + 'it''s a ! in",
+            "     1 a string'",
+            "      Y = 2",
+            "",
+        ]
+        .join("\n");
+        let free_form = to_free_form(&source);
+        assert_eq!(free_form.len(), source.len());
+        // Only the line break before `Y` is left
+        assert_eq!(free_form.lines().count(), 2);
+        assert_eq!(
+            free_form.split_whitespace().collect::<Vec<_>>().join(" "),
+            "X = 1 + 2 + 'it''s a ! in a string' Y = 2"
+        );
+    }
+
+    #[test]
+    fn truncate_after_column_72() {
+        let source = format!("      X = 1{}2\n", " ".repeat(61));
+        let free_form = to_free_form(&source);
+        assert_eq!(free_form.len(), source.len());
+        assert_eq!(free_form.trim(), "X = 1");
+    }
+
+    #[test]
+    fn tab_format() {
+        let source = "10\tX = 1 +\n\t1 2\n";
+        assert_eq!(to_free_form(source), "10\tX = 1 + \t  2\n");
+    }
+
+    #[test]
+    fn fixes_across_continuations() {
+        let source = [
+            "      X = DBLE(1)",
+            "      Y = DBLE(",
+            "C     comment between continuations",
+            "     &  2)",
+            "",
+        ]
+        .join("\n");
+        let breaks = continuation_breaks(&source);
+        assert_eq!(
+            breaks,
+            [TextRange::new(TextSize::new(33), TextSize::new(76))]
+        );
+
+        let replace = |start: u32, end: u32| {
+            Fix::unsafe_edit(Edit::range_replacement(
+                "real(1, dp)".to_string(),
+                TextRange::new(start.into(), end.into()),
+            ))
+        };
+        // `DBLE(1)`
+        assert!(!fix_spans_continuation(&replace(10, 17), &breaks));
+        // `DBLE(` to `2)`
+        assert!(fix_spans_continuation(&replace(28, 80), &breaks));
+        // Just `2`, on the continuation line
+        assert!(!fix_spans_continuation(&replace(78, 79), &breaks));
+    }
+
+    #[test]
+    fn comment_lines() {
+        assert!(is_comment_line("C comment"));
+        assert!(is_comment_line("   ! comment"));
+        assert!(!is_comment_line("     ! continuation"));
+        assert!(!is_comment_line("      X = 1 ! comment"));
+    }
+}
@@ -0,0 +1,83 @@
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use crate::check::{read_to_string, resolve_settings, write_diff};
+use crate::cli::{CheckArgs, FormatArgs, GlobalConfigArgs};
+use crate::fixed_form::is_fixed_form;
+use crate::formatter::format_source;
+use crate::fs::{get_files, relativize_path};
+use crate::logging::LogLevel;
+use crate::warn_user;
+
+/// Format files, or check whether they're formatted.
+pub fn format(args: FormatArgs, global_options: &GlobalConfigArgs) -> Result<ExitCode> {
+    let check_args = CheckArgs {
+        files: Some(args.files),
+        ..CheckArgs::default()
+    };
+    let settings = resolve_settings(&check_args, global_options)?;
+    let files = get_files(&settings.file_resolver, false, None)?;
+    let write = !(args.check || args.diff);
+
+    let mut changed = 0;
+    let mut unchanged = 0;
+    let mut failed = 0;
+    // Fixed-form files have a layout of their own, so they're left alone
+    for path in files
+        .iter()
+        .filter(|path| !is_fixed_form(path, &settings.check))
+    {
+        let source =
+            read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let formatted = match format_source(&source, &settings.format) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                warn_user!("Failed to format {}: {error}", relativize_path(path));
+                failed += 1;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            unchanged += 1;
+            continue;
+        }
+        changed += 1;
+
+        if args.diff {
+            write_diff(path, &source, &formatted)?;
+        } else if args.check {
+            println!("Would reformat: {}", relativize_path(path).bold());
+        } else {
+            std::fs::write(path, formatted)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+    }
+
+    if global_options.log_level() >= LogLevel::Default {
+        let mut summary = vec![if write {
+            format!("{changed} file(s) reformatted")
+        } else {
+            format!("{changed} file(s) would be reformatted")
+        }];
+        if unchanged > 0 {
+            summary.push(if write {
+                format!("{unchanged} file(s) left unchanged")
+            } else {
+                format!("{unchanged} file(s) already formatted")
+            });
+        }
+        if failed > 0 {
+            summary.push(format!("{failed} file(s) could not be formatted"));
+        }
+        eprintln!("{}", summary.join(", ").bold());
+    }
+
+    if failed > 0 || (!write && changed > 0) {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
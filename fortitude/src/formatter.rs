@@ -0,0 +1,526 @@
+//! Reprint free-form Fortran in a consistent layout.
+//!
+//! The formatter works on the leaves of the tree-sitter CST, in order. Line breaks
+//! between them are kept as they are, but everything else between them is rewritten:
+//!
+//! - Each line is indented according to how deeply it's nested in constructs (those
+//!   with an `end` statement), with branches such as `else`, `case`, and `contains`
+//!   written at the same level as the start of their construct.
+//! - Continuation lines are indented a fixed amount past the start of their statement.
+//! - Runs of spaces between tokens on the same line are collapsed, and, optionally,
+//!   operators, commas, and brackets are spaced consistently.
+//! - Keywords are written in a consistent case.
+//!
+//! Comments, strings, numbers, `format` statements, and preprocessor directives are
+//! always written exactly as they are.
+
+use std::borrow::Cow;
+
+use anyhow::{bail, Context, Result};
+use tree_sitter::{Node, Parser, Tree};
+
+use crate::ast::FortitudeNode;
+use crate::settings::{FormatSettings, KeywordCase};
+
+/// Nodes that are written exactly as they are, including any line breaks inside them.
+const VERBATIM: &[&str] = &[
+    "comment",
+    "string_literal",
+    "number_literal",
+    "boz_literal",
+    "format_statement",
+    "preproc_def",
+    "preproc_function_def",
+    "preproc_include",
+    "preproc_call",
+];
+
+/// Nodes that start a new branch of a construct. These are written at the same level
+/// as the statement that opens the construct.
+const BRANCHES: &[&str] = &[
+    "elseif_clause",
+    "else_clause",
+    "elsewhere_clause",
+    "case_statement",
+    "type_statement",
+    "rank_statement",
+    "contains_statement",
+];
+
+/// Nodes that may have binary operators or assignments as direct children.
+const OPERATORS: &[&str] = &[
+    "math_expression",
+    "relational_expression",
+    "logical_expression",
+    "concatenation_expression",
+    "assignment_statement",
+    "pointer_association_statement",
+    "init_declarator",
+    "pointer_init_declarator",
+    "loop_control_expression",
+];
+
+/// Format free-form Fortran `source`.
+///
+/// Returns an error if `source` can't be parsed, or if formatting it would change
+/// its tokens.
+pub(crate) fn format_source(source: &str, settings: &FormatSettings) -> Result<String> {
+    let tree = parse(source)?;
+    let root = tree.root_node();
+    if root.has_error() {
+        bail!("source contains syntax errors");
+    }
+
+    let formatted = Formatter::new(source, root, settings).format();
+
+    // The formatter should only ever change the whitespace between tokens, and the
+    // case of keywords, so check that's the case before we let anyone use the result
+    let formatted_tree = parse(&formatted)?;
+    let formatted_root = formatted_tree.root_node();
+    if formatted_root.has_error()
+        || !same_tokens(&tokens(root), source, &tokens(formatted_root), &formatted)
+    {
+        bail!("formatting would change the meaning of the code");
+    }
+
+    Ok(formatted)
+}
+
+fn parse(source: &str) -> Result<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_fortran::LANGUAGE.into())
+        .context("Error loading Fortran grammar")?;
+    parser.parse(source, None).context("Failed to parse")
+}
+
+/// The leaves of the tree, treating verbatim nodes as leaves, ignoring any that are
+/// empty.
+fn tokens(root: Node) -> Vec<Node> {
+    let mut tokens = Vec::new();
+    push_tokens(root, &mut tokens);
+    tokens
+}
+
+fn push_tokens<'tree>(node: Node<'tree>, tokens: &mut Vec<Node<'tree>>) {
+    if node.child_count() == 0 || VERBATIM.contains(&node.kind()) {
+        if node.start_byte() < node.end_byte() {
+            tokens.push(node);
+        }
+        return;
+    }
+    for child in node.children(&mut node.walk()) {
+        push_tokens(child, tokens);
+    }
+}
+
+fn same_tokens(original: &[Node], source: &str, formatted: &[Node], output: &str) -> bool {
+    original.len() == formatted.len()
+        && original.iter().zip(formatted).all(|(old, new)| {
+            old.kind() == new.kind()
+                && source[old.byte_range()].eq_ignore_ascii_case(&output[new.byte_range()])
+        })
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    settings: &'a FormatSettings,
+    tokens: Vec<Node<'a>>,
+    /// For the first token on each line: its offset, and the offset of the start of
+    /// the statement it's part of. Comment lines are their own statement.
+    lines: Vec<(usize, usize)>,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &'a str, root: Node<'a>, settings: &'a FormatSettings) -> Self {
+        let tokens = tokens(root);
+
+        let mut lines = Vec::new();
+        let mut statement_start = 0;
+        let mut continued = false;
+        for (index, token) in tokens.iter().enumerate() {
+            let first_on_line = index == 0
+                || source[tokens[index - 1].end_byte()..token.start_byte()].contains('\n');
+            if first_on_line {
+                if token.kind() == "comment" || source[token.start_byte()..].starts_with('#') {
+                    lines.push((token.start_byte(), token.start_byte()));
+                    continue;
+                }
+                if !continued && token.kind() != "&" {
+                    statement_start = token.start_byte();
+                }
+                continued = false;
+                lines.push((token.start_byte(), statement_start));
+            } else if token.kind() == "&" {
+                continued = true;
+            }
+        }
+
+        Self {
+            source,
+            settings,
+            tokens,
+            lines,
+        }
+    }
+
+    fn format(&self) -> String {
+        let source = self.source;
+        let mut output = String::with_capacity(source.len());
+
+        let mut index = 0;
+        let mut previous_end = 0;
+        // The previous token on the current line, and whether it started the line
+        let mut previous: Option<(Node, bool)> = None;
+        let mut statement_depth = 0;
+        let mut continued = false;
+
+        while let Some(token) = self.tokens.get(index) {
+            let gap = &source[previous_end..token.start_byte()];
+            let first_on_line = index == 0 || gap.contains('\n');
+
+            if !gap.trim().is_empty() {
+                // Something the parser hasn't recorded as a token, so leave it alone
+                output.push_str(gap);
+            } else if first_on_line {
+                push_line_breaks(&mut output, gap);
+
+                if source[token.start_byte()..].starts_with('#') {
+                    let start = line_start(source, token.start_byte());
+                    let end = self.directive_end(&mut index);
+                    output.push_str(&source[start..end]);
+                    previous_end = end;
+                    previous = None;
+                    continue;
+                }
+
+                let is_comment = token.kind() == "comment";
+                let is_continuation = continued || token.kind() == "&";
+                if !is_comment && !is_continuation {
+                    statement_depth = self.depth(token);
+                }
+                if !is_comment {
+                    continued = false;
+                }
+
+                let mut indent = if is_comment && !is_continuation {
+                    self.depth(token) * self.settings.indent_width
+                } else {
+                    statement_depth * self.settings.indent_width
+                };
+                if is_continuation {
+                    indent += self.settings.continuation_indent;
+                }
+                output.push_str(&" ".repeat(indent));
+            } else if let Some((previous, previous_first)) = previous {
+                output.push_str(self.space(&previous, previous_first, token, gap));
+            }
+
+            output.push_str(&self.text(token));
+            if token.kind() == "&" && !first_on_line {
+                continued = true;
+            }
+            previous = Some((*token, first_on_line));
+            previous_end = token.end_byte();
+            index += 1;
+        }
+
+        let rest = &source[previous_end..];
+        if rest.trim().is_empty() {
+            push_line_breaks(&mut output, rest);
+        } else {
+            output.push_str(rest);
+        }
+
+        output
+    }
+
+    /// Find the end of the preprocessor directive starting at the token at `index`,
+    /// including any lines it's continued onto, and move `index` past it.
+    fn directive_end(&self, index: &mut usize) -> usize {
+        let start = self.tokens[*index].start_byte();
+        let mut end = line_end(self.source, start);
+        while let Some(token) = self.tokens.get(*index) {
+            if token.start_byte() >= end {
+                break;
+            }
+            end = end.max(line_end(
+                self.source,
+                token.end_byte().saturating_sub(1).max(start),
+            ));
+            *index += 1;
+        }
+        end
+    }
+
+    /// The offset of the start of the statement that the line containing `offset` is
+    /// part of.
+    fn statement_start(&self, offset: usize) -> usize {
+        let line = self.lines.partition_point(|(start, _)| *start <= offset);
+        line.checked_sub(1).map_or(0, |line| self.lines[line].1)
+    }
+
+    /// How many levels `token`, the first on its line, should be indented by.
+    fn depth(&self, token: &Node) -> usize {
+        let statement = self.statement_start(token.start_byte());
+        let mut depth = 0;
+        let mut branches = 0;
+        for ancestor in token.ancestors() {
+            let opened_here = self.statement_start(ancestor.start_byte()) == statement;
+            if let Some(end) = end_statement(&ancestor) {
+                if !opened_here && self.statement_start(end.start_byte()) != statement {
+                    depth += 1;
+                }
+            }
+            if opened_here && BRANCHES.contains(&ancestor.kind()) {
+                branches += 1;
+            }
+        }
+        depth - branches.min(depth)
+    }
+
+    /// The text to put between `previous` and `token`, on the same line.
+    fn space(&self, previous: &Node, previous_first: bool, token: &Node, gap: &'a str) -> &'a str {
+        // Keep inline comments lined up, and leave the space after a leading `&` alone
+        if previous_first && previous.kind() == "&" {
+            return gap;
+        }
+        if token.kind() == "comment" {
+            return if gap.is_empty() { " " } else { gap };
+        }
+        if token.kind() == "&" {
+            return " ";
+        }
+
+        if self.settings.operator_spacing {
+            if matches!(token.kind(), "," | ";" | ")" | "/)" | "]" | "%")
+                || matches!(previous.kind(), "(" | "(/" | "[" | "%")
+                || (token.kind() == "(" && previous.kind() == "identifier")
+            {
+                return "";
+            }
+            if matches!(previous.kind(), "," | ";" | "::")
+                || token.kind() == "::"
+                || is_operator(previous)
+                || is_operator(token)
+            {
+                return " ";
+            }
+        }
+
+        if gap.is_empty() {
+            ""
+        } else {
+            " "
+        }
+    }
+
+    /// The text of `token`, in the configured case if it's a keyword.
+    fn text(&self, token: &Node) -> Cow<'a, str> {
+        let text = &self.source[token.byte_range()];
+        if !is_keyword(token, text) {
+            return Cow::Borrowed(text);
+        }
+        match self.settings.keyword_case {
+            KeywordCase::Lower => Cow::Owned(text.to_ascii_lowercase()),
+            KeywordCase::Upper => Cow::Owned(text.to_ascii_uppercase()),
+            KeywordCase::Preserve => Cow::Borrowed(text),
+        }
+    }
+}
+
+/// The `end` statement of a construct, if `node` is one.
+fn end_statement<'tree>(node: &Node<'tree>) -> Option<Node<'tree>> {
+    node.named_children(&mut node.walk())
+        .skip(1)
+        .find(|child| child.kind().starts_with("end_") && child.kind().ends_with("_statement"))
+}
+
+/// Is `token` a binary operator or an assignment?
+fn is_operator(token: &Node) -> bool {
+    !token.is_named()
+        && token.kind() != ","
+        && token
+            .parent()
+            .is_some_and(|parent| OPERATORS.contains(&parent.kind()))
+}
+
+/// Is `token` a keyword, intrinsic type, or dotted operator? Some keywords, such as
+/// `end do`, may include spaces.
+fn is_keyword(token: &Node, text: &str) -> bool {
+    (!token.is_named() || token.kind() == "intrinsic_type")
+        && text.chars().any(|c| c.is_ascii_alphabetic())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphabetic() || matches!(c, '_' | '.' | ' ' | '\t'))
+}
+
+/// Write the line breaks in `gap`, dropping any other whitespace.
+fn push_line_breaks(output: &mut String, gap: &str) {
+    for line in gap
+        .split_inclusive('\n')
+        .filter(|line| line.ends_with('\n'))
+    {
+        if line.ends_with("\r\n") {
+            output.push_str("\r\n");
+        } else {
+            output.push('\n');
+        }
+    }
+}
+
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |index| index + 1)
+}
+
+fn line_end(source: &str, offset: usize) -> usize {
+    source[offset..]
+        .find('\n')
+        .map_or(source.len(), |index| offset + index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use textwrap::dedent;
+
+    fn format(source: &str, settings: &FormatSettings) -> String {
+        format_source(&dedent(source), settings).unwrap()
+    }
+
+    #[test]
+    fn indentation() {
+        let source = "
+            module test
+            implicit none
+            contains
+            subroutine foo(x)
+            integer, intent(inout) :: x
+            ! A comment
+            if (x > 1) then
+            x = 1
+            else if (x < 0) then
+            x = 0
+            else
+            do while (x < 10)
+            x = x + 1
+            end do
+            end if
+            select case (x)
+            case (1)
+            x = 2
+            case default
+            x = 3
+            end select
+            end subroutine foo
+            end module test
+            ";
+        let expected = dedent(
+            "
+            module test
+              implicit none
+            contains
+              subroutine foo(x)
+                integer, intent(inout) :: x
+                ! A comment
+                if (x > 1) then
+                  x = 1
+                else if (x < 0) then
+                  x = 0
+                else
+                  do while (x < 10)
+                    x = x + 1
+                  end do
+                end if
+                select case (x)
+                case (1)
+                  x = 2
+                case default
+                  x = 3
+                end select
+              end subroutine foo
+            end module test
+            ",
+        );
+        assert_eq!(format(source, &FormatSettings::default()), expected);
+    }
+
+    #[test]
+    fn spacing_and_case() {
+        let source = "
+            PROGRAM TEST
+                INTEGER::X ( 3 )
+                X(1)=2*X( 2 )+1   ! Inline comment
+                IF(X(1) .GT.  2 .AND. X(2)/=1) PRINT *,X(1) ,'a  b'
+            END PROGRAM TEST
+            ";
+        let expected = dedent(
+            "
+            program TEST
+              integer :: X(3)
+              X(1) = 2 * X(2) + 1   ! Inline comment
+              if(X(1) .gt. 2 .and. X(2) /= 1) print *, X(1), 'a  b'
+            end program TEST
+            ",
+        );
+        assert_eq!(format(source, &FormatSettings::default()), expected);
+    }
+
+    #[test]
+    fn continuation_lines() {
+        let source = "
+            program test
+            call foo(1, &
+            2, &
+                                & 3)
+            end program test
+            ";
+        let expected = dedent(
+            "
+            program test
+              call foo(1, &
+                  2, &
+                  & 3)
+            end program test
+            ",
+        );
+        assert_eq!(format(source, &FormatSettings::default()), expected);
+    }
+
+    #[test]
+    fn options() {
+        let settings = FormatSettings {
+            indent_width: 4,
+            continuation_indent: 2,
+            keyword_case: KeywordCase::Upper,
+            operator_spacing: false,
+        };
+        let source = "
+            program test
+            x=1+  2
+            call foo(1, &
+            2)
+            end program test
+            ";
+        let expected = dedent(
+            "
+            PROGRAM test
+                x=1+ 2
+                CALL foo(1, &
+                  2)
+            END PROGRAM test
+            ",
+        );
+        assert_eq!(format(source, &settings), expected);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(format_source(
+            "program test\n  x = = 1\nend program\n",
+            &FormatSettings::default()
+        )
+        .is_err());
+    }
+}
@@ -11,11 +11,12 @@ use log::debug;
 use path_absolutize::Absolutize;
 use ruff_cache::{CacheKey, CacheKeyHasher};
 use ruff_macros::CacheKey;
+use rustc_hash::FxHashSet;
 use serde::{de, Deserialize, Deserializer, Serialize};
 
 use crate::registry::Rule;
 use crate::rule_selector::CompiledPerFileIgnoreList;
-use crate::settings::FileResolverSettings;
+use crate::settings::{FileResolverSettings, PreviewMode};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Serialize, CacheKey)]
 pub enum FilePattern {
@@ -247,6 +248,24 @@ pub const FORTRAN_EXTS: &[&str] = &[
     "f90", "F90", "f95", "F95", "f03", "F03", "f08", "F08", "f18", "F18", "f23", "F23",
 ];
 
+/// Default extensions of fixed-form files
+pub const FIXED_FORM_EXTS: &[&str] = &["f", "F", "for", "FOR", "f77", "F77", "ftn", "FTN"];
+
+/// The extensions to check if none are given. Fixed-form files are only included in
+/// preview.
+pub(crate) fn default_file_extensions(preview: PreviewMode) -> Vec<String> {
+    let fixed_form = if preview.is_enabled() {
+        FIXED_FORM_EXTS
+    } else {
+        &[]
+    };
+    FORTRAN_EXTS
+        .iter()
+        .chain(fixed_form)
+        .map(|ext| ext.to_string())
+        .collect()
+}
+
 // Default paths to exclude when searching paths
 pub(crate) static EXCLUDE_BUILTINS: &[FilePattern] = &[
     FilePattern::Builtin(".git"),
@@ -280,8 +299,13 @@ fn resolve_default_files(files: &[PathBuf], is_stdin: bool) -> Vec<PathBuf> {
     }
 }
 
-/// Expand the input list of files to include all Fortran files.
-pub fn get_files(resolver: &FileResolverSettings, is_stdin: bool) -> anyhow::Result<Vec<PathBuf>> {
+/// Expand the input list of files to include all Fortran files. If `changed_files` is
+/// given and no paths were provided, only files in that set are included.
+pub fn get_files(
+    resolver: &FileResolverSettings,
+    is_stdin: bool,
+    changed_files: Option<&FxHashSet<PathBuf>>,
+) -> anyhow::Result<Vec<PathBuf>> {
     debug!("Gathering files");
     debug!("Project root: {:?}", resolver.project_root);
     let paths = resolve_default_files(&resolver.files, is_stdin);
@@ -342,6 +366,15 @@ pub fn get_files(resolver: &FileResolverSettings, is_stdin: bool) -> anyhow::Res
         vec![]
     };
 
+    // Only search for changed files when no paths were given
+    let dir_contents = match changed_files {
+        Some(changed_files) if resolver.files.is_empty() => dir_contents
+            .into_iter()
+            .filter(|path| changed_files.contains(&normalize_path(path)))
+            .collect(),
+        _ => dir_contents,
+    };
+
     // Return all files found
     Ok(files.into_iter().chain(dir_contents).collect())
 }
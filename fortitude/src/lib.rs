@@ -1,26 +1,44 @@
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+mod add_allow;
 mod allow_comments;
 mod ast;
+mod baseline;
+mod cache;
+mod changed_lines;
 pub mod check;
+pub mod clean;
 pub mod cli;
 mod configuration;
+pub mod convert;
+mod custom_rules;
 mod diagnostics;
 pub mod explain;
 mod fix;
+mod fixed_form;
+pub mod format;
+mod formatter;
 mod fs;
+pub mod linter;
 pub mod locator;
 pub mod logging;
 pub mod message;
 pub mod options;
 pub mod options_base;
+pub mod plugin;
+mod preprocessor;
 mod printer;
+mod project;
 pub mod registry;
+mod resolver;
 mod rule_redirects;
 mod rule_selector;
 pub mod rule_table;
 pub mod rules;
+pub mod semantic;
+pub mod server;
 pub mod settings;
+pub mod severity;
 mod show_files;
 mod show_settings;
 pub mod stdin;
@@ -28,12 +46,14 @@ pub mod stdin;
 mod test;
 mod text_helpers;
 pub mod version;
+mod watch;
 pub use crate::registry::clap_completion::RuleParser;
 pub use crate::rule_selector::clap_completion::RuleSelectorParser;
 
 use ast::FortitudeNode;
 use ruff_diagnostics::{Diagnostic, DiagnosticKind};
 use ruff_source_file::SourceFile;
+use semantic::SemanticModel;
 use settings::Settings;
 use std::path::Path;
 use tree_sitter::Node;
@@ -72,6 +92,20 @@ pub trait AstRule {
     fn entrypoints() -> Vec<&'static str>;
 }
 
+/// Implemented by rules that analyse the abstract syntax tree, and need the semantic
+/// model of the file to look up the symbols visible from `node`.
+pub trait SemanticAstRule {
+    fn check(
+        settings: &Settings,
+        node: &Node,
+        source: &SourceFile,
+        semantic: &SemanticModel,
+    ) -> Option<Vec<Diagnostic>>;
+
+    /// Return list of tree-sitter node types on which a rule should trigger.
+    fn entrypoints() -> Vec<&'static str>;
+}
+
 /// Simplify making a `SourceFile` in tests
 #[cfg(test)]
 pub fn test_file(source: &str) -> SourceFile {
@@ -0,0 +1,209 @@
+//! A library interface for checking Fortran source code, for tools that embed
+//! Fortitude rather than running the command line interface.
+//!
+//! Build [`Settings`] with [`SettingsBuilder`](crate::settings::SettingsBuilder), then
+//! use them to create a [`Linter`], which can check and fix any number of files:
+//!
+//! ```
+//! use std::path::Path;
+//!
+//! use fortitude::linter::Linter;
+//! use fortitude::options::Options;
+//! use fortitude::settings::SettingsBuilder;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let options: Options = toml::from_str(
+//!     r#"
+//!     [check]
+//!     select = ["C001", "S061"]
+//!     "#,
+//! )?;
+//! let settings = SettingsBuilder::new().options(options).build()?;
+//! let linter = Linter::new(settings);
+//!
+//! let source = "program hello\nend program\n";
+//! let messages = linter.lint_source(Path::new("hello.f90"), source)?;
+//! let codes: Vec<_> = messages.iter().map(|message| message.code()).collect();
+//! assert_eq!(codes, ["C001", "S061"]);
+//!
+//! let (fixed, _) = linter.fix_source(Path::new("hello.f90"), source)?;
+//! assert_eq!(fixed, "program hello\nend program hello\n");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use ruff_source_file::SourceFileBuilder;
+
+use crate::check::{
+    apply_per_file_ignores, ast_entrypoint_map, check_and_fix_file, check_only_file,
+    read_to_string, rules_to_path_rules, rules_to_text_rules, FixTable, FixerResult,
+};
+use crate::message::DiagnosticMessage;
+use crate::rules::{AstRuleEnum, PathRuleEnum, TextRuleEnum};
+use crate::settings::Settings;
+
+/// Checks and fixes Fortran source code using a fixed set of [`Settings`].
+///
+/// Unlike `fortitude check`, the linter doesn't search for configuration files, use the
+/// cache, or report anything itself. Files are checked with the same settings wherever
+/// they are, although `per-file-ignores` patterns are still matched against their paths.
+pub struct Linter {
+    settings: Settings,
+    path_rules: Vec<PathRuleEnum>,
+    text_rules: Vec<TextRuleEnum>,
+    ast_entrypoints: BTreeMap<&'static str, Vec<AstRuleEnum>>,
+}
+
+impl Linter {
+    pub fn new(settings: Settings) -> Self {
+        let rules = &settings.check.rules;
+        Self {
+            path_rules: rules_to_path_rules(rules),
+            text_rules: rules_to_text_rules(rules),
+            ast_entrypoints: ast_entrypoint_map(rules),
+            settings,
+        }
+    }
+
+    /// The settings used to check files
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Check `source`, as the contents of the file at `path`, returning the violations
+    /// found. The file isn't read, but its path is used by rules that check file names
+    /// and extensions, and to match `per-file-ignores`.
+    pub fn lint_source(&self, path: &Path, source: &str) -> Result<Vec<DiagnosticMessage>> {
+        let file = SourceFileBuilder::new(path.to_string_lossy(), source).finish();
+        let mut messages = check_only_file(
+            &self.settings.check.rules,
+            &self.path_rules,
+            &self.text_rules,
+            &self.ast_entrypoints,
+            path,
+            &file,
+            &self.settings,
+            self.settings.check.ignore_allow_comments,
+        )?;
+        apply_per_file_ignores(&mut messages, path, &self.settings);
+        for message in &mut messages {
+            message.set_severity(self.settings.check.severity.get(message));
+        }
+        Ok(messages)
+    }
+
+    /// Read and check the file at `path`, returning the violations found.
+    pub fn lint_file(&self, path: &Path) -> Result<Vec<DiagnosticMessage>> {
+        let source =
+            read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        self.lint_source(path, &source)
+    }
+
+    /// Apply every available fix to `source`, as the contents of the file at `path`,
+    /// returning the fixed source and the number of fixes applied for each rule. Unsafe
+    /// fixes are only applied if enabled by the `unsafe-fixes` setting.
+    ///
+    /// Files with syntax errors aren't fixed, and return an error instead.
+    pub fn fix_source(&self, path: &Path, source: &str) -> Result<(String, FixTable)> {
+        let file = SourceFileBuilder::new(path.to_string_lossy(), source).finish();
+        let FixerResult {
+            transformed, fixed, ..
+        } = check_and_fix_file(
+            &self.settings.check.rules,
+            &self.path_rules,
+            &self.text_rules,
+            &self.ast_entrypoints,
+            path,
+            &file,
+            &self.settings,
+            self.settings.check.ignore_allow_comments,
+        )?;
+        Ok((transformed.source_text().to_string(), fixed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::str::FromStr;
+
+    use anyhow::Result;
+
+    use super::Linter;
+    use crate::options::{CheckOptions, Options};
+    use crate::rule_selector::RuleSelector;
+    use crate::rules::Rule;
+    use crate::settings::SettingsBuilder;
+    use crate::severity::Severity;
+
+    fn linter(check: CheckOptions) -> Result<Linter> {
+        let options = Options {
+            check: Some(check),
+            ..Options::default()
+        };
+        Ok(Linter::new(
+            SettingsBuilder::new().options(options).build()?,
+        ))
+    }
+
+    #[test]
+    fn lint_source() -> Result<()> {
+        let linter = linter(CheckOptions {
+            select: Some(vec![RuleSelector::from_str("C001")?]),
+            ..CheckOptions::default()
+        })?;
+        let messages = linter.lint_source(Path::new("test.f90"), "program test\nend program\n")?;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].rule(), Some(Rule::ImplicitTyping));
+        assert_eq!(messages[0].filename(), "test.f90");
+        assert_eq!(messages[0].severity(), Severity::Error);
+        Ok(())
+    }
+
+    #[test]
+    fn per_file_ignores_and_severity() -> Result<()> {
+        let linter = linter(CheckOptions {
+            select: Some(vec![
+                RuleSelector::from_str("C001")?,
+                RuleSelector::from_str("S061")?,
+            ]),
+            per_file_ignores: Some(
+                [(
+                    "test.f90".to_string(),
+                    vec![RuleSelector::from_str("C001")?],
+                )]
+                .into_iter()
+                .collect(),
+            ),
+            severity: Some([(RuleSelector::from_str("S")?, Severity::Warning)].into()),
+            ..CheckOptions::default()
+        })?;
+        let messages = linter.lint_source(Path::new("test.f90"), "program test\nend program\n")?;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].rule(), Some(Rule::UnnamedEndStatement));
+        assert_eq!(messages[0].severity(), Severity::Warning);
+        Ok(())
+    }
+
+    #[test]
+    fn fix_source() -> Result<()> {
+        let linter = linter(CheckOptions {
+            select: Some(vec![RuleSelector::from_str("S061")?]),
+            ..CheckOptions::default()
+        })?;
+        let (fixed, table) =
+            linter.fix_source(Path::new("test.f90"), "program test\nend program\n")?;
+        assert_eq!(fixed, "program test\nend program test\n");
+        assert_eq!(table.get("S061"), Some(&1));
+
+        // Syntax errors prevent any fixes
+        assert!(linter
+            .fix_source(Path::new("test.f90"), "program test\nend program (\n")
+            .is_err());
+        Ok(())
+    }
+}
@@ -5,9 +5,13 @@ use anyhow::Result;
 use clap::{CommandFactory, Parser};
 use colored::Colorize;
 use fortitude::check::check;
+use fortitude::clean::clean;
 use fortitude::cli::{Cli, SubCommands};
+use fortitude::convert::convert;
 use fortitude::explain::explain;
+use fortitude::format::format;
 use fortitude::logging::set_up_logging;
+use fortitude::server::server;
 
 fn main() -> Result<ExitCode> {
     let args = Cli::parse();
@@ -16,7 +20,11 @@ fn main() -> Result<ExitCode> {
 
     let status = match args.command {
         SubCommands::Check(check_args) => check(check_args, &args.global_options),
-        SubCommands::Explain(args) => explain(args),
+        SubCommands::Explain(explain_args) => explain(explain_args, &args.global_options),
+        SubCommands::Clean => clean(&args.global_options),
+        SubCommands::Convert(convert_args) => convert(convert_args, &args.global_options),
+        SubCommands::Format(format_args) => format(format_args, &args.global_options),
+        SubCommands::Server => server(&args.global_options),
         SubCommands::GenerateShellCompletion { shell } => {
             shell.generate(&mut Cli::command(), &mut stdout());
             return Ok(ExitCode::SUCCESS);
@@ -5,6 +5,7 @@
 use std::io::Write;
 
 use crate::message::Emitter;
+use crate::severity::Severity;
 
 use super::DiagnosticMessage;
 
@@ -24,14 +25,13 @@ impl Emitter for AzureEmitter {
 
             writeln!(
                 writer,
-                "##vso[task.logissue type=error\
+                "##vso[task.logissue type={issue_type}\
                         ;sourcepath={filename};linenumber={line};columnnumber={col};{code}]{body}",
+                issue_type = azure_issue_type(message.severity()),
                 filename = message.filename(),
                 line = location.row,
                 col = location.column,
-                code = message
-                    .rule()
-                    .map_or_else(String::new, |rule| format!("code={};", rule.noqa_code())),
+                code = format!("code={};", message.code()),
                 body = message.body(),
             )?;
         }
@@ -40,6 +40,15 @@ impl Emitter for AzureEmitter {
     }
 }
 
+/// Azure Pipelines only has errors and warnings, so anything less severe than an
+/// error is a warning
+fn azure_issue_type(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning | Severity::Info => "warning",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use insta::assert_snapshot;
@@ -0,0 +1,66 @@
+use std::io::Write;
+
+use crate::fs::relativize_path;
+use crate::message::{group_messages_by_filename, xml_escape, Emitter, MessageWithLocation};
+
+use super::DiagnosticMessage;
+
+/// Generate violations in Checkstyle XML format, as read by, for example, the Jenkins
+/// Warnings Next Generation plugin.
+/// See: [Checkstyle](https://checkstyle.org/)
+#[derive(Default)]
+pub struct CheckstyleEmitter;
+
+impl Emitter for CheckstyleEmitter {
+    fn emit(
+        &mut self,
+        writer: &mut dyn Write,
+        messages: &[DiagnosticMessage],
+    ) -> anyhow::Result<()> {
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(writer, r#"<checkstyle version="4.3">"#)?;
+
+        for (filename, messages) in group_messages_by_filename(messages) {
+            writeln!(
+                writer,
+                r#"    <file name="{}">"#,
+                xml_escape(&relativize_path(filename))
+            )?;
+            for message in messages {
+                let MessageWithLocation {
+                    message,
+                    start_location,
+                } = message;
+                writeln!(
+                    writer,
+                    r#"        <error line="{row}" column="{column}" severity="{severity}" message="{body}" source="org.plasmafair.fortitude.{code}"/>"#,
+                    row = start_location.row,
+                    column = start_location.column,
+                    severity = message.severity(),
+                    body = xml_escape(message.body()),
+                    code = xml_escape(message.code()),
+                )?;
+            }
+            writeln!(writer, "    </file>")?;
+        }
+
+        writeln!(writer, "</checkstyle>")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use crate::message::tests::{capture_emitter_output, create_messages};
+    use crate::message::CheckstyleEmitter;
+
+    #[test]
+    fn output() {
+        let mut emitter = CheckstyleEmitter;
+        let content = capture_emitter_output(&mut emitter, &create_messages());
+
+        assert_snapshot!(content);
+    }
+}
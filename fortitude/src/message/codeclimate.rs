@@ -0,0 +1,187 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+
+use serde::ser::SerializeSeq;
+use serde::{Serialize, Serializer};
+use serde_json::json;
+
+use crate::baseline::normalized_snippet;
+use crate::fs::{relativize_path, relativize_path_to};
+use crate::message::Emitter;
+use crate::registry::{Category, RuleNamespace};
+use crate::severity::Severity;
+
+use super::DiagnosticMessage;
+
+/// Generate JSON with violations in Code Climate format
+//  https://github.com/codeclimate/platform/blob/master/spec/analyzers/SPEC.md#issues
+pub struct CodeclimateEmitter {
+    project_dir: Option<String>,
+}
+
+impl Default for CodeclimateEmitter {
+    fn default() -> Self {
+        Self {
+            project_dir: std::env::var("CI_PROJECT_DIR").ok(),
+        }
+    }
+}
+
+impl Emitter for CodeclimateEmitter {
+    fn emit(
+        &mut self,
+        writer: &mut dyn Write,
+        messages: &[DiagnosticMessage],
+    ) -> anyhow::Result<()> {
+        serde_json::to_writer_pretty(
+            writer,
+            &SerializedMessages {
+                messages,
+                project_dir: self.project_dir.as_deref(),
+            },
+        )?;
+
+        Ok(())
+    }
+}
+
+struct SerializedMessages<'a> {
+    messages: &'a [DiagnosticMessage],
+    project_dir: Option<&'a str>,
+}
+
+impl Serialize for SerializedMessages<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_seq(Some(self.messages.len()))?;
+        let mut fingerprints = HashSet::<u64>::with_capacity(self.messages.len());
+
+        for message in self.messages {
+            let start_location = message.compute_start_location();
+            let end_location = message.compute_end_location();
+
+            let path = self.project_dir.as_ref().map_or_else(
+                || relativize_path(message.filename()),
+                |project_dir| relativize_path_to(message.filename(), project_dir),
+            );
+
+            let mut message_fingerprint = fingerprint(message, &path, 0);
+
+            // Identical violations on identical lines in the same file need distinct
+            // fingerprints, so chain them
+            while fingerprints.contains(&message_fingerprint) {
+                message_fingerprint = fingerprint(message, &path, message_fingerprint);
+            }
+            fingerprints.insert(message_fingerprint);
+
+            let category = category(message);
+
+            let value = json!({
+                "type": "issue",
+                "check_name": message.code(),
+                "description": message.body(),
+                "categories": [code_climate_category(category)],
+                "severity": codeclimate_severity(message.severity(), category),
+                "fingerprint": format!("{:x}", message_fingerprint),
+                "location": {
+                    "path": path,
+                    "positions": {
+                        "begin": {
+                            "line": start_location.row,
+                            "column": start_location.column,
+                        },
+                        "end": {
+                            "line": end_location.row,
+                            "column": end_location.column,
+                        },
+                    },
+                },
+            });
+
+            s.serialize_element(&value)?;
+        }
+
+        s.end()
+    }
+}
+
+fn category(message: &DiagnosticMessage) -> Option<Category> {
+    let rule = message.rule()?;
+    Category::parse_code(&rule.noqa_code().to_string()).map(|(category, _)| category)
+}
+
+/// The Code Climate category that best describes each category of rule
+fn code_climate_category(category: Option<Category>) -> &'static str {
+    match category {
+        Some(Category::Error | Category::Correctness) => "Bug Risk",
+        Some(Category::Obsolescent | Category::Portability) => "Compatibility",
+        Some(Category::Modernisation) => "Clarity",
+        Some(Category::Style | Category::Fortitude) | None => "Style",
+    }
+}
+
+/// The Code Climate severity corresponding to each severity. Files that couldn't be
+/// read or parsed aren't checked at all, so they're more severe than any other error
+fn codeclimate_severity(severity: Severity, category: Option<Category>) -> &'static str {
+    match (severity, category) {
+        (Severity::Error, Some(Category::Error)) => "critical",
+        (Severity::Error, _) => "major",
+        (Severity::Warning, _) => "minor",
+        (Severity::Info, _) => "info",
+    }
+}
+
+/// Generate a fingerprint to identify a violation that doesn't change if the lines
+/// around it are edited.
+fn fingerprint(message: &DiagnosticMessage, project_path: &str, salt: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    salt.hash(&mut hasher);
+    message.code().hash(&mut hasher);
+    project_path.hash(&mut hasher);
+    normalized_snippet(message).hash(&mut hasher);
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use crate::message::tests::{capture_emitter_output, create_messages};
+    use crate::message::CodeclimateEmitter;
+
+    #[test]
+    fn output() {
+        let mut emitter = CodeclimateEmitter::default();
+        let content = capture_emitter_output(&mut emitter, &create_messages());
+
+        assert_snapshot!(redact_fingerprint(&content));
+    }
+
+    // Redact the fingerprint because the default hasher isn't stable across platforms.
+    fn redact_fingerprint(content: &str) -> String {
+        static FINGERPRINT_HAY_KEY: &str = r#""fingerprint": ""#;
+
+        let mut output = String::with_capacity(content.len());
+        let mut last = 0;
+
+        for (start, _) in content.match_indices(FINGERPRINT_HAY_KEY) {
+            let fingerprint_hash_start = start + FINGERPRINT_HAY_KEY.len();
+            output.push_str(&content[last..fingerprint_hash_start]);
+            output.push_str("<redacted>");
+            last = fingerprint_hash_start
+                + content[fingerprint_hash_start..]
+                    .find('"')
+                    .expect("Expected terminating quote");
+        }
+
+        output.push_str(&content[last..]);
+
+        output
+    }
+}
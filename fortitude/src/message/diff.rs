@@ -4,6 +4,7 @@
 
 use std::fmt::{Display, Formatter};
 use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 
 use colored::{Color, ColoredString, Colorize, Styles};
 
@@ -13,6 +14,7 @@ use similar::{ChangeTag, TextDiff};
 use ruff_diagnostics::{Applicability, Fix};
 use ruff_source_file::{OneIndexed, SourceFile};
 
+use crate::fs::relativize_path;
 use crate::message::DiagnosticMessage;
 use crate::text_helpers::ShowNonprinting;
 
@@ -124,6 +126,47 @@ impl Display for Diff<'_> {
     }
 }
 
+/// A unified diff of all the changes made to a file, as printed by `--diff`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SourceDiff {
+    path: PathBuf,
+    new_path: PathBuf,
+    original: String,
+    transformed: String,
+}
+
+impl SourceDiff {
+    pub(crate) fn new(path: &Path, original: &str, transformed: &str) -> Self {
+        Self::renamed(path, path, original, transformed)
+    }
+
+    /// A diff of a file that has been changed and moved to `new_path`.
+    pub(crate) fn renamed(path: &Path, new_path: &Path, original: &str, transformed: &str) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            new_path: new_path.to_path_buf(),
+            original: original.to_string(),
+            transformed: transformed.to_string(),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Display for SourceDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let diff = TextDiff::from_lines(&self.original, &self.transformed);
+        let mut unified = diff.unified_diff();
+        unified.header(
+            &relativize_path(&self.path),
+            &relativize_path(&self.new_path),
+        );
+        writeln!(f, "{unified}")
+    }
+}
+
 struct LineStyle {
     fgcolor: Option<Color>,
     style: Option<Styles>,
@@ -6,6 +6,7 @@ use std::io::Write;
 
 use crate::fs::relativize_path;
 use crate::message::Emitter;
+use crate::severity::Severity;
 
 use super::DiagnosticMessage;
 
@@ -28,8 +29,9 @@ impl Emitter for GithubEmitter {
 
             write!(
                 writer,
-                "::error title=Fortitude{code},file={file},line={row},col={column},endLine={end_row},endColumn={end_column}::",
-                code = message.rule().map_or_else(String::new, |rule| format!(" ({})", rule.noqa_code())),
+                "::{level} title=Fortitude{code},file={file},line={row},col={column},endLine={end_row},endColumn={end_column}::",
+                level = github_level(message.severity()),
+                code = format!(" ({})", message.code()),
                 file = message.filename(),
                 row = source_location.row,
                 column = source_location.column,
@@ -45,17 +47,22 @@ impl Emitter for GithubEmitter {
                 column = location.column,
             )?;
 
-            if let Some(rule) = message.rule() {
-                write!(writer, " {}", rule.noqa_code())?;
-            }
-
-            writeln!(writer, " {}", message.body())?;
+            writeln!(writer, " {} {}", message.code(), message.body())?;
         }
 
         Ok(())
     }
 }
 
+/// GitHub has no lower level of annotation than a notice
+fn github_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "notice",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use insta::assert_snapshot;
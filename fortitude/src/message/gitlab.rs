@@ -13,6 +13,7 @@ use serde_json::json;
 
 use crate::fs::{relativize_path, relativize_path_to};
 use crate::message::Emitter;
+use crate::severity::Severity;
 
 use super::DiagnosticMessage;
 
@@ -84,15 +85,11 @@ impl Serialize for SerializedMessages<'_> {
             }
             fingerprints.insert(message_fingerprint);
 
-            let description = if let Some(rule) = message.rule() {
-                format!("({}) {}", rule.noqa_code(), message.body())
-            } else {
-                message.body().to_string()
-            };
+            let description = format!("({}) {}", message.code(), message.body());
 
             let value = json!({
                 "description": description,
-                "severity": "major",
+                "severity": gitlab_severity(message.severity()),
                 "fingerprint": format!("{:x}", message_fingerprint),
                 "location": {
                     "path": path,
@@ -107,12 +104,25 @@ impl Serialize for SerializedMessages<'_> {
     }
 }
 
+/// The GitLab code quality severity corresponding to each severity
+fn gitlab_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "major",
+        Severity::Warning => "minor",
+        Severity::Info => "info",
+    }
+}
+
 /// Generate a unique fingerprint to identify a violation.
 fn fingerprint(message: &DiagnosticMessage, project_path: &str, salt: u64) -> u64 {
     let mut hasher = DefaultHasher::new();
 
     salt.hash(&mut hasher);
     message.name().hash(&mut hasher);
+    // Custom rules all share the same name, so they're told apart by their own code
+    if let Some(custom_rule) = message.custom_rule() {
+        custom_rule.code.hash(&mut hasher);
+    }
     project_path.hash(&mut hasher);
 
     hasher.finish()
@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use ruff_text_size::{Ranged, TextRange};
+
+use crate::fs::relativize_path;
+use crate::message::diff::Diff;
+use crate::message::text::MessageCodeFrame;
+use crate::message::{group_messages_by_filename, xml_escape, Emitter, MessageWithLocation};
+use crate::registry::{Category, RuleNamespace};
+use crate::rules::Rule;
+
+use super::DiagnosticMessage;
+
+const STYLE: &str = r#"body { font-family: sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.75em; text-align: left; }
+td.count { text-align: right; }
+pre { background: #f6f8fa; padding: 0.75em; overflow-x: auto; }
+.code { color: #b31d28; font-family: monospace; font-weight: bold; }
+.annotation { color: #b31d28; font-weight: bold; }
+.delete { color: #b31d28; background: #ffeef0; }
+.insert { color: #22863a; background: #f0fff4; }
+.file { display: none; }
+.file:target { display: block; }
+body:has(.file:target) #summary { display: none; }"#;
+
+/// Generate a self-contained HTML report, with a summary of the violations by category
+/// and rule, and a page for each file showing its violations in context along with
+/// any available fixes. Pages are linked by anchors, so no scripts or external assets
+/// are needed to view the report.
+#[derive(Default)]
+pub struct HtmlEmitter;
+
+impl Emitter for HtmlEmitter {
+    fn emit(
+        &mut self,
+        writer: &mut dyn Write,
+        messages: &[DiagnosticMessage],
+    ) -> anyhow::Result<()> {
+        // Source snippets and diffs are styled by the report rather than by terminal
+        // escape codes, so render them without colour
+        let colorize = colored::control::SHOULD_COLORIZE.should_colorize();
+        colored::control::set_override(false);
+        let result = write_report(writer, messages);
+        colored::control::set_override(colorize);
+        result
+    }
+}
+
+fn write_report(writer: &mut dyn Write, messages: &[DiagnosticMessage]) -> anyhow::Result<()> {
+    let files = group_messages_by_filename(messages);
+    let fixable = messages.iter().filter(|message| message.fixable()).count();
+
+    let mut categories: BTreeMap<Category, usize> = BTreeMap::new();
+    let mut rules: BTreeMap<&str, (String, usize)> = BTreeMap::new();
+    for message in messages {
+        if let Some(category) = category(message) {
+            *categories.entry(category).or_default() += 1;
+        }
+        rules
+            .entry(message.code())
+            .or_insert_with(|| (rule_name(message), 0))
+            .1 += 1;
+    }
+
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, r#"<html lang="en">"#)?;
+    writeln!(writer, "<head>")?;
+    writeln!(writer, r#"<meta charset="utf-8">"#)?;
+    writeln!(writer, "<title>Fortitude report</title>")?;
+    writeln!(writer, "<style>\n{STYLE}\n</style>")?;
+    writeln!(writer, "</head>")?;
+    writeln!(writer, "<body>")?;
+
+    writeln!(writer, r#"<section id="summary">"#)?;
+    writeln!(writer, "<h1>Fortitude report</h1>")?;
+    writeln!(
+        writer,
+        "<p>Found {} violation{} in {} file{} ({} fixable).</p>",
+        messages.len(),
+        if messages.len() == 1 { "" } else { "s" },
+        files.len(),
+        if files.len() == 1 { "" } else { "s" },
+        fixable,
+    )?;
+
+    writeln!(writer, "<h2>Violations by category</h2>")?;
+    writeln!(writer, "<table>")?;
+    writeln!(writer, "<tr><th>Category</th><th>Count</th></tr>")?;
+    for (category, count) in &categories {
+        writeln!(
+            writer,
+            r#"<tr><td>{}</td><td class="count">{count}</td></tr>"#,
+            category.name()
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h2>Violations by rule</h2>")?;
+    writeln!(writer, "<table>")?;
+    writeln!(writer, "<tr><th>Code</th><th>Rule</th><th>Count</th></tr>")?;
+    for (code, (name, count)) in &rules {
+        writeln!(
+            writer,
+            r#"<tr><td class="code">{}</td><td>{}</td><td class="count">{count}</td></tr>"#,
+            xml_escape(code),
+            xml_escape(name),
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h2>Files</h2>")?;
+    writeln!(writer, "<table>")?;
+    writeln!(writer, "<tr><th>File</th><th>Count</th></tr>")?;
+    for (index, (filename, messages)) in files.iter().enumerate() {
+        writeln!(
+            writer,
+            r##"<tr><td><a href="#file-{index}">{}</a></td><td class="count">{}</td></tr>"##,
+            xml_escape(&relativize_path(filename)),
+            messages.len(),
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+    writeln!(writer, "</section>")?;
+
+    for (index, (filename, messages)) in files.iter().enumerate() {
+        writeln!(writer, r#"<section class="file" id="file-{index}">"#)?;
+        writeln!(writer, r##"<p><a href="#summary">Back to summary</a></p>"##)?;
+        writeln!(
+            writer,
+            "<h2>{}</h2>",
+            xml_escape(&relativize_path(filename))
+        )?;
+        for message in messages {
+            write_message(writer, message)?;
+        }
+        writeln!(writer, "</section>")?;
+    }
+
+    writeln!(writer, "</body>")?;
+    writeln!(writer, "</html>")?;
+    Ok(())
+}
+
+fn write_message(writer: &mut dyn Write, message: &MessageWithLocation) -> anyhow::Result<()> {
+    let MessageWithLocation {
+        message,
+        start_location,
+    } = message;
+
+    writeln!(
+        writer,
+        r#"<h3>{row}:{column}: <span class="code">{code}</span> {body}</h3>"#,
+        row = start_location.row,
+        column = start_location.column,
+        code = xml_escape(message.code()),
+        body = xml_escape(message.body()),
+    )?;
+
+    // The `0..0` range is used to highlight file-level diagnostics.
+    if message.range() != TextRange::default() {
+        let frame = MessageCodeFrame { message }.to_string();
+        writeln!(writer, r#"<pre class="source">"#)?;
+        for line in frame.trim_end().lines() {
+            // Lines under the source marking the violation are made up of carets
+            let is_annotation = line
+                .split_once('|')
+                .is_some_and(|(_, text)| text.trim_start().starts_with('^'));
+            if is_annotation {
+                writeln!(
+                    writer,
+                    r#"<span class="annotation">{}</span>"#,
+                    xml_escape(line)
+                )?;
+            } else {
+                writeln!(writer, "{}", xml_escape(line))?;
+            }
+        }
+        writeln!(writer, "</pre>")?;
+    }
+
+    if let Some(diff) = Diff::from_message(message) {
+        let diff = diff.to_string();
+        writeln!(writer, r#"<pre class="fix">"#)?;
+        for line in diff.trim_end().lines() {
+            let class = match line
+                .split_once('|')
+                .and_then(|(_, text)| text.chars().next())
+            {
+                Some('-') => Some("delete"),
+                Some('+') => Some("insert"),
+                _ => None,
+            };
+            // Unchanged lines are left unstyled
+            match class {
+                Some(class) => writeln!(
+                    writer,
+                    r#"<span class="{class}">{}</span>"#,
+                    xml_escape(line)
+                )?,
+                None => writeln!(writer, "{}", xml_escape(line))?,
+            }
+        }
+        writeln!(writer, "</pre>")?;
+    }
+
+    Ok(())
+}
+
+fn category(message: &DiagnosticMessage) -> Option<Category> {
+    let rule = message.rule()?;
+    Category::parse_code(&rule.noqa_code().to_string()).map(|(category, _)| category)
+}
+
+/// Custom rules share a [`Rule`], but are reported under their own codes
+fn rule_name(message: &DiagnosticMessage) -> String {
+    match message.rule() {
+        Some(rule) if rule != Rule::CustomRule => rule.as_ref().to_string(),
+        _ => message.code().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use crate::message::tests::{capture_emitter_output, create_messages};
+    use crate::message::HtmlEmitter;
+
+    #[test]
+    fn output() {
+        let mut emitter = HtmlEmitter;
+        let content = capture_emitter_output(&mut emitter, &create_messages());
+
+        assert_snapshot!(content);
+    }
+}
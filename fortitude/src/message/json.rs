@@ -64,8 +64,9 @@ pub(crate) fn message_to_json_value(message: &DiagnosticMessage) -> Value {
     let end_location = source_code.source_location(message.end());
 
     json!({
-        "code": message.rule().map(|rule| rule.noqa_code().to_string()),
+        "code": message.code(),
         "message": message.body(),
+        "severity": message.severity(),
         "fix": fix,
         "location": start_location,
         "end_location": end_location,
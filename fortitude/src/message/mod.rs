@@ -1,39 +1,52 @@
 pub use azure::AzureEmitter;
+pub use checkstyle::CheckstyleEmitter;
+pub use codeclimate::CodeclimateEmitter;
 pub use github::GithubEmitter;
 pub use gitlab::GitlabEmitter;
 pub use grouped::GroupedEmitter;
+pub use html::HtmlEmitter;
 pub use json::JsonEmitter;
 pub use json_lines::JsonLinesEmitter;
 pub use junit::JunitEmitter;
 pub use pylint::PylintEmitter;
 pub use rdjson::RdjsonEmitter;
 pub use sarif::SarifEmitter;
+pub use sonarqube::SonarqubeEmitter;
 pub use text::TextEmitter;
 
+pub(crate) use diff::SourceDiff;
+
 mod azure;
+mod checkstyle;
+mod codeclimate;
 mod diff;
 mod github;
 mod gitlab;
 mod grouped;
+mod html;
 mod json;
 mod json_lines;
 mod junit;
 mod pylint;
 mod rdjson;
 mod sarif;
+mod sonarqube;
 mod text;
 
 use std::collections::BTreeMap;
 use std::io::Write;
 use std::{cmp::Ordering, ops::Deref};
 
+use crate::custom_rules::CustomRuleId;
+use crate::diagnostics::Violation;
+use crate::severity::Severity;
 use crate::{registry::AsRule, rules::Rule};
 use ruff_diagnostics::{Diagnostic, DiagnosticKind, Fix};
 use ruff_source_file::{SourceFile, SourceFileBuilder, SourceLocation};
 use ruff_text_size::{Ranged, TextRange};
 
 /// Reports of each violation. They are pretty-printable and sortable.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DiagnosticMessage {
     kind: DiagnosticKind,
     range: TextRange,
@@ -41,8 +54,12 @@ pub struct DiagnosticMessage {
     file: SourceFile,
     /// The rule code that was violated, expressed as a string.
     code: String,
+    /// The custom rule that was violated, if any.
+    custom_rule: Option<CustomRuleId>,
     /// The suggested fix for the violation.
     fix: Option<Fix>,
+    /// How seriously the violation should be treated.
+    severity: Severity,
 }
 
 impl DiagnosticMessage {
@@ -52,20 +69,28 @@ impl DiagnosticMessage {
             kind: diagnostic.kind,
             file: file.clone(),
             code,
+            custom_rule: None,
             range: diagnostic.range,
             fix: diagnostic.fix,
+            severity: Severity::default(),
         }
     }
 
     pub fn from_error<S: AsRef<str>>(filename: S, diagnostic: Diagnostic) -> Self {
-        let code = diagnostic.kind.rule().noqa_code().to_string();
-        Self {
-            kind: diagnostic.kind,
-            file: SourceFileBuilder::new(filename.as_ref(), "").finish(),
-            code,
-            range: diagnostic.range,
-            fix: diagnostic.fix,
-        }
+        let file = SourceFileBuilder::new(filename.as_ref(), "").finish();
+        Self::from_ruff(&file, diagnostic)
+    }
+
+    /// Create a message from a violation, which may have been raised by a custom rule.
+    pub(crate) fn from_violation(file: &SourceFile, violation: Violation) -> Self {
+        let mut message = Self::from_ruff(file, violation.diagnostic);
+        message.set_custom_rule(violation.custom_rule);
+        message
+    }
+
+    /// Returns the [`DiagnosticKind`] of the message.
+    pub fn kind(&self) -> &DiagnosticKind {
+        &self.kind
     }
 
     /// Returns the name used to represent the diagnostic.
@@ -78,6 +103,32 @@ impl DiagnosticMessage {
         &self.kind.body
     }
 
+    /// Returns the code of the violated rule, which may be a custom rule.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Returns the name of the violated rule, which may be a custom rule.
+    pub fn rule_name(&self) -> &str {
+        match &self.custom_rule {
+            Some(custom_rule) => &custom_rule.name,
+            None => self.kind.rule().into(),
+        }
+    }
+
+    /// Returns the custom rule that was violated, if any.
+    pub(crate) fn custom_rule(&self) -> Option<&CustomRuleId> {
+        self.custom_rule.as_ref()
+    }
+
+    /// Sets the custom rule that was violated, which the message is reported under.
+    pub(crate) fn set_custom_rule(&mut self, custom_rule: Option<CustomRuleId>) {
+        if let Some(custom_rule) = &custom_rule {
+            self.code.clone_from(&custom_rule.code);
+        }
+        self.custom_rule = custom_rule;
+    }
+
     /// Returns the fix suggestion for the violation.
     pub fn suggestion(&self) -> Option<&str> {
         self.kind.suggestion.as_deref()
@@ -88,6 +139,16 @@ impl DiagnosticMessage {
         self.fix.as_ref()
     }
 
+    /// Returns the [`Severity`] of the violation.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Sets the [`Severity`] of the violation.
+    pub fn set_severity(&mut self, severity: Severity) {
+        self.severity = severity;
+    }
+
     /// Returns `true` if the message contains a [`Fix`].
     pub fn fixable(&self) -> bool {
         self.fix().is_some()
@@ -168,6 +229,22 @@ impl Deref for MessageWithLocation<'_> {
     }
 }
 
+/// Escape text for use in XML, or HTML
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 fn group_messages_by_filename(
     messages: &[DiagnosticMessage],
 ) -> BTreeMap<&str, Vec<MessageWithLocation>> {
@@ -191,6 +268,7 @@ mod tests {
     use ruff_text_size::{TextRange, TextSize};
 
     use crate::message::{DiagnosticMessage, Emitter};
+    use crate::severity::Severity;
 
     pub(super) fn create_messages() -> Vec<DiagnosticMessage> {
         let test_contents = r#"module test
@@ -240,10 +318,17 @@ end module
 
         let file_2_source = SourceFileBuilder::new("star_kind.f90", file_2).finish();
 
+        let mut unnamed_end_statement =
+            DiagnosticMessage::from_ruff(&test_source, unnamed_end_statement);
+        unnamed_end_statement.set_severity(Severity::Warning);
+
+        let mut star_kind = DiagnosticMessage::from_ruff(&file_2_source, star_kind);
+        star_kind.set_severity(Severity::Info);
+
         vec![
             DiagnosticMessage::from_ruff(&test_source, superfluous_implicit_none),
-            DiagnosticMessage::from_ruff(&test_source, unnamed_end_statement),
-            DiagnosticMessage::from_ruff(&file_2_source, star_kind),
+            unnamed_end_statement,
+            star_kind,
         ]
     }
 
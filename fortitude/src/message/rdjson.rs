@@ -13,6 +13,7 @@ use ruff_source_file::SourceCode;
 use ruff_text_size::Ranged;
 
 use crate::message::{Emitter, SourceLocation};
+use crate::severity::Severity;
 
 use super::DiagnosticMessage;
 
@@ -70,12 +71,13 @@ fn message_to_rdjson_value(message: &DiagnosticMessage) -> Value {
     if let Some(fix) = message.fix() {
         json!({
             "message": message.body(),
+            "severity": rdjson_severity(message.severity()),
             "location": {
                 "path": message.filename(),
                 "range": rdjson_range(&start_location, &end_location),
             },
             "code": {
-                "value": message.rule().map(|rule| rule.noqa_code().to_string()),
+                "value": message.code(),
                 // "url": message.rule().and_then(|rule| rule.url()),
             },
             "suggestions": rdjson_suggestions(fix.edits(), &source_code),
@@ -83,18 +85,27 @@ fn message_to_rdjson_value(message: &DiagnosticMessage) -> Value {
     } else {
         json!({
             "message": message.body(),
+            "severity": rdjson_severity(message.severity()),
             "location": {
                 "path": message.filename(),
                 "range": rdjson_range(&start_location, &end_location),
             },
             "code": {
-                "value": message.rule().map(|rule| rule.noqa_code().to_string()),
+                "value": message.code(),
                 // "url": message.rule().and_then(|rule| rule.url()),
             },
         })
     }
 }
 
+fn rdjson_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "ERROR",
+        Severity::Warning => "WARNING",
+        Severity::Info => "INFO",
+    }
+}
+
 fn rdjson_suggestions(edits: &[Edit], source_code: &SourceCode) -> Value {
     Value::Array(
         edits
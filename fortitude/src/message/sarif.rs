@@ -2,7 +2,7 @@
 // Copyright 2022 Charles Marsh
 // SPDX-License-Identifier: MIT
 
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 use std::io::Write;
 
 use anyhow::Result;
@@ -15,6 +15,7 @@ use crate::fs::normalize_path;
 use crate::message::Emitter;
 use crate::registry::{Category, RuleNamespace};
 use crate::rules::Rule;
+use crate::severity::Severity;
 use crate::VERSION;
 
 use super::DiagnosticMessage;
@@ -28,9 +29,21 @@ impl Emitter for SarifEmitter {
             .map(SarifResult::from_message)
             .collect::<Result<Vec<_>>>()?;
 
-        let unique_rules: HashSet<_> = results.iter().filter_map(|result| result.rule).collect();
-        let mut rules: Vec<SarifRule> = unique_rules.into_iter().map(SarifRule::from).collect();
-        rules.sort_by(|a, b| a.code.cmp(&b.code));
+        // Rules may have different severities in different directories, so report the
+        // highest for each one
+        let mut unique_rules: BTreeMap<String, (Rule, Severity)> = BTreeMap::new();
+        for result in &results {
+            if let Some(rule) = result.rule {
+                let (_, severity) = unique_rules
+                    .entry(result.code.clone())
+                    .or_insert((rule, result.severity));
+                *severity = (*severity).max(result.severity);
+            }
+        }
+        let rules: Vec<SarifRule> = unique_rules
+            .into_iter()
+            .map(|(code, (rule, severity))| SarifRule::from((rule, code, severity)))
+            .collect();
 
         let output = json!({
             "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
@@ -59,19 +72,21 @@ struct SarifRule<'a> {
     linter: &'a str,
     summary: &'a str,
     explanation: Option<&'a str>,
+    severity: Severity,
     // url: Option<String>,
 }
 
-impl From<Rule> for SarifRule<'_> {
-    fn from(rule: Rule) -> Self {
-        let code = rule.noqa_code().to_string();
-        let (linter, _) = Category::parse_code(&code).unwrap();
+/// Custom rules share a [`Rule`], but are reported under their own codes
+impl From<(Rule, String, Severity)> for SarifRule<'_> {
+    fn from((rule, code, severity): (Rule, String, Severity)) -> Self {
+        let (linter, _) = Category::parse_code(&rule.noqa_code().to_string()).unwrap();
         Self {
             name: rule.into(),
             code,
             linter: linter.name(),
             summary: rule.message_formats()[0],
             explanation: rule.explanation(),
+            severity,
             // url: rule.url(),
         }
     }
@@ -98,7 +113,7 @@ impl Serialize for SarifRule<'_> {
                 "id": self.code,
                 "kind": self.linter,
                 "name": self.name,
-                "problem.severity": "error".to_string(),
+                "problem.severity": problem_severity(self.severity),
             },
         })
         .serialize(serializer)
@@ -108,7 +123,8 @@ impl Serialize for SarifRule<'_> {
 #[derive(Debug)]
 struct SarifResult {
     rule: Option<Rule>,
-    level: String,
+    code: String,
+    severity: Severity,
     message: String,
     uri: String,
     start_line: OneIndexed,
@@ -125,7 +141,8 @@ impl SarifResult {
         let path = normalize_path(message.filename());
         Ok(Self {
             rule: message.rule(),
-            level: "error".to_string(),
+            code: message.code().to_string(),
+            severity: message.severity(),
             message: message.body().to_string(),
             uri: url::Url::from_file_path(&path)
                 .map_err(|()| anyhow::anyhow!("Failed to convert path to URL: {}", path.display()))?
@@ -145,7 +162,8 @@ impl SarifResult {
         let path = normalize_path(message.filename());
         Ok(Self {
             rule: message.rule(),
-            level: "error".to_string(),
+            code: message.code().to_string(),
+            severity: message.severity(),
             message: message.body().to_string(),
             uri: path.display().to_string(),
             start_line: start_location.row,
@@ -162,7 +180,7 @@ impl Serialize for SarifResult {
         S: Serializer,
     {
         json!({
-            "level": self.level,
+            "level": sarif_level(self.severity),
             "message": {
                 "text": self.message,
             },
@@ -179,12 +197,30 @@ impl Serialize for SarifResult {
                     }
                 }
             }],
-            "ruleId": self.rule.map(|rule| rule.noqa_code().to_string()),
+            "ruleId": self.code,
         })
         .serialize(serializer)
     }
 }
 
+/// The SARIF level of a result
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "note",
+    }
+}
+
+/// The severity of a rule, as used by GitHub code scanning
+fn problem_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "recommendation",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::message::tests::{capture_emitter_output, create_messages};
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use serde_json::{json, Value};
+
+use crate::fs::relativize_path;
+use crate::message::Emitter;
+use crate::registry::{Category, RuleNamespace};
+use crate::rules::Rule;
+use crate::severity::Severity;
+
+use super::DiagnosticMessage;
+
+/// Generate JSON with violations in SonarQube's generic issue import format
+//  https://docs.sonarsource.com/sonarqube-server/latest/analyzing-source-code/importing-external-issues/generic-issue-import-format/
+#[derive(Default)]
+pub struct SonarqubeEmitter;
+
+impl Emitter for SonarqubeEmitter {
+    fn emit(
+        &mut self,
+        writer: &mut dyn Write,
+        messages: &[DiagnosticMessage],
+    ) -> anyhow::Result<()> {
+        let mut rules = BTreeMap::new();
+        let mut issues = Vec::with_capacity(messages.len());
+
+        for message in messages {
+            let Some(rule) = message.rule() else {
+                continue;
+            };
+            // A rule may have different severities in different files, so it's given
+            // the highest of them
+            let (_, severity) = rules
+                .entry(message.code())
+                .or_insert((rule, message.severity()));
+            *severity = (*severity).max(message.severity());
+
+            let start_location = message.compute_start_location();
+            let end_location = message.compute_end_location();
+
+            // SonarQube columns start from zero
+            issues.push(json!({
+                "ruleId": message.code(),
+                "primaryLocation": {
+                    "message": message.body(),
+                    "filePath": relativize_path(message.filename()),
+                    "textRange": {
+                        "startLine": start_location.row,
+                        "startColumn": start_location.column.to_zero_indexed(),
+                        "endLine": end_location.row,
+                        "endColumn": end_location.column.to_zero_indexed(),
+                    },
+                },
+            }));
+        }
+
+        let output = json!({
+            "rules": rules
+                .into_iter()
+                .map(|(code, (rule, severity))| rule_to_json(rule, code, severity))
+                .collect::<Vec<_>>(),
+            "issues": issues,
+        });
+        serde_json::to_writer_pretty(writer, &output)?;
+        Ok(())
+    }
+}
+
+/// Custom rules share a [`Rule`], but are reported under their own codes
+fn rule_to_json(rule: Rule, code: &str, severity: Severity) -> Value {
+    let name = if rule == Rule::CustomRule {
+        code
+    } else {
+        rule.as_ref()
+    };
+    let category =
+        Category::parse_code(&rule.noqa_code().to_string()).map(|(category, _)| category);
+    let (attribute, quality) = clean_code_impact(category);
+
+    json!({
+        "id": code,
+        "name": name,
+        "description": rule.message_formats()[0],
+        "engineId": "fortitude",
+        "cleanCodeAttribute": attribute,
+        "impacts": [{
+            "softwareQuality": quality,
+            "severity": impact_severity(severity),
+        }],
+    })
+}
+
+/// The clean code attribute and software quality that best describe each category of
+/// rule
+fn clean_code_impact(category: Option<Category>) -> (&'static str, &'static str) {
+    match category {
+        Some(Category::Error | Category::Correctness) => ("LOGICAL", "RELIABILITY"),
+        Some(Category::Obsolescent | Category::Portability) => ("CONVENTIONAL", "MAINTAINABILITY"),
+        Some(Category::Modernisation) => ("CLEAR", "MAINTAINABILITY"),
+        Some(Category::Style | Category::Fortitude) | None => ("CONVENTIONAL", "MAINTAINABILITY"),
+    }
+}
+
+/// The severity of a rule's impact on software quality, as used by SonarQube
+fn impact_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "HIGH",
+        Severity::Warning => "MEDIUM",
+        Severity::Info => "LOW",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use crate::message::tests::{capture_emitter_output, create_messages};
+    use crate::message::SonarqubeEmitter;
+
+    #[test]
+    fn output() {
+        let mut emitter = SonarqubeEmitter;
+        let content = capture_emitter_output(&mut emitter, &create_messages());
+
+        assert_snapshot!(content);
+    }
+}
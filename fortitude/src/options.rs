@@ -2,6 +2,7 @@
 // Copyright 2022 Charles Marsh
 // SPDX-License-Identifier: MIT
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use ruff_macros::{CombineOptions, OptionsMetadata};
@@ -9,29 +10,67 @@ use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    configuration::CombinePluginOptions,
     rule_selector::RuleSelector,
     rules::{
-        correctness::exit_labels,
+        correctness::{
+            exit_labels,
+            magic_numbers::{self, settings::MagicNumberContext},
+        },
         portability::{self},
         style::{
             keywords,
             strings::{self, settings::Quote},
         },
     },
-    settings::{OutputFormat, ProgressBar},
+    settings::{FormatSettings, KeywordCase, OutputFormat, OutputTarget, ProgressBar},
+    severity::Severity,
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, Default, OptionsMetadata, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct Options {
+    /// A path to another configuration file to inherit settings from. Relative paths
+    /// are resolved against the directory containing this file.
+    ///
+    /// Settings in this file replace the corresponding settings in the extended file,
+    /// including whole tables of options for individual rules. Paths in the extended
+    /// file, such as [`exclude`](#check_exclude) and [`baseline`](#check_baseline),
+    /// remain relative to the directory containing it. The extended file may itself
+    /// extend another file.
+    #[option(
+        default = "null",
+        value_type = "str",
+        example = r#"
+            # Use the settings shared by all of the organisation's projects
+            extend = "../fortitude-base.toml"
+        "#
+    )]
+    pub extend: Option<PathBuf>,
+
     #[option_group]
     pub check: Option<CheckOptions>,
+
+    #[option_group]
+    pub format: Option<FormatOptions>,
+}
+
+impl CombinePluginOptions for Options {
+    fn combine(self, other: Self) -> Self {
+        Self {
+            extend: self.extend.or(other.extend),
+            check: self.check.combine(other.check),
+            format: self.format.combine(other.format),
+        }
+    }
 }
 
 /// Configures how Fortitude checks your code.
 ///
 /// Options specified in the `check` section take precedence over the deprecated top-level settings.
-#[derive(Clone, Debug, PartialEq, Eq, Default, OptionsMetadata, Serialize, Deserialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, Default, OptionsMetadata, CombineOptions, Serialize, Deserialize,
+)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct CheckOptions {
     /// A list of file patterns to include when linting.
@@ -83,12 +122,16 @@ pub struct CheckOptions {
 
     /// The style in which violation messages should be formatted: `"full"` (default)
     /// (shows source), `"concise"`, `"grouped"` (group messages by file), `"json"`
-    /// (machine-readable), `"junit"` (machine-readable XML), `"github"` (GitHub
-    /// Actions annotations), `"gitlab"` (GitLab CI code quality report),
-    /// `"pylint"` (Pylint text format) or `"azure"` (Azure Pipeline logging commands).
+    /// (machine-readable), `"json-lines"` (one JSON object per line), `"junit"`
+    /// (machine-readable XML), `"github"` (GitHub Actions annotations), `"gitlab"`
+    /// (GitLab CI code quality report), `"pylint"` (Pylint text format), `"rdjson"`
+    /// (reviewdog diagnostic format), `"azure"` (Azure Pipeline logging commands),
+    /// `"sarif"` (SARIF 2.1.0), `"checkstyle"` (Checkstyle XML), `"codeclimate"` (Code
+    /// Climate JSON), `"sonarqube"` (SonarQube generic issue import format) or `"html"`
+    /// (self-contained HTML report).
     #[option(
         default = r#""full""#,
-        value_type = r#""full" | "concise" | "grouped" | "json" | "junit" | "github" | "gitlab" | "pylint" | "azure""#,
+        value_type = r#""full" | "concise" | "grouped" | "json" | "json-lines" | "junit" | "github" | "gitlab" | "pylint" | "rdjson" | "azure" | "sarif" | "checkstyle" | "codeclimate" | "sonarqube" | "html""#,
         example = r#"
             # Group violations by containing file.
             output-format = "grouped"
@@ -96,6 +139,23 @@ pub struct CheckOptions {
     )]
     pub output_format: Option<OutputFormat>,
 
+    /// Additional output formats, each written to its own file, as
+    /// `"format:path"`. Relative paths are resolved against the project root.
+    ///
+    /// These are written alongside the main output, so that several reports can be
+    /// produced from a single run. Formats given with a path to `--output-format`
+    /// are added to these. Each file contains the full report, even when the main
+    /// output is replaced by `--statistics`.
+    #[option(
+        default = "[]",
+        value_type = "list[str]",
+        example = r#"
+            # Also write reports for GitHub code scanning and for test results
+            extend-output-format = ["sarif:fortitude.sarif", "junit:fortitude-junit.xml"]
+        "#
+    )]
+    pub extend_output_format: Option<Vec<OutputTarget>>,
+
     /// Whether to enable preview mode. When preview mode is enabled, Fortitude will
     /// use unstable rules, fixes, and formatting.
     #[option(
@@ -121,6 +181,65 @@ pub struct CheckOptions {
     )]
     pub progress_bar: Option<ProgressBar>,
 
+    /// A path to the cache directory. Relative paths are resolved against the
+    /// project root.
+    ///
+    /// By default, Fortitude stores cache results in a `.fortitude_cache` directory in
+    /// the project root. This setting is overridden by the `--cache-dir` command-line
+    /// option and the `FORTITUDE_CACHE_DIR` environment variable.
+    #[option(
+        default = r#"".fortitude_cache""#,
+        value_type = "str",
+        example = r#"cache-dir = "build/.fortitude_cache""#
+    )]
+    pub cache_dir: Option<PathBuf>,
+
+    /// A path to a baseline file, as written by `--generate-baseline`. Relative paths
+    /// are resolved against the project root.
+    ///
+    /// Violations recorded in the baseline are not reported, so that Fortitude can be
+    /// adopted by an existing project without fixing every violation first. Violations
+    /// are matched by rule, file, and the contents of the offending lines, so they
+    /// remain suppressed if other parts of the file change. Use `--show-fixed-baseline`
+    /// to list entries that no longer match a violation and can be removed.
+    #[option(
+        default = "null",
+        value_type = "str",
+        example = r#"baseline = "fortitude-baseline.json""#
+    )]
+    pub baseline: Option<PathBuf>,
+
+    /// Evaluate C preprocessor conditionals before checking files, so that only the
+    /// active branches of `#if`, `#ifdef`, and `#ifndef` blocks are checked. `#define`
+    /// and `#undef` directives are followed, but other directives such as `#include`
+    /// are skipped, and macros are not expanded in Fortran code.
+    ///
+    /// Without this, code containing preprocessor directives often can't be parsed
+    /// correctly, resulting in syntax errors that hide any later violations. Use
+    /// `define` to set which macros are defined.
+    #[option(
+        default = "false",
+        value_type = "bool",
+        example = r#"
+            # Only check the active branches of `#ifdef` blocks.
+            preprocess = true
+        "#
+    )]
+    pub preprocess: Option<bool>,
+
+    /// Macros to define when `preprocess` is enabled, either as `NAME` or as
+    /// `NAME=VALUE`. Macros defined without a value are set to `1`. Definitions
+    /// passed on the command line with `-D` are added to these.
+    #[option(
+        default = "[]",
+        value_type = "list[str]",
+        example = r#"
+            # Check the MPI branches, with a fixed number of dimensions.
+            define = ["MPI", "NDIMS=3"]
+        "#
+    )]
+    pub define: Option<Vec<String>>,
+
     // Rule selection
     /// A list of rule codes or prefixes to ignore. Prefixes can specify exact
     /// rules (like `T003` or `superfluous-implicit-none`), entire categories
@@ -168,7 +287,8 @@ pub struct CheckOptions {
     pub extend_select: Option<Vec<RuleSelector>>,
 
     // File resolver options
-    /// A list of file extensions to check
+    /// A list of file extensions to check. In preview, the extensions in
+    /// [`fixed-form-extensions`](#fixed-form-extensions) are also checked by default.
     #[option(
         default = r#"["f90", "F90", "f95", "F95", "f03", "F03", "f08", "F08", "f18", "F18", "f23", "F23"]"#,
         value_type = "list[str]",
@@ -176,6 +296,18 @@ pub struct CheckOptions {
     )]
     pub file_extensions: Option<Vec<String>>,
 
+    /// A list of file extensions of fixed-form files. These are converted to free-form
+    /// before they're checked, and lines in them may only be 72 columns long.
+    #[option(
+        default = r#"["f", "F", "for", "FOR", "f77", "F77", "ftn", "FTN"]"#,
+        value_type = "list[str]",
+        example = r#"
+            # `.f` files are free-form in this project.
+            fixed-form-extensions = ["for", "f77"]
+        "#
+    )]
+    pub fixed_form_extensions: Option<Vec<String>>,
+
     /// A list of file patterns to exclude from formatting and linting.
     ///
     /// Exclusions are based on globs, and can be either:
@@ -288,6 +420,78 @@ pub struct CheckOptions {
     )]
     pub per_file_ignores: Option<FxHashMap<String, Vec<RuleSelector>>>,
 
+    /// A mapping from rule codes or prefixes to the severity of their violations:
+    /// `"error"`, `"warning"`, or `"info"`. Where several entries refer to the same
+    /// rule, the most specific one is used. Rules that aren't mentioned are errors.
+    ///
+    /// Violations of every severity are reported, and cause a non-zero exit status
+    /// unless `--exit-on` is used to raise the threshold. Output formats for CI
+    /// services, such as `"github"` and `"sarif"`, report each violation at the
+    /// corresponding level.
+    #[option(
+        default = "{}",
+        value_type = r#"dict[RuleSelector, "error" | "warning" | "info"]"#,
+        scope = "severity",
+        example = r#"
+            # Report style violations as warnings, except for `S201` (superfluous implicit none).
+            "S" = "warning"
+            "S201" = "error"
+            # Report modernisation suggestions for information only.
+            "MOD" = "info"
+        "#
+    )]
+    pub severity: Option<BTreeMap<RuleSelector, Severity>>,
+
+    /// Custom rules, defined by [tree-sitter queries](https://tree-sitter.github.io/tree-sitter/using-parsers/queries/).
+    /// Each rule is a table, keyed by the rule's name, with the following entries:
+    ///
+    /// - `code`: the code used to select, ignore, or allow the rule. This must be
+    ///   made up of capital letters followed by digits, and can't start with the
+    ///   prefix of one of Fortitude's rule categories.
+    /// - `query`: the query to match against each file. A violation is reported at
+    ///   the node captured as `@violation`, or at the first capture in the query if
+    ///   there is no `@violation` capture.
+    /// - `message`: the message to report. Captures from the query can be
+    ///   interpolated as `{capture}`.
+    /// - `replacement` (optional): text to replace the reported node with, which can
+    ///   also interpolate captures. This is applied as an unsafe fix.
+    ///
+    /// Custom rules are enabled by default, and can be selected, ignored, and allowed
+    /// by code, code prefix, or name like any other rule, although they can't be used
+    /// in `per-file-ignores`. See [`custom-rule`](rules/custom-rule.md) for more
+    /// details.
+    #[option(
+        default = "{}",
+        value_type = "dict[str, CustomRuleOptions]",
+        scope = "custom-rules",
+        example = r#"
+            # Forbid `stop` statements
+            no-stop = { code = "ACME001", query = "(stop_statement) @violation", message = "Use `error stop` instead of `stop`" }
+            # Replace `dble(x)` with `real(x, dp)`
+            no-dble = { code = "ACME002", query = '(call_expression (identifier) @name (argument_list (_) @arg) (#match? @name "(?i)^dble$")) @violation', message = "Use `real({arg}, dp)` instead of `{violation}`", replacement = "real({arg}, dp)" }
+        "#
+    )]
+    pub custom_rules: Option<BTreeMap<String, CustomRuleOptions>>,
+
+    /// Paths to plugins providing additional rules. Relative paths are resolved
+    /// against the project root.
+    ///
+    /// Plugins are dynamic libraries, built from Rust crates that use the
+    /// `fortitude::plugin` interface, for rules that can't be written as
+    /// `custom-rules` queries. Plugin rules are reported under the plugin's own code
+    /// prefix, and otherwise behave like custom rules. Plugins must be built against
+    /// the same version of Fortitude, and with the same Rust compiler, as the
+    /// `fortitude` executable.
+    ///
+    /// Plugins run with the same permissions as Fortitude itself, so only load
+    /// plugins you trust.
+    #[option(
+        default = "[]",
+        value_type = "list[str]",
+        example = r#"plugins = ["target/release/libacme_rules.so"]"#
+    )]
+    pub plugins: Option<Vec<PathBuf>>,
+
     /// Options for the `exit-or-cycle-in-unlabelled-loops` rule
     #[option_group]
     pub exit_unlabelled_loops: Option<ExitUnlabelledLoopOptions>,
@@ -296,6 +500,10 @@ pub struct CheckOptions {
     #[option_group]
     pub keyword_whitespace: Option<KeywordWhitespaceOptions>,
 
+    /// Options for the magic number rules
+    #[option_group]
+    pub magic_numbers: Option<MagicNumberOptions>,
+
     /// Options for the `bad-string-quote` rule
     #[option_group]
     pub strings: Option<StringOptions>,
@@ -368,6 +576,46 @@ impl KeywordWhitespaceOptions {
     }
 }
 
+/// Options for the magic number rules, such as `magic-number-in-array-size` and
+/// `magic-number-in-expression`
+#[derive(
+    Clone, Debug, PartialEq, Eq, Default, OptionsMetadata, CombineOptions, Serialize, Deserialize,
+)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct MagicNumberOptions {
+    /// Values that aren't considered magic numbers. Real literals that are equal to
+    /// one of these values, such as `2.0`, are also allowed.
+    #[option(
+        default = "[0, 1, 2, 3, 4]",
+        value_type = "list[int]",
+        example = "allowed-values = [0, 1, 2, 10, 100]"
+    )]
+    pub allowed_values: Option<Vec<i64>>,
+
+    /// Places where any number is allowed by
+    /// [`magic-number-in-expression`](rules/magic-number-in-expression.md):
+    ///
+    /// - `"array-bounds"`: subscripts and sections of arrays, such as `x(10)`
+    /// - `"loop-bounds"`: the bounds and step of `do` loops, such as `do i = 1, 10`
+    /// - `"stop-codes"`: the codes of `stop` and `error stop` statements
+    #[option(
+        default = r#"["stop-codes"]"#,
+        value_type = r#"list["array-bounds" | "loop-bounds" | "stop-codes"]"#,
+        example = r#"allowed-contexts = ["loop-bounds", "stop-codes"]"#
+    )]
+    pub allowed_contexts: Option<Vec<MagicNumberContext>>,
+}
+
+impl MagicNumberOptions {
+    pub fn into_settings(self) -> magic_numbers::settings::Settings {
+        let defaults = magic_numbers::settings::Settings::default();
+        magic_numbers::settings::Settings {
+            allowed_values: self.allowed_values.unwrap_or(defaults.allowed_values),
+            allowed_contexts: self.allowed_contexts.unwrap_or(defaults.allowed_contexts),
+        }
+    }
+}
+
 /// Options for the string literal rules
 #[derive(
     Clone, Debug, PartialEq, Eq, Default, OptionsMetadata, CombineOptions, Serialize, Deserialize,
@@ -418,3 +666,63 @@ impl PortabilityOptions {
         }
     }
 }
+
+/// A custom rule, defined by a tree-sitter query
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CustomRuleOptions {
+    pub code: String,
+    pub query: String,
+    pub message: String,
+    pub replacement: Option<String>,
+}
+
+/// Configures how `fortitude format` lays out your code.
+#[derive(
+    Clone, Debug, PartialEq, Eq, Default, OptionsMetadata, CombineOptions, Serialize, Deserialize,
+)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FormatOptions {
+    /// The number of spaces to indent by for each level of nesting, such as the body
+    /// of a procedure, loop, or `if` block.
+    #[option(default = "2", value_type = "int", example = "indent-width = 4")]
+    pub indent_width: Option<usize>,
+
+    /// The number of extra spaces to indent continuation lines by, relative to the
+    /// start of the statement they continue.
+    #[option(default = "4", value_type = "int", example = "continuation-indent = 2")]
+    pub continuation_indent: Option<usize>,
+
+    /// The case to write keywords, intrinsic types, and dotted operators such as
+    /// `.and.` in. Use `"preserve"` to leave them as they are.
+    #[option(
+        default = r#""lower""#,
+        value_type = r#""lower" | "upper" | "preserve""#,
+        example = r#"keyword-case = "upper""#
+    )]
+    pub keyword_case: Option<KeywordCase>,
+
+    /// Whether to put a single space either side of assignments and binary operators,
+    /// and after commas, and to remove spaces just inside brackets. If turned off,
+    /// runs of spaces between tokens are still collapsed to a single space.
+    #[option(
+        default = "true",
+        value_type = "bool",
+        example = "operator-spacing = false"
+    )]
+    pub operator_spacing: Option<bool>,
+}
+
+impl FormatOptions {
+    pub fn into_settings(self) -> FormatSettings {
+        let default = FormatSettings::default();
+        FormatSettings {
+            indent_width: self.indent_width.unwrap_or(default.indent_width),
+            continuation_indent: self
+                .continuation_indent
+                .unwrap_or(default.continuation_indent),
+            keyword_case: self.keyword_case.unwrap_or(default.keyword_case),
+            operator_spacing: self.operator_spacing.unwrap_or(default.operator_spacing),
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! Public interface for rules that are compiled separately from Fortitude and loaded at
+//! runtime from the libraries listed in `check.plugins`.
+//!
+//! A plugin is a `cdylib` crate that depends on the same version of `fortitude`, and
+//! which declares its rules using [`declare_plugin!`]:
+//!
+//! ```ignore
+//! use fortitude::plugin::*;
+//!
+//! struct NoStop;
+//!
+//! impl PluginAstRule for NoStop {
+//!     fn check(&self, _settings: &Settings, node: &Node, _source: &SourceFile) -> Vec<PluginViolation> {
+//!         vec![PluginViolation::from_node("Use `error stop` instead of `stop`", node)]
+//!     }
+//!
+//!     fn entrypoints(&self) -> Vec<&'static str> {
+//!         vec!["stop_statement"]
+//!     }
+//! }
+//!
+//! struct Acme;
+//!
+//! impl Plugin for Acme {
+//!     fn prefix(&self) -> &str {
+//!         "ACME"
+//!     }
+//!
+//!     fn rules(&self) -> Vec<PluginRule> {
+//!         vec![PluginRule {
+//!             code: "001".to_string(),
+//!             name: "no-stop".to_string(),
+//!             explanation: "## What it does\nChecks for `stop` statements.".to_string(),
+//!             check: RuleCheck::Ast(Box::new(NoStop)),
+//!         }]
+//!     }
+//! }
+//!
+//! declare_plugin!(Acme);
+//! ```
+//!
+//! Rust has no stable ABI, so plugins must be built with the same version of Fortitude
+//! and the same compiler as the executable that loads them. This is checked when the
+//! plugin is loaded.
+
+use std::ffi::{c_char, CStr};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use libloading::{Library, Symbol};
+
+pub use ruff_diagnostics::{Edit, Fix};
+pub use ruff_source_file::SourceFile;
+pub use ruff_text_size::{TextRange, TextSize};
+pub use tree_sitter::Node;
+
+pub use crate::settings::Settings;
+
+macro_rules! abi_version {
+    () => {
+        concat!(
+            env!("CARGO_PKG_VERSION"),
+            " (",
+            env!("FORTITUDE_RUSTC_VERSION"),
+            ")"
+        )
+    };
+}
+
+/// Version of the plugin interface. Plugins built against a different version are
+/// rejected when loaded.
+pub const PLUGIN_ABI_VERSION: &str = abi_version!();
+
+/// [`PLUGIN_ABI_VERSION`] as a null-terminated string, for use by [`declare_plugin!`]
+#[doc(hidden)]
+pub const PLUGIN_ABI_VERSION_NUL: &str = concat!(abi_version!(), "\0");
+
+/// A violation raised by a plugin rule. It is reported under the code of the rule that
+/// raised it.
+#[derive(Debug, Clone)]
+pub struct PluginViolation {
+    pub message: String,
+    pub range: TextRange,
+    /// Title describing the fix, or how to fix the violation manually
+    pub suggestion: Option<String>,
+    pub fix: Option<Fix>,
+}
+
+impl PluginViolation {
+    pub fn new(message: impl Into<String>, range: TextRange) -> Self {
+        Self {
+            message: message.into(),
+            range,
+            suggestion: None,
+            fix: None,
+        }
+    }
+
+    /// Report a violation on the whole of `node`
+    pub fn from_node(message: impl Into<String>, node: &Node) -> Self {
+        let range = TextRange::new(
+            TextSize::try_from(node.start_byte()).unwrap(),
+            TextSize::try_from(node.end_byte()).unwrap(),
+        );
+        Self::new(message, range)
+    }
+
+    pub fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+/// Implemented by plugin rules that check file paths
+pub trait PluginPathRule: Send + Sync {
+    fn check(&self, settings: &Settings, path: &Path) -> Option<PluginViolation>;
+}
+
+/// Implemented by plugin rules that analyse lines of code directly
+pub trait PluginTextRule: Send + Sync {
+    fn check(&self, settings: &Settings, source: &SourceFile) -> Vec<PluginViolation>;
+}
+
+/// Implemented by plugin rules that analyse the abstract syntax tree
+pub trait PluginAstRule: Send + Sync {
+    fn check(&self, settings: &Settings, node: &Node, source: &SourceFile) -> Vec<PluginViolation>;
+
+    /// The kinds of node that `check` is called on
+    fn entrypoints(&self) -> Vec<&'static str>;
+}
+
+pub enum RuleCheck {
+    Path(Box<dyn PluginPathRule>),
+    Text(Box<dyn PluginTextRule>),
+    Ast(Box<dyn PluginAstRule>),
+}
+
+/// A rule provided by a plugin
+pub struct PluginRule {
+    /// The code of the rule, without the plugin's prefix, such as `001`
+    pub code: String,
+    /// The name of the rule, such as `no-stop`
+    pub name: String,
+    /// Shown by `fortitude explain`, in the same format as the built-in rules
+    pub explanation: String,
+    pub check: RuleCheck,
+}
+
+/// The entry point of a plugin, registered with [`declare_plugin!`]
+pub trait Plugin: Send + Sync {
+    /// Prefix of the codes of this plugin's rules, such as `ACME`. This must be made up
+    /// of capital letters, and mustn't be used by any other rules.
+    fn prefix(&self) -> &str;
+
+    fn rules(&self) -> Vec<PluginRule>;
+}
+
+/// Export a [`Plugin`] from a `cdylib` crate so that Fortitude can load it. The
+/// argument is an expression that constructs the plugin.
+#[macro_export]
+macro_rules! declare_plugin {
+    ($plugin:expr) => {
+        #[no_mangle]
+        pub extern "C" fn fortitude_plugin_abi_version() -> *const ::std::ffi::c_char {
+            $crate::plugin::PLUGIN_ABI_VERSION_NUL.as_ptr().cast()
+        }
+
+        #[no_mangle]
+        pub fn fortitude_plugin_create() -> ::std::boxed::Box<dyn $crate::plugin::Plugin> {
+            ::std::boxed::Box::new($plugin)
+        }
+    };
+}
+
+/// A plugin loaded from a dynamic library. The library must be kept alive for as long
+/// as the plugin and any of the rules created by it.
+pub(crate) struct LoadedPlugin {
+    pub plugin: Box<dyn Plugin>,
+    pub library: Library,
+}
+
+pub(crate) fn load_plugin(path: &Path) -> Result<LoadedPlugin> {
+    // SAFETY: Loading a library runs its initialisation code, which we have to trust in
+    // the same way as any other code the user asks us to run
+    let library = unsafe { Library::new(path) }
+        .with_context(|| format!("Failed to load plugin {}", path.display()))?;
+
+    // SAFETY: The version is exported by `declare_plugin!` as a C function returning a
+    // null-terminated static string, which is safe to call from any compiler version
+    let version = unsafe {
+        let abi_version: Symbol<extern "C" fn() -> *const c_char> = library
+            .get(b"fortitude_plugin_abi_version\0")
+            .with_context(|| {
+                format!(
+                    "{} is not a Fortitude plugin, see `declare_plugin!`",
+                    path.display()
+                )
+            })?;
+        CStr::from_ptr(abi_version()).to_string_lossy().into_owned()
+    };
+    check_abi_version(path, &version)?;
+
+    // SAFETY: The plugin was built with the same version of Fortitude and the same
+    // compiler, so the layout of `Box<dyn Plugin>` matches on both sides
+    let plugin = unsafe {
+        let create: Symbol<fn() -> Box<dyn Plugin>> = library
+            .get(b"fortitude_plugin_create\0")
+            .with_context(|| format!("Plugin {} has no entry point", path.display()))?;
+        create()
+    };
+
+    Ok(LoadedPlugin { plugin, library })
+}
+
+/// Plugins must be built against the same version of Fortitude, with the same compiler
+fn check_abi_version(path: &Path, version: &str) -> Result<()> {
+    if version != PLUGIN_ABI_VERSION {
+        bail!(
+            "Plugin {} was built for Fortitude {version}, but this is Fortitude {PLUGIN_ABI_VERSION}. Rebuild the plugin against this version.",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use anyhow::Result;
+    use tempfile::TempDir;
+
+    use super::{check_abi_version, load_plugin, PLUGIN_ABI_VERSION};
+
+    /// A library that's always available to load, but which isn't a plugin
+    #[cfg(target_os = "linux")]
+    const SYSTEM_LIBRARY: &str = "libc.so.6";
+    #[cfg(target_os = "macos")]
+    const SYSTEM_LIBRARY: &str = "/usr/lib/libSystem.B.dylib";
+    #[cfg(windows)]
+    const SYSTEM_LIBRARY: &str = "kernel32.dll";
+
+    #[test]
+    fn missing_library() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        let path = tempdir.path().join("libmissing.so");
+        let Err(err) = load_plugin(&path) else {
+            panic!("Loaded a plugin that doesn't exist");
+        };
+        assert_eq!(
+            err.to_string(),
+            format!("Failed to load plugin {}", path.display())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_library() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        let path = tempdir.path().join("libinvalid.so");
+        std::fs::write(&path, "not a library")?;
+        let Err(err) = load_plugin(&path) else {
+            panic!("Loaded a plugin from a text file");
+        };
+        assert_eq!(
+            err.to_string(),
+            format!("Failed to load plugin {}", path.display())
+        );
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+    #[test]
+    fn not_a_plugin() {
+        let path = Path::new(SYSTEM_LIBRARY);
+        let Err(err) = load_plugin(path) else {
+            panic!("Loaded {SYSTEM_LIBRARY} as a plugin");
+        };
+        assert_eq!(
+            err.to_string(),
+            format!("{SYSTEM_LIBRARY} is not a Fortitude plugin, see `declare_plugin!`")
+        );
+    }
+
+    #[test]
+    fn abi_version() {
+        let path = Path::new("libacme.so");
+        assert!(check_abi_version(path, PLUGIN_ABI_VERSION).is_ok());
+
+        let err = check_abi_version(path, "0.1.0 (rustc 1.80.0)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Plugin libacme.so was built for Fortitude 0.1.0 (rustc 1.80.0), but this is Fortitude {PLUGIN_ABI_VERSION}. Rebuild the plugin against this version."
+            )
+        );
+    }
+}
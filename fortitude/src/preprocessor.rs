@@ -0,0 +1,538 @@
+//! A minimal C preprocessor, used to find the active branches of `#if` blocks so that
+//! only those are parsed.
+//!
+//! Rather than building a new file, directives and lines in inactive branches are
+//! replaced with whitespace. Every byte offset in the output is the same as in the
+//! original file, so diagnostics raised on the parsed tree already refer to the
+//! original source, and text rules can keep checking the original file. Macros are
+//! only expanded inside `#if` and `#elif` expressions, never in Fortran code.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use rustc_hash::FxHashMap;
+
+use crate::settings::CheckSettings;
+
+/// Limit on nested macro expansions in `#if` expressions, to guard against macros
+/// that refer to themselves.
+const MAX_EXPANSION_DEPTH: usize = 32;
+
+/// Parse macro definitions of the form `NAME` or `NAME=VALUE`, as passed to `-D`.
+/// Macros without a value are defined as `1`.
+pub(crate) fn parse_definitions<'a>(
+    definitions: impl IntoIterator<Item = &'a String>,
+) -> Result<BTreeMap<String, String>> {
+    definitions
+        .into_iter()
+        .map(|definition| {
+            let (name, value) = definition.split_once('=').unwrap_or((definition, "1"));
+            let name = name.trim();
+            if !is_identifier(name) {
+                return Err(anyhow!(
+                    "Invalid macro definition '{definition}', expected NAME or NAME=VALUE"
+                ));
+            }
+            Ok((name.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// The text of a file that should be parsed: either the original source, or, if
+/// preprocessing is enabled, the source with only the active branches left in.
+pub(crate) fn active_source<'a>(source: &'a str, settings: &CheckSettings) -> Cow<'a, str> {
+    if settings.preprocess {
+        preprocess(source, &settings.define)
+    } else {
+        Cow::Borrowed(source)
+    }
+}
+
+/// Blank out preprocessor directives and any lines in inactive branches of
+/// conditionals. The result is always exactly the same length as `source`.
+///
+/// `#if` expressions that can't be evaluated are treated as false.
+pub(crate) fn preprocess<'a>(
+    source: &'a str,
+    definitions: &BTreeMap<String, String>,
+) -> Cow<'a, str> {
+    if !source.lines().any(is_directive) {
+        return Cow::Borrowed(source);
+    }
+
+    let mut macros: FxHashMap<String, String> = definitions
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    let mut conditionals: Vec<Conditional> = Vec::new();
+    let mut output = String::with_capacity(source.len());
+
+    let mut lines = source.split_inclusive('\n');
+    while let Some(line) = lines.next() {
+        let active = conditionals.last().map_or(true, |block| block.active);
+        if !is_directive(line) {
+            if active {
+                output.push_str(line);
+            } else {
+                blank(line, &mut output);
+            }
+            continue;
+        }
+
+        // Directives may be continued onto the next line with a backslash
+        blank(line, &mut output);
+        let mut directive = line.trim_end_matches(['\r', '\n']).to_string();
+        while directive.ends_with('\\') {
+            directive.pop();
+            let Some(next) = lines.next() else {
+                break;
+            };
+            blank(next, &mut output);
+            directive.push_str(next.trim_end_matches(['\r', '\n']));
+        }
+
+        let directive = strip_comments(directive.trim_start().trim_start_matches('#'));
+        let directive = directive.trim_start();
+        let keyword_end = directive
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(directive.len());
+        let (keyword, rest) = directive.split_at(keyword_end);
+        let rest = rest.trim();
+
+        match keyword {
+            "if" | "ifdef" | "ifndef" => {
+                let taken = active
+                    && match keyword {
+                        "if" => evaluate(rest, &macros),
+                        "ifdef" => macros.contains_key(leading_identifier(rest)),
+                        _ => !macros.contains_key(leading_identifier(rest)),
+                    };
+                conditionals.push(Conditional {
+                    parent_active: active,
+                    taken,
+                    active: taken,
+                });
+            }
+            "elif" => {
+                if let Some(block) = conditionals.last_mut() {
+                    block.active = block.parent_active && !block.taken && evaluate(rest, &macros);
+                    block.taken |= block.active;
+                }
+            }
+            "else" => {
+                if let Some(block) = conditionals.last_mut() {
+                    block.active = block.parent_active && !block.taken;
+                    block.taken = true;
+                }
+            }
+            "endif" => {
+                conditionals.pop();
+            }
+            "define" if active => {
+                let name = leading_identifier(rest);
+                let value = &rest[name.len()..];
+                // Function-like macros can't be expanded meaningfully in conditions,
+                // but `defined` should still see them
+                let value = if value.starts_with('(') {
+                    ""
+                } else {
+                    value.trim()
+                };
+                if !name.is_empty() {
+                    macros.insert(name.to_string(), value.to_string());
+                }
+            }
+            "undef" if active => {
+                macros.remove(leading_identifier(rest));
+            }
+            // Other directives, such as `#include`, `#pragma`, and `#error`, are
+            // skipped entirely
+            _ => {}
+        }
+    }
+
+    Cow::Owned(output)
+}
+
+/// The state of a single `#if` ... `#endif` block.
+struct Conditional {
+    /// Whether the block containing this one is active.
+    parent_active: bool,
+    /// Whether any branch of this block has been active so far.
+    taken: bool,
+    /// Whether the current branch is active.
+    active: bool,
+}
+
+fn is_directive(line: &str) -> bool {
+    line.trim_start().starts_with('#')
+}
+
+/// Replace every character in `line` except line endings with spaces, keeping the
+/// byte length the same.
+fn blank(line: &str, output: &mut String) {
+    for c in line.chars() {
+        match c {
+            '\n' | '\r' => output.push(c),
+            _ => output.extend(std::iter::repeat(' ').take(c.len_utf8())),
+        }
+    }
+}
+
+/// Remove C-style `/* ... */` comments from a directive.
+fn strip_comments(directive: &str) -> String {
+    let mut result = String::with_capacity(directive.len());
+    let mut rest = directive;
+    while let Some(start) = rest.find("/*") {
+        result.push_str(&rest[..start]);
+        result.push(' ');
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    result.push_str(rest);
+    result
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn leading_identifier(text: &str) -> &str {
+    let end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+// `#if` expressions
+// -----------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Identifier(String),
+    Punct(&'static str),
+}
+
+/// Longer operators first, so that they take priority over their prefixes.
+const PUNCTUATION: [&str; 24] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "!", "~", "+", "-", "*", "/", "%", "<", ">",
+    "&", "^", "|", "?", ":", "(", ")",
+];
+
+/// Evaluate the condition of an `#if` or `#elif` directive.
+fn evaluate(expression: &str, macros: &FxHashMap<String, String>) -> bool {
+    tokenize(expression)
+        .and_then(|tokens| expand(tokens, macros, 0))
+        .and_then(|tokens| {
+            let mut parser = ExpressionParser {
+                tokens: &tokens,
+                position: 0,
+            };
+            let value = parser.ternary()?;
+            (parser.position == tokens.len()).then_some(value)
+        })
+        .is_some_and(|value| value != 0)
+}
+
+fn tokenize(text: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_integer(&rest[..end])?));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let name = leading_identifier(rest);
+            tokens.push(Token::Identifier(name.to_string()));
+            rest = &rest[name.len()..];
+        } else {
+            let punct = PUNCTUATION.iter().find(|punct| rest.starts_with(**punct))?;
+            tokens.push(Token::Punct(punct));
+            rest = &rest[punct.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Some(tokens)
+}
+
+fn parse_integer(literal: &str) -> Option<i64> {
+    let literal = literal.trim_end_matches(['u', 'U', 'l', 'L']);
+    if let Some(hex) = literal
+        .strip_prefix("0x")
+        .or_else(|| literal.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16).ok()
+    } else if literal.len() > 1 && literal.starts_with('0') {
+        i64::from_str_radix(&literal[1..], 8).ok()
+    } else {
+        literal.parse().ok()
+    }
+}
+
+/// Replace `defined` operators and macro names with their values. Identifiers that
+/// aren't macros evaluate to zero.
+fn expand(
+    tokens: Vec<Token>,
+    macros: &FxHashMap<String, String>,
+    depth: usize,
+) -> Option<Vec<Token>> {
+    if depth > MAX_EXPANSION_DEPTH {
+        return None;
+    }
+
+    let mut expanded = Vec::with_capacity(tokens.len());
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Identifier(name) if name == "defined" => {
+                let name = match tokens.next()? {
+                    Token::Identifier(name) => name,
+                    Token::Punct("(") => match (tokens.next()?, tokens.next()?) {
+                        (Token::Identifier(name), Token::Punct(")")) => name,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                expanded.push(Token::Number(macros.contains_key(&name).into()));
+            }
+            Token::Identifier(name) => match macros.get(&name) {
+                Some(value) => expanded.extend(expand(tokenize(value)?, macros, depth + 1)?),
+                None => expanded.push(Token::Number(0)),
+            },
+            token => expanded.push(token),
+        }
+    }
+    Some(expanded)
+}
+
+/// Precedence climbing parser for C integer constant expressions, evaluating as it
+/// goes.
+struct ExpressionParser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl ExpressionParser<'_> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Punct(next)) if *next == punct);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn ternary(&mut self) -> Option<i64> {
+        let condition = self.binary(1)?;
+        if !self.eat("?") {
+            return Some(condition);
+        }
+        let if_true = self.ternary()?;
+        if !self.eat(":") {
+            return None;
+        }
+        let if_false = self.ternary()?;
+        Some(if condition != 0 { if_true } else { if_false })
+    }
+
+    fn binary(&mut self, min_precedence: u8) -> Option<i64> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Punct(op)) = self.peek() {
+            let op = *op;
+            let Some(precedence) = precedence(op) else {
+                break;
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.position += 1;
+            let rhs = self.binary(precedence + 1)?;
+            lhs = match op {
+                "*" => lhs.wrapping_mul(rhs),
+                "/" => lhs.checked_div(rhs)?,
+                "%" => lhs.checked_rem(rhs)?,
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "<<" => lhs.wrapping_shl(rhs as u32),
+                ">>" => lhs.wrapping_shr(rhs as u32),
+                "<" => (lhs < rhs).into(),
+                ">" => (lhs > rhs).into(),
+                "<=" => (lhs <= rhs).into(),
+                ">=" => (lhs >= rhs).into(),
+                "==" => (lhs == rhs).into(),
+                "!=" => (lhs != rhs).into(),
+                "&" => lhs & rhs,
+                "^" => lhs ^ rhs,
+                "|" => lhs | rhs,
+                "&&" => (lhs != 0 && rhs != 0).into(),
+                "||" => (lhs != 0 || rhs != 0).into(),
+                _ => unreachable!("All binary operators have a precedence"),
+            };
+        }
+        Some(lhs)
+    }
+
+    fn unary(&mut self) -> Option<i64> {
+        match self.next()? {
+            Token::Number(value) => Some(*value),
+            Token::Punct("!") => Some((self.unary()? == 0).into()),
+            Token::Punct("~") => Some(!self.unary()?),
+            Token::Punct("-") => Some(self.unary()?.wrapping_neg()),
+            Token::Punct("+") => self.unary(),
+            Token::Punct("(") => {
+                let value = self.ternary()?;
+                self.eat(")").then_some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn precedence(op: &str) -> Option<u8> {
+    let precedence = match op {
+        "*" | "/" | "%" => 10,
+        "+" | "-" => 9,
+        "<<" | ">>" => 8,
+        "<" | ">" | "<=" | ">=" => 7,
+        "==" | "!=" => 6,
+        "&" => 5,
+        "^" => 4,
+        "|" => 3,
+        "&&" => 2,
+        "||" => 1,
+        _ => return None,
+    };
+    Some(precedence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use textwrap::dedent;
+
+    fn definitions(definitions: &[&str]) -> BTreeMap<String, String> {
+        let definitions: Vec<String> = definitions.iter().map(|d| d.to_string()).collect();
+        parse_definitions(&definitions).unwrap()
+    }
+
+    /// Preprocess `source`, and return just the lines that are left.
+    fn active_lines(source: &str, defined: &[&str]) -> Vec<String> {
+        let source = dedent(source);
+        let output = preprocess(&source, &definitions(defined));
+        assert_eq!(output.len(), source.len());
+        output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn no_directives() {
+        let source = "program test\nend program test\n";
+        assert!(matches!(
+            preprocess(source, &BTreeMap::new()),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn ifdef() {
+        let source = r#"
+            program test
+            #ifdef MPI
+              use mpi
+            #else
+              use serial
+            #endif
+            end program test
+            "#;
+        assert_eq!(
+            active_lines(source, &["MPI"]),
+            ["program test", "use mpi", "end program test"]
+        );
+        assert_eq!(
+            active_lines(source, &[]),
+            ["program test", "use serial", "end program test"]
+        );
+    }
+
+    #[test]
+    fn if_elif_else() {
+        let source = r#"
+            #define NDIMS 2
+            #if NDIMS == 3 && !defined(FLAT)
+              three
+            #elif NDIMS > 1 /* includes 2 */
+            #  ifndef FLAT
+              two
+            #  endif
+            #elif UNDEFINED_MACRO
+              undefined
+            #else
+              one
+            #endif
+            "#;
+        assert_eq!(active_lines(source, &[]), ["two"]);
+        assert_eq!(active_lines(source, &["FLAT"]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn define_and_undef() {
+        let source = r#"
+            #define DEBUG
+            #if defined DEBUG && LEVEL >= 0x2
+              debug
+            #endif
+            #undef DEBUG
+            #ifdef DEBUG
+              still debug
+            #endif
+            "#;
+        assert_eq!(active_lines(source, &["LEVEL=2"]), ["debug"]);
+        assert_eq!(active_lines(source, &["LEVEL=1"]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn inactive_defines_are_ignored() {
+        let source = r#"
+            #if 0
+            #define HIDDEN
+            #endif
+            #if defined(HIDDEN) || \
+                defined(SHOWN)
+              visible
+            #endif
+            "#;
+        assert_eq!(active_lines(source, &[]), Vec::<String>::new());
+        assert_eq!(active_lines(source, &["SHOWN"]), ["visible"]);
+    }
+
+    #[test]
+    fn invalid_definitions() {
+        let definitions = vec!["1FOO=2".to_string()];
+        assert!(parse_definitions(&definitions).is_err());
+    }
+}
@@ -1,8 +1,9 @@
 use std::cmp::Reverse;
-use std::fmt::Display;
-use std::io::Write;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bitflags::bitflags;
 use colored::Colorize;
 use itertools::{iterate, Itertools};
@@ -13,12 +14,11 @@ use crate::diagnostics::{Diagnostics, FixMap};
 use crate::fs::relativize_path;
 use crate::logging::LogLevel;
 use crate::message::{
-    AzureEmitter, DiagnosticMessage, Emitter, GithubEmitter, GitlabEmitter, GroupedEmitter,
-    JsonEmitter, JsonLinesEmitter, JunitEmitter, PylintEmitter, RdjsonEmitter, SarifEmitter,
-    TextEmitter,
+    AzureEmitter, CheckstyleEmitter, CodeclimateEmitter, DiagnosticMessage, Emitter, GithubEmitter,
+    GitlabEmitter, GroupedEmitter, HtmlEmitter, JsonEmitter, JsonLinesEmitter, JunitEmitter,
+    PylintEmitter, RdjsonEmitter, SarifEmitter, SonarqubeEmitter, TextEmitter,
 };
-use crate::rules::Rule;
-use crate::settings::{FixMode, OutputFormat, UnsafeFixes};
+use crate::settings::{FixMode, OutputFormat, OutputTarget, UnsafeFixes};
 
 bitflags! {
     #[derive(Default, Debug, Copy, Clone)]
@@ -33,66 +33,13 @@ bitflags! {
 }
 
 #[derive(Serialize)]
-struct ExpandedStatistics {
-    code: Option<SerializeRuleAsCode>,
-    name: SerializeRuleAsTitle,
+struct ExpandedStatistics<'a> {
+    code: &'a str,
+    name: &'a str,
     count: usize,
     fixable: bool,
 }
 
-#[derive(Copy, Clone)]
-struct SerializeRuleAsCode(Rule);
-
-impl Serialize for SerializeRuleAsCode {
-    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_str(&self.0.noqa_code().to_string())
-    }
-}
-
-impl Display for SerializeRuleAsCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0.noqa_code())
-    }
-}
-
-impl From<Rule> for SerializeRuleAsCode {
-    fn from(rule: Rule) -> Self {
-        Self(rule)
-    }
-}
-
-struct SerializeRuleAsTitle(Rule);
-
-impl Serialize for SerializeRuleAsTitle {
-    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_str(self.0.as_ref())
-    }
-}
-
-impl Display for SerializeRuleAsTitle {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(self.0.as_ref())
-    }
-}
-
-impl From<Option<Rule>> for SerializeRuleAsTitle {
-    fn from(rule: Option<Rule>) -> Self {
-        match rule {
-            Some(rule) => Self(rule),
-            // This is a bit weird, but it's because `DiagnosticMessage::rule`
-            // returns `Option<Rule>`, leftover from ruff which treats
-            // `SyntaxError` specially and we don't. Should just not return `Option` there
-            None => Self(Rule::SyntaxError),
-        }
-    }
-}
-
 pub(crate) struct Printer {
     format: OutputFormat,
     log_level: LogLevel,
@@ -118,6 +65,46 @@ impl Printer {
         }
     }
 
+    /// Use a different output format with the same options
+    pub(crate) fn with_format(&self, format: OutputFormat) -> Self {
+        Self {
+            format,
+            log_level: self.log_level,
+            flags: self.flags,
+            fix_mode: self.fix_mode,
+            unsafe_fixes: self.unsafe_fixes,
+        }
+    }
+
+    /// Write the results to each of `outputs`, in its own format, as well as to the
+    /// main output. Files always get the full report, even if the main output only
+    /// shows statistics, as most formats have no way to represent them.
+    pub(crate) fn write_output_files(
+        &self,
+        results: &CheckResults,
+        outputs: &[OutputTarget],
+    ) -> Result<()> {
+        if outputs.is_empty() {
+            return Ok(());
+        }
+
+        // Terminal colour codes shouldn't be written to files
+        let colorize = colored::control::SHOULD_COLORIZE.should_colorize();
+        colored::control::set_override(false);
+        let result = outputs.iter().try_for_each(|output| {
+            let Some(path) = &output.path else {
+                return Ok(());
+            };
+            let mut writer = create_output_file(path)?;
+            let printer = self.with_format(output.format);
+            printer.write_once(results, &mut writer)?;
+            writer.flush()?;
+            Ok(())
+        });
+        colored::control::set_override(colorize);
+        result
+    }
+
     fn write_summary_text(&self, writer: &mut dyn Write, results: &CheckResults) -> Result<()> {
         if self.log_level < LogLevel::Default {
             return Ok(());
@@ -340,6 +327,18 @@ impl Printer {
             OutputFormat::Pylint => {
                 PylintEmitter.emit(writer, &results.diagnostics.messages)?;
             }
+            OutputFormat::Checkstyle => {
+                CheckstyleEmitter.emit(writer, &results.diagnostics.messages)?;
+            }
+            OutputFormat::Codeclimate => {
+                CodeclimateEmitter::default().emit(writer, &results.diagnostics.messages)?;
+            }
+            OutputFormat::Sonarqube => {
+                SonarqubeEmitter.emit(writer, &results.diagnostics.messages)?;
+            }
+            OutputFormat::Html => {
+                HtmlEmitter.emit(writer, &results.diagnostics.messages)?;
+            }
         }
 
         writer.flush()?;
@@ -355,12 +354,12 @@ impl Printer {
             .diagnostics
             .messages
             .iter()
-            .sorted_by_key(|message| (message.rule(), message.fixable()))
+            .sorted_by_key(|message| (message.code(), message.fixable()))
             .fold(
                 vec![],
                 |mut acc: Vec<(&DiagnosticMessage, usize)>, message| {
                     if let Some((prev_message, count)) = acc.last_mut() {
-                        if prev_message.rule() == message.rule() {
+                        if prev_message.code() == message.code() {
                             *count += 1;
                             return acc;
                         }
@@ -371,8 +370,8 @@ impl Printer {
             )
             .iter()
             .map(|&(message, count)| ExpandedStatistics {
-                code: message.rule().map(std::convert::Into::into),
-                name: message.rule().into(),
+                code: message.code(),
+                name: message.rule_name(),
                 count,
                 fixable: if let Some(fix) = message.fix() {
                     fix.applies(self.unsafe_fixes.required_applicability())
@@ -400,12 +399,7 @@ impl Printer {
                 );
                 let code_width = statistics
                     .iter()
-                    .map(|statistic| {
-                        statistic
-                            .code
-                            .map_or_else(String::new, |rule| rule.to_string())
-                            .len()
-                    })
+                    .map(|statistic| statistic.code.len())
                     .max()
                     .unwrap();
                 let any_fixable = statistics.iter().any(|statistic| statistic.fixable);
@@ -419,11 +413,7 @@ impl Printer {
                         writer,
                         "{:>count_width$}\t{:<code_width$}\t{}{}",
                         statistic.count.to_string().bold(),
-                        statistic
-                            .code
-                            .map_or_else(String::new, |rule| rule.to_string())
-                            .red()
-                            .bold(),
+                        statistic.code.red().bold(),
                         if any_fixable {
                             if statistic.fixable {
                                 &fixable
@@ -457,6 +447,16 @@ impl Printer {
     }
 }
 
+/// Create a file to write output to, along with any missing parent directories
+pub(crate) fn create_output_file(path: &Path) -> Result<BufWriter<File>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create output file {}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
 fn num_digits(n: usize) -> usize {
     iterate(n, |&n| n / 10)
         .take_while(|&n| n > 0)
@@ -507,8 +507,8 @@ fn print_fix_summary(writer: &mut dyn Write, fixed: &FixMap) -> Result<()> {
             writeln!(
                 writer,
                 "    {count:>num_digits$} × {} ({})",
-                rule.noqa_code().to_string().red().bold(),
-                rule.as_ref(),
+                rule.code().red().bold(),
+                rule.name(),
             )?;
         }
     }
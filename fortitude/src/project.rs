@@ -0,0 +1,744 @@
+//! Project-wide analysis, run after all files have been checked individually.
+//!
+//! Every file is indexed while it's checked to find the modules it defines and uses,
+//! which is then used to check rules that need to see the whole project at once.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::iter::once;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use ruff_diagnostics::Diagnostic;
+use ruff_source_file::SourceFile;
+use ruff_text_size::TextRange;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Tree};
+
+use crate::allow_comments::{gather_allow_comments, resolve_disabled_regions, AllowCommentKind};
+use crate::ast::FortitudeNode;
+use crate::check::apply_per_file_ignores;
+use crate::message::DiagnosticMessage;
+use crate::registry::AsRule;
+use crate::rules::correctness::modules::{
+    CircularModuleDependency, UndefinedModule, UnusedPublicEntity,
+};
+use crate::rules::Rule;
+use crate::semantic::{Scope, ScopeKind, SemanticModel, SymbolKind};
+use crate::settings::Settings;
+
+/// Rules that are checked by [`check_project`].
+pub(crate) const PROJECT_RULES: &[Rule] = &[
+    Rule::UndefinedModule,
+    Rule::CircularModuleDependency,
+    Rule::UnusedPublicEntity,
+];
+
+/// The codes of [`PROJECT_RULES`], for warning that they can't be checked.
+pub(crate) fn project_rule_codes() -> String {
+    PROJECT_RULES
+        .iter()
+        .map(|rule| rule.noqa_code().to_string())
+        .join(", ")
+}
+
+/// Modules provided by the compiler, which are never defined in the project.
+const INTRINSIC_MODULES: &[&str] = &[
+    "iso_fortran_env",
+    "iso_c_binding",
+    "ieee_arithmetic",
+    "ieee_exceptions",
+    "ieee_features",
+    "omp_lib",
+    "omp_lib_kinds",
+    "openacc",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum UnitKind {
+    Module,
+    Submodule,
+    /// Programs and external procedures.
+    Other,
+}
+
+/// A `use` statement anywhere in a program unit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Use {
+    module: String,
+    intrinsic: bool,
+    range: TextRange,
+}
+
+/// A public entity of a module.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entity {
+    name: String,
+    range: TextRange,
+}
+
+/// A top-level program unit: a module, submodule, program or external procedure.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ProgramUnit {
+    kind: UnitKind,
+    /// The lowercased name, if it has one.
+    name: Option<String>,
+    /// For submodules, the module they extend.
+    ancestor: Option<Use>,
+    uses: Vec<Use>,
+    /// All identifiers in the unit, lowercased.
+    identifiers: FxHashSet<String>,
+    public: Vec<Entity>,
+}
+
+/// A rule allowed by an allow comment, and the range it applies to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct AllowedRule {
+    #[serde(with = "rule_name")]
+    rule: Rule,
+    range: TextRange,
+}
+
+/// Everything needed from a file to check project rules. This is built from the
+/// syntax tree while the file is checked individually, and cached along with its
+/// diagnostics, so files don't need to be parsed again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct FileIndex {
+    units: Vec<ProgramUnit>,
+    allowed: Vec<AllowedRule>,
+}
+
+impl FileIndex {
+    pub(crate) fn new(tree: &Tree, file: &SourceFile) -> Self {
+        let src = file.source_text();
+        let semantic = SemanticModel::new(tree, src);
+
+        let root = tree.root_node();
+        let units = root
+            .named_children(&mut root.walk())
+            .filter_map(|node| ProgramUnit::new(&node, &semantic, src))
+            .collect();
+
+        let mut allow_comments = once(root)
+            .chain(root.descendants())
+            .filter_map(|node| gather_allow_comments(&node, file))
+            .collect_vec();
+        resolve_disabled_regions(&mut allow_comments);
+        let allowed = allow_comments
+            .iter()
+            .filter(|comment| comment.kind != AllowCommentKind::Enable)
+            .flat_map(|comment| &comment.codes)
+            .filter_map(|code| {
+                Some(AllowedRule {
+                    rule: code.rule?,
+                    range: code.range?,
+                })
+            })
+            .collect();
+
+        Self { units, allowed }
+    }
+}
+
+/// A file that has been indexed for project rules.
+#[derive(Debug, PartialEq)]
+pub(crate) struct IndexedFile {
+    path: PathBuf,
+    file: SourceFile,
+    index: FileIndex,
+}
+
+impl IndexedFile {
+    pub(crate) fn new(path: &Path, file: SourceFile, index: FileIndex) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            file,
+            index,
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn index(&self) -> &FileIndex {
+        &self.index
+    }
+}
+
+/// Rules are stored in the cache by name.
+mod rule_name {
+    use std::str::FromStr;
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use crate::rules::Rule;
+
+    pub(super) fn serialize<S: Serializer>(rule: &Rule, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(rule.as_ref())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Rule, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Rule::from_str(&name).map_err(de::Error::custom)
+    }
+}
+
+impl ProgramUnit {
+    fn new(node: &Node, semantic: &SemanticModel, src: &str) -> Option<Self> {
+        let kind = match node.kind() {
+            "module" => UnitKind::Module,
+            "submodule" => UnitKind::Submodule,
+            "program" | "function" | "subroutine" => UnitKind::Other,
+            _ => return None,
+        };
+        let scope = semantic.scope(semantic.scope_of(node)?);
+        let name = scope.name().map(str::to_lowercase);
+
+        let ancestor = if kind == UnitKind::Submodule {
+            node.named_child(0)
+                .and_then(|statement| statement.child_with_name("module_name"))
+                .and_then(|module| {
+                    Some(Use {
+                        module: module.to_text(src)?.to_lowercase(),
+                        intrinsic: false,
+                        range: module.textrange(),
+                    })
+                })
+        } else {
+            None
+        };
+
+        let mut uses = Vec::new();
+        let mut identifiers = FxHashSet::default();
+        for child in node.descendants() {
+            match child.kind() {
+                "use_statement" => {
+                    let Some(module) = child
+                        .child_with_name("module_name")
+                        .and_then(|module| module.to_text(src))
+                    else {
+                        continue;
+                    };
+                    let intrinsic = child.children(&mut child.walk()).any(|keyword| {
+                        keyword
+                            .to_text(src)
+                            .is_some_and(|text| text.eq_ignore_ascii_case("intrinsic"))
+                    });
+                    uses.push(Use {
+                        module: module.to_lowercase(),
+                        intrinsic,
+                        range: child.textrange(),
+                    });
+                }
+                "identifier" => {
+                    if let Some(text) = child.to_text(src) {
+                        identifiers.insert(text.to_lowercase());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let public = if scope.kind() == ScopeKind::Module {
+            public_entities(node, scope, src)
+        } else {
+            vec![]
+        };
+
+        Some(Self {
+            kind,
+            name,
+            ancestor,
+            uses,
+            identifiers,
+            public,
+        })
+    }
+}
+
+/// Find the public entities declared in a module.
+fn public_entities(node: &Node, scope: &Scope, src: &str) -> Vec<Entity> {
+    let mut default_public = true;
+    let mut public = FxHashSet::default();
+    let mut private = FxHashSet::default();
+    for child in node.named_children(&mut node.walk()) {
+        let names = match child.kind() {
+            "public_statement" => &mut public,
+            "private_statement" => &mut private,
+            _ => continue,
+        };
+        if child.named_child(0).is_none() {
+            default_public = child.kind() == "public_statement";
+        }
+        names.extend(
+            child
+                .named_descendants()
+                .filter(|name| name.kind() == "identifier")
+                .filter_map(|name| name.to_text(src))
+                .map(str::to_lowercase),
+        );
+    }
+
+    scope
+        .symbols()
+        .filter(|symbol| {
+            matches!(
+                symbol.kind(),
+                SymbolKind::Variable
+                    | SymbolKind::NamedConstant
+                    | SymbolKind::Procedure
+                    | SymbolKind::DerivedType
+                    | SymbolKind::Interface
+            )
+        })
+        .filter(|symbol| {
+            let name = symbol.name().to_lowercase();
+            if symbol.has_attribute("public") || public.contains(&name) {
+                true
+            } else if symbol.has_attribute("private") || private.contains(&name) {
+                false
+            } else {
+                default_public
+            }
+        })
+        .map(|symbol| Entity {
+            name: symbol.name().to_string(),
+            range: symbol.range(),
+        })
+        .collect()
+}
+
+/// Check rules that need information from every file in the project, using the
+/// indexes built while checking each file. `settings` gives the settings for each file,
+/// which decide the rules it's checked for.
+pub(crate) fn check_project<'a>(
+    indexed: &[IndexedFile],
+    settings: impl Fn(&Path) -> &'a Settings,
+) -> Vec<DiagnosticMessage> {
+    // Every file is still needed to find where modules are defined and used, even if
+    // it has project rules disabled itself
+    let file_settings = indexed
+        .iter()
+        .map(|file| settings(&file.path))
+        .collect_vec();
+    let enabled = |rule| {
+        file_settings
+            .iter()
+            .any(|settings| settings.check.rules.enabled(rule))
+    };
+
+    let mut diagnostics: Vec<Vec<Diagnostic>> = indexed.iter().map(|_| Vec::new()).collect();
+
+    // Modules defined in the project, and the modules they depend on
+    let mut modules: BTreeMap<&str, (usize, &ProgramUnit)> = BTreeMap::new();
+    for (index, file) in indexed.iter().enumerate() {
+        for unit in &file.index.units {
+            if let (UnitKind::Module, Some(name)) = (unit.kind, &unit.name) {
+                modules.entry(name).or_insert((index, unit));
+            }
+        }
+    }
+    let dependencies: FxHashMap<&str, Vec<&str>> = modules
+        .iter()
+        .map(|(name, (_, unit))| {
+            let used = unit
+                .uses
+                .iter()
+                .map(|used| used.module.as_str())
+                .filter(|used| modules.contains_key(used))
+                .collect();
+            (*name, used)
+        })
+        .collect();
+
+    if enabled(Rule::UndefinedModule) {
+        for (index, file) in indexed.iter().enumerate() {
+            for unit in &file.index.units {
+                for used in unit.uses.iter().chain(&unit.ancestor) {
+                    if used.intrinsic
+                        || INTRINSIC_MODULES.contains(&used.module.as_str())
+                        || modules.contains_key(used.module.as_str())
+                    {
+                        continue;
+                    }
+                    let name = used.module.clone();
+                    diagnostics[index].push(Diagnostic::new(UndefinedModule { name }, used.range));
+                }
+            }
+        }
+    }
+
+    if enabled(Rule::CircularModuleDependency) {
+        for (name, (index, unit)) in &modules {
+            for used in &unit.uses {
+                let Some(path) = path_between(&dependencies, &used.module, name) else {
+                    continue;
+                };
+                let cycle = once(*name).chain(path).join(" -> ");
+                diagnostics[*index].push(Diagnostic::new(
+                    CircularModuleDependency { cycle },
+                    used.range,
+                ));
+            }
+        }
+    }
+
+    if enabled(Rule::UnusedPublicEntity) {
+        // Everything that each program unit can see through `use` statements
+        let units: Vec<(&ProgramUnit, FxHashSet<&str>)> = indexed
+            .iter()
+            .flat_map(|file| &file.index.units)
+            .map(|unit| {
+                let direct = unit.uses.iter().map(|used| used.module.as_str());
+                (unit, reachable(&dependencies, direct))
+            })
+            .collect();
+
+        for (name, (index, module)) in &modules {
+            for entity in &module.public {
+                let entity_name = entity.name.to_lowercase();
+                let used = units.iter().any(|(unit, visible)| {
+                    !extends(unit, name)
+                        && visible.contains(name)
+                        && unit.identifiers.contains(&entity_name)
+                });
+                if !used {
+                    diagnostics[*index].push(Diagnostic::new(
+                        UnusedPublicEntity {
+                            module: (*name).to_string(),
+                            name: entity.name.clone(),
+                        },
+                        entity.range,
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut messages = Vec::new();
+    for ((file, settings), diagnostics) in indexed.iter().zip(file_settings).zip(diagnostics) {
+        let rules = &settings.check.rules;
+        let ignore_allow_comments = settings.check.ignore_allow_comments.is_enabled();
+        let mut file_messages = diagnostics
+            .into_iter()
+            .filter(|diagnostic| rules.enabled(diagnostic.kind.rule()))
+            .filter(|diagnostic| {
+                ignore_allow_comments
+                    || !file.index.allowed.iter().any(|allowed| {
+                        allowed.rule == diagnostic.kind.rule()
+                            && allowed.range.contains_range(diagnostic.range)
+                    })
+            })
+            .map(|diagnostic| DiagnosticMessage::from_ruff(&file.file, diagnostic))
+            .collect();
+        apply_per_file_ignores(&mut file_messages, &file.path, settings);
+        messages.extend(file_messages);
+    }
+    messages.sort();
+    messages
+}
+
+/// Returns true if `unit` is `module` or one of its submodules.
+fn extends(unit: &ProgramUnit, module: &str) -> bool {
+    match unit.kind {
+        UnitKind::Module => unit.name.as_deref() == Some(module),
+        UnitKind::Submodule => unit
+            .ancestor
+            .as_ref()
+            .is_some_and(|ancestor| ancestor.module == module),
+        UnitKind::Other => false,
+    }
+}
+
+/// All modules reachable from `start`, including `start` itself.
+fn reachable<'a>(
+    dependencies: &FxHashMap<&'a str, Vec<&'a str>>,
+    start: impl Iterator<Item = &'a str>,
+) -> FxHashSet<&'a str> {
+    let mut seen = FxHashSet::default();
+    let mut queue: VecDeque<&str> = start.collect();
+    while let Some(module) = queue.pop_front() {
+        if seen.insert(module) {
+            queue.extend(dependencies.get(module).into_iter().flatten());
+        }
+    }
+    seen
+}
+
+/// The shortest chain of dependencies from `from` to `to`, including both ends.
+fn path_between<'a>(
+    dependencies: &FxHashMap<&'a str, Vec<&'a str>>,
+    from: &'a str,
+    to: &str,
+) -> Option<Vec<&'a str>> {
+    let mut previous: FxHashMap<&str, &str> = FxHashMap::default();
+    let mut queue = VecDeque::from([from]);
+    while let Some(module) = queue.pop_front() {
+        if module == to {
+            let mut path = vec![module];
+            while let Some(prev) = previous.get(path.last().unwrap()) {
+                path.push(*prev);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for next in dependencies.get(module).into_iter().flatten() {
+            if *next != from && !previous.contains_key(next) {
+                previous.insert(*next, module);
+                queue.push_back(*next);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use textwrap::dedent;
+
+    use ruff_source_file::SourceFileBuilder;
+    use tree_sitter::Parser;
+
+    use super::{check_project, FileIndex, IndexedFile};
+    use crate::rule_table::RuleTable;
+    use crate::rules::Rule;
+    use crate::settings::Settings;
+
+    fn index(path: &str, source: &str) -> IndexedFile {
+        let file = SourceFileBuilder::new(path, dedent(source)).finish();
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_fortran::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(file.source_text(), None).unwrap();
+        let index = FileIndex::new(&tree, &file);
+        IndexedFile::new(Path::new(path), file, index)
+    }
+
+    fn settings(rules: &[Rule]) -> Settings {
+        let mut settings = Settings::default();
+        settings.check.rules = RuleTable::from_iter(rules.iter().copied());
+        settings
+    }
+
+    fn check(sources: &[(&str, &str)], rules: &[Rule]) -> Vec<(String, Rule, String)> {
+        let settings = settings(rules);
+        check_with(sources, |_| &settings)
+    }
+
+    fn check_with<'a>(
+        sources: &[(&str, &str)],
+        settings: impl Fn(&Path) -> &'a Settings,
+    ) -> Vec<(String, Rule, String)> {
+        let indexed: Vec<_> = sources
+            .iter()
+            .map(|(path, source)| index(path, source))
+            .collect();
+        check_project(&indexed, settings)
+            .iter()
+            .map(|message| {
+                (
+                    message.filename().to_string(),
+                    message.rule().unwrap(),
+                    message.body().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn undefined_module() {
+        let messages = check(
+            &[
+                (
+                    "a.f90",
+                    "
+                    module a
+                      use, intrinsic :: iso_fortran_env, only: real64
+                      use iso_c_binding
+                      use b
+                      use missing
+                    end module a
+                    ",
+                ),
+                (
+                    "b.f90",
+                    "
+                    module b
+                    end module b
+
+                    submodule (other) b_impl
+                    end submodule b_impl
+                    ",
+                ),
+            ],
+            &[Rule::UndefinedModule],
+        );
+        assert_eq!(
+            messages,
+            [
+                (
+                    "a.f90".to_string(),
+                    Rule::UndefinedModule,
+                    "module 'missing' is not defined in any checked file".to_string()
+                ),
+                (
+                    "b.f90".to_string(),
+                    Rule::UndefinedModule,
+                    "module 'other' is not defined in any checked file".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn per_file_settings() {
+        let enabled = settings(&[Rule::UndefinedModule]);
+        let disabled = settings(&[]);
+        let messages = check_with(
+            &[
+                (
+                    "a.f90",
+                    "
+                    module a
+                      use b
+                      use missing_a
+                    end module a
+                    ",
+                ),
+                (
+                    "legacy/b.f90",
+                    "
+                    module b
+                      use missing_b
+                    end module b
+                    ",
+                ),
+            ],
+            |path| {
+                if path.starts_with("legacy") {
+                    &disabled
+                } else {
+                    &enabled
+                }
+            },
+        );
+        // Modules are still found in files that don't check project rules themselves
+        assert_eq!(
+            messages,
+            [(
+                "a.f90".to_string(),
+                Rule::UndefinedModule,
+                "module 'missing_a' is not defined in any checked file".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn circular_dependency() {
+        let messages = check(
+            &[
+                (
+                    "a.f90",
+                    "
+                    module a
+                      use b
+                    end module a
+                    ",
+                ),
+                (
+                    "b.f90",
+                    "
+                    module b
+                    contains
+                      subroutine foo()
+                        use c
+                      end subroutine foo
+                    end module b
+
+                    module c
+                      use a
+                    end module c
+
+                    ! Submodules can depend on their ancestor's dependents
+                    submodule (a) a_impl
+                      use c
+                    end submodule a_impl
+                    ",
+                ),
+            ],
+            &[Rule::CircularModuleDependency],
+        );
+        let bodies: Vec<_> = messages.iter().map(|(_, _, body)| body.as_str()).collect();
+        assert_eq!(
+            bodies,
+            [
+                "circular module dependency: a -> b -> c -> a",
+                "circular module dependency: b -> c -> a -> b",
+                "circular module dependency: c -> a -> b -> c",
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_public_entity() {
+        let messages = check(
+            &[
+                (
+                    "lib.f90",
+                    "
+                    module lib
+                      implicit none
+                      private
+                      public :: used, reexported, unused, internal
+                      integer, parameter :: used = 1, reexported = 2, unused = 3
+                      integer :: hidden
+                    contains
+                      subroutine internal()
+                        print *, hidden
+                      end subroutine internal
+                    end module lib
+
+                    submodule (lib) lib_impl
+                    contains
+                      subroutine other()
+                        call internal()
+                      end subroutine other
+                    end submodule lib_impl
+
+                    module wrapper
+                      use lib
+                    end module wrapper
+                    ",
+                ),
+                (
+                    "main.f90",
+                    "
+                    program main
+                      use lib, only: used
+                      use wrapper
+                      print *, used + reexported
+                    end program main
+                    ",
+                ),
+            ],
+            &[Rule::UnusedPublicEntity],
+        );
+        let bodies: Vec<_> = messages.iter().map(|(_, _, body)| body.as_str()).collect();
+        assert_eq!(
+            bodies,
+            [
+                "public entity 'unused' of module 'lib' is never used outside of it",
+                "public entity 'internal' of module 'lib' is never used outside of it",
+            ]
+        );
+        assert!(messages
+            .iter()
+            .all(|(_, rule, _)| *rule == Rule::UnusedPublicEntity));
+    }
+}
@@ -0,0 +1,158 @@
+//! Resolve the settings that apply to each file from the configuration files in the
+//! directories above it.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use log::debug;
+use rustc_hash::FxHashSet;
+
+use crate::check::{
+    ast_entrypoint_map, resolve_settings, rules_to_path_rules, rules_to_text_rules,
+};
+use crate::cli::{CheckArgs, GlobalConfigArgs};
+use crate::configuration::{config_file_path, load_options, settings_toml, Configuration};
+use crate::fs;
+use crate::rules::{AstRuleEnum, PathRuleEnum, TextRuleEnum};
+use crate::settings::Settings;
+
+/// The settings from one configuration file, along with the rules they enable.
+pub(crate) struct ResolvedSettings {
+    pub settings: Settings,
+    /// The configuration file the settings were read from, if any.
+    pub config_file: Option<PathBuf>,
+    pub path_rules: Vec<PathRuleEnum>,
+    pub text_rules: Vec<TextRuleEnum>,
+    pub ast_entrypoints: BTreeMap<&'static str, Vec<AstRuleEnum>>,
+}
+
+impl ResolvedSettings {
+    fn new(settings: Settings, config_file: Option<PathBuf>) -> Self {
+        let rules = &settings.check.rules;
+        Self {
+            path_rules: rules_to_path_rules(rules),
+            text_rules: rules_to_text_rules(rules),
+            ast_entrypoints: ast_entrypoint_map(rules),
+            config_file,
+            settings,
+        }
+    }
+}
+
+/// Maps each file to the settings from the nearest configuration file above it.
+///
+/// The configuration file for the working directory, or the one given with
+/// `--config-file`, determines the project root, and settings that apply to the run as
+/// a whole, such as which files are checked and how violations are reported.
+/// Configuration files in subdirectories of the project root then override the rules
+/// and rule options for the files beneath them. Command line arguments take priority
+/// over all configuration files.
+pub(crate) struct Resolver {
+    root: ResolvedSettings,
+    /// Settings from configuration files in subdirectories of the project root, keyed by
+    /// the directory containing them.
+    nested: BTreeMap<PathBuf, ResolvedSettings>,
+    /// Directories that have already been searched for configuration files.
+    searched: FxHashSet<PathBuf>,
+    /// Whether to look for configuration files in subdirectories. This is disabled by
+    /// `--config-file`, so that only the given file is used.
+    hierarchical: bool,
+    args: CheckArgs,
+}
+
+impl Resolver {
+    pub(crate) fn new(args: &CheckArgs, global_options: &GlobalConfigArgs) -> Result<Self> {
+        let settings = resolve_settings(args, global_options)?;
+        let config_file = config_file_path(&global_options.config_file)?;
+        Ok(Self::from_settings(
+            settings,
+            config_file,
+            global_options.config_file.is_none(),
+            args,
+        ))
+    }
+
+    /// Create a resolver from settings that have already been read for the project root.
+    pub(crate) fn from_settings(
+        settings: Settings,
+        config_file: Option<PathBuf>,
+        hierarchical: bool,
+        args: &CheckArgs,
+    ) -> Self {
+        Self {
+            root: ResolvedSettings::new(settings, config_file),
+            nested: BTreeMap::new(),
+            searched: FxHashSet::default(),
+            hierarchical,
+            args: args.clone(),
+        }
+    }
+
+    /// The settings for the project as a whole.
+    pub(crate) fn root(&self) -> &ResolvedSettings {
+        &self.root
+    }
+
+    /// Find and read any configuration files in subdirectories of the project root that
+    /// apply to `files`.
+    pub(crate) fn discover<'a>(
+        &mut self,
+        files: impl IntoIterator<Item = &'a PathBuf>,
+    ) -> Result<()> {
+        if !self.hierarchical {
+            return Ok(());
+        }
+
+        let project_root = self.root.settings.check.project_root.clone();
+        for file in files {
+            let file = fs::normalize_path(file);
+            for directory in file.ancestors().skip(1) {
+                if directory == project_root || !directory.starts_with(&project_root) {
+                    break;
+                }
+                // Any ancestors were searched at the same time
+                if !self.searched.insert(directory.to_path_buf()) {
+                    break;
+                }
+                if let Some(config_file) = settings_toml(directory)? {
+                    debug!("Found nested configuration file {}", config_file.display());
+                    let settings =
+                        Configuration::from_options(load_options(&config_file)?, directory)
+                            .into_settings(directory, &self.args)?;
+                    self.nested.insert(
+                        directory.to_path_buf(),
+                        ResolvedSettings::new(settings, Some(config_file)),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The settings that apply to `path`, from the nearest configuration file above it.
+    pub(crate) fn resolve(&self, path: &Path) -> &ResolvedSettings {
+        if self.nested.is_empty() {
+            return &self.root;
+        }
+        fs::normalize_path(path)
+            .ancestors()
+            .skip(1)
+            .find_map(|directory| self.nested.get(directory))
+            .unwrap_or(&self.root)
+    }
+
+    /// The configuration files in use.
+    pub(crate) fn config_files(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(&self.root)
+            .chain(self.nested.values())
+            .filter_map(|resolved| resolved.config_file.as_deref())
+    }
+
+    /// All of the settings in use, starting with the project root's.
+    pub(crate) fn settings(&self) -> impl Iterator<Item = &Settings> {
+        std::iter::once(&self.root)
+            .chain(self.nested.values())
+            .map(|resolved| &resolved.settings)
+    }
+}
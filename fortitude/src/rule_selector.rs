@@ -9,6 +9,8 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use globset::{Glob, GlobMatcher};
+use lazy_regex::regex;
+use ruff_cache::{CacheKey, CacheKeyHasher};
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
@@ -18,7 +20,7 @@ use crate::registry::{Category, Rule, RuleNamespace, RuleSet};
 use crate::rule_redirects::{get_deprecated_category, get_redirect};
 use crate::rules::{RuleCodePrefix, RuleGroup, RuleIter};
 use crate::settings::{PatternPrefixPair, PreviewMode};
-use crate::{display_settings, fs};
+use crate::{display_settings, fs, warn_user_once_by_message};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum RuleSelector {
@@ -42,6 +44,8 @@ pub enum RuleSelector {
         redirected_to: Vec<RuleCodePrefix>,
         redirected_from: String,
     },
+    /// Select custom rules from the config file by code, code prefix, or name.
+    Custom(String),
 }
 
 impl From<Category> for RuleSelector {
@@ -94,8 +98,9 @@ impl FromStr for RuleSelector {
                     });
                 }
 
-                let (category, code) =
-                    Category::parse_code(s).ok_or_else(|| ParseError::Unknown(s.to_string()))?;
+                let Some((category, code)) = Category::parse_code(s) else {
+                    return Self::parse_custom(s);
+                };
 
                 if code.is_empty() {
                     return Ok(Self::Category(category));
@@ -157,10 +162,29 @@ impl RuleSelector {
             RuleSelector::DeprecatedCategory {
                 redirected_from, ..
             } => (redirected_from.to_string(), "".to_string()),
+            RuleSelector::Custom(code) => ("".to_string(), code.to_string()),
+        }
+    }
+}
+
+impl RuleSelector {
+    /// Anything that isn't a built-in selector may refer to custom rules, which are
+    /// only checked once the config file has been read
+    fn parse_custom(s: &str) -> Result<Self, ParseError> {
+        if is_custom_rule_identifier(s) {
+            Ok(Self::Custom(s.to_string()))
+        } else {
+            Err(ParseError::Unknown(s.to_string()))
         }
     }
 }
 
+/// Returns `true` if `s` could be the code or name of a custom rule. These need to be
+/// usable in allow comments.
+pub(crate) fn is_custom_rule_identifier(s: &str) -> bool {
+    regex!(r"^\w[-\w]*$").is_match(s)
+}
+
 impl Serialize for RuleSelector {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -220,6 +244,7 @@ impl RuleSelector {
                     .collect::<Vec<_>>()
                     .into_iter(),
             ),
+            RuleSelector::Custom(_) => RuleSelectorIter::Vec(vec![].into_iter()),
         }
     }
 
@@ -281,7 +306,7 @@ impl RuleSelector {
         match self {
             RuleSelector::All => Specificity::All,
             RuleSelector::Category(..) => Specificity::Category,
-            RuleSelector::Rule { .. } => Specificity::Rule,
+            RuleSelector::Rule { .. } | RuleSelector::Custom(_) => Specificity::Rule,
             RuleSelector::Prefix { prefix, .. } => {
                 let prefix: &'static str = prefix.short_code();
                 match prefix.len() {
@@ -318,8 +343,9 @@ impl RuleSelector {
         match s {
             "ALL" => Ok(Self::All),
             _ => {
-                let (category, code) =
-                    Category::parse_code(s).ok_or_else(|| ParseError::Unknown(s.to_string()))?;
+                let Some((category, code)) = Category::parse_code(s) else {
+                    return Self::parse_custom(s);
+                };
 
                 if code.is_empty() {
                     return Ok(Self::Category(category));
@@ -467,6 +493,13 @@ impl PerFileIgnore {
         // Rules in preview are included here even if preview mode is disabled; it's
         // safe to ignore disabled rules
         let rules: RuleSet = prefixes.iter().flat_map(RuleSelector::all_rules).collect();
+        for prefix in prefixes {
+            if let RuleSelector::Custom(code) = prefix {
+                warn_user_once_by_message!(
+                    "Custom rules can't be ignored with `per-file-ignores`, so `{code}` will have no effect."
+                );
+            }
+        }
         let negated = pattern.starts_with('!');
         if negated {
             pattern.drain(..1);
@@ -509,6 +542,15 @@ pub struct CompiledPerFileIgnore {
     pub rules: RuleSet,
 }
 
+impl CacheKey for CompiledPerFileIgnore {
+    fn cache_key(&self, state: &mut CacheKeyHasher) {
+        self.absolute_matcher.glob().glob().cache_key(state);
+        self.basename_matcher.glob().glob().cache_key(state);
+        self.negated.cache_key(state);
+        self.rules.cache_key(state);
+    }
+}
+
 impl Display for CompiledPerFileIgnore {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         display_settings! {
@@ -555,6 +597,12 @@ impl CompiledPerFileIgnoreList {
     }
 }
 
+impl CacheKey for CompiledPerFileIgnoreList {
+    fn cache_key(&self, state: &mut CacheKeyHasher) {
+        self.ignores.cache_key(state);
+    }
+}
+
 impl Display for CompiledPerFileIgnoreList {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         if self.ignores.is_empty() {
@@ -1,6 +1,7 @@
 use crate::ast::FortitudeNode;
+use crate::semantic::SemanticModel;
 use crate::settings::Settings;
-use crate::{AstRule, FromAstNode};
+use crate::{FromAstNode, SemanticAstRule};
 use ruff_diagnostics::{Diagnostic, Violation};
 use ruff_macros::{derive_message_formats, ViolationMetadata};
 use ruff_source_file::SourceFile;
@@ -78,28 +79,34 @@ impl Violation for InitialisationInDeclaration {
     }
 }
 
-impl AstRule for InitialisationInDeclaration {
-    fn check(_settings: &Settings, node: &Node, src: &SourceFile) -> Option<Vec<Diagnostic>> {
+impl SemanticAstRule for InitialisationInDeclaration {
+    fn check(
+        _settings: &Settings,
+        node: &Node,
+        src: &SourceFile,
+        semantic: &SemanticModel,
+    ) -> Option<Vec<Diagnostic>> {
         let src = src.source_text();
+        let scope = semantic.enclosing_scope(node);
         // Only check in procedures
-        node.ancestors().find(|parent| {
-            ["function", "subroutine", "module_procedure"].contains(&parent.kind())
-        })?;
+        if !semantic
+            .ancestors(scope)
+            .any(|scope| scope.kind().is_procedure())
+        {
+            return None;
+        }
 
-        let declaration = node
-            .ancestors()
-            .find(|parent| parent.kind() == "variable_declaration")?;
+        let left = node.child_by_field_name("left")?;
+        let identifier = left.child_with_name("identifier").unwrap_or(left);
+        // Components of derived types aren't symbols in the scope
+        let symbol = semantic.scope(scope).symbol(identifier.to_text(src)?)?;
 
         // Init in declaration ok for save and parameter
-        if declaration
-            .children_by_field_name("attribute", &mut declaration.walk())
-            .filter_map(|attr| attr.to_text(src))
-            .any(|attr_name| ["save", "parameter"].contains(&attr_name.to_lowercase().as_str()))
-        {
+        if symbol.has_attribute("save") || symbol.has_attribute("parameter") {
             return None;
         }
 
-        let name = node.child_by_field_name("left")?.to_text(src)?.to_string();
+        let name = left.to_text(src)?.to_string();
         some_vec![Diagnostic::from_node(Self { name }, node)]
     }
 
@@ -203,36 +210,35 @@ impl Violation for PointerInitialisationInDeclaration {
     }
 }
 
-impl AstRule for PointerInitialisationInDeclaration {
-    fn check(_settings: &Settings, node: &Node, src: &SourceFile) -> Option<Vec<Diagnostic>> {
+impl SemanticAstRule for PointerInitialisationInDeclaration {
+    fn check(
+        _settings: &Settings,
+        node: &Node,
+        src: &SourceFile,
+        semantic: &SemanticModel,
+    ) -> Option<Vec<Diagnostic>> {
         let src = src.source_text();
+        let scope = semantic.enclosing_scope(node);
         // Only check in procedures
-        node.ancestors().find(|parent| {
-            ["function", "subroutine", "module_procedure"].contains(&parent.kind())
-        })?;
-
-        let declaration = node
-            .ancestors()
-            .find(|parent| parent.kind() == "variable_declaration")?;
-
-        // Init in declaration ok for save (in this rule)
-        if declaration
-            .children_by_field_name("attribute", &mut declaration.walk())
-            .filter_map(|attr| attr.to_text(src))
-            .any(|attr_name| ["save"].contains(&attr_name.to_lowercase().as_str()))
+        if !semantic
+            .ancestors(scope)
+            .any(|scope| scope.kind().is_procedure())
         {
             return None;
         }
 
         let var = node.child_by_field_name("left")?;
-
         // Array syntax on the variable name
-        if let Some(arr) = var.child_with_name("identifier") {
-            let name = arr.to_text(src)?.to_string();
-            return some_vec![Diagnostic::from_node(Self { name }, node)];
+        let identifier = var.child_with_name("identifier").unwrap_or(var);
+        // Components of derived types aren't symbols in the scope
+        let symbol = semantic.scope(scope).symbol(identifier.to_text(src)?)?;
+
+        // Init in declaration ok for save (in this rule)
+        if symbol.has_attribute("save") {
+            return None;
         }
 
-        let name = var.to_text(src)?.to_string();
+        let name = identifier.to_text(src)?.to_string();
         some_vec![Diagnostic::from_node(Self { name }, node)]
     }
 
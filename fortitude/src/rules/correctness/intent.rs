@@ -1,6 +1,6 @@
-use crate::ast::FortitudeNode;
+use crate::semantic::{SemanticModel, SymbolKind};
 use crate::settings::Settings;
-use crate::{AstRule, FromAstNode};
+use crate::SemanticAstRule;
 use ruff_diagnostics::{Diagnostic, Violation};
 use ruff_macros::{derive_message_formats, ViolationMetadata};
 use ruff_source_file::SourceFile;
@@ -44,77 +44,32 @@ impl Violation for MissingIntent {
     }
 }
 
-impl AstRule for MissingIntent {
-    fn check(_settings: &Settings, node: &Node, src: &SourceFile) -> Option<Vec<Diagnostic>> {
-        let src = src.source_text();
-        // Names of all the dummy arguments
-        let parameters: Vec<&str> = node
-            .child_by_field_name("parameters")?
-            .named_children(&mut node.walk())
-            .filter_map(|param| param.to_text(src))
-            .collect();
-
-        let parent = node.parent()?;
-        let entity = parent.kind().to_string();
+impl SemanticAstRule for MissingIntent {
+    fn check(
+        _settings: &Settings,
+        node: &Node,
+        _src: &SourceFile,
+        semantic: &SemanticModel,
+    ) -> Option<Vec<Diagnostic>> {
+        let scope = semantic.scope(semantic.scope_of(&node.parent()?)?);
+        let entity = scope.kind().to_string();
 
-        // Logic here is:
-        // 1. find variable declarations
-        // 2. ignore `procedure` arguments
-        // 3. filter to the declarations that don't have an `intent` or `value` attribute
-        // 4. filter to the ones that contain any of the dummy arguments
-        // 5. collect into a vec of violations
-        //
-        // We filter by missing intent first, so we only have to
-        // filter by the dummy args once -- otherwise we either catch
-        // local var decls on the same line, or need to iterate over
-        // the decl names twice
-        let violations = parent
-            .named_children(&mut parent.walk())
-            .filter(|child| child.kind() == "variable_declaration")
-            .filter(|decl| {
-                if let Some(type_) = decl.child_by_field_name("type") {
-                    type_.kind() != "procedure"
-                } else {
-                    false
-                }
-            })
-            .filter(|decl| {
-                !decl
-                    .children_by_field_name("attribute", &mut decl.walk())
-                    .any(|attr| {
-                        let attr = attr.to_text(src).unwrap_or("").to_lowercase();
-                        attr.starts_with("intent") || attr.starts_with("value")
-                    })
-            })
-            .flat_map(|decl| {
-                decl.children_by_field_name("declarator", &mut decl.walk())
-                    .filter_map(|declarator| {
-                        let identifier = match declarator.kind() {
-                            "identifier" => Some(declarator),
-                            "sized_declarator" => declarator.child_with_name("identifier"),
-                            // Although tree-sitter-fortran grammar allows
-                            // `init_declarator` and `pointer_init_declarator`
-                            // here, dummy arguments aren't actually allow
-                            // initialisers. _Could_ still catch them here, and
-                            // flag as syntax error elsewhere?
-                            _ => None,
-                        }?;
-                        let name = identifier.to_text(src)?;
-                        if parameters.contains(&name) {
-                            return Some((declarator, name));
-                        }
-                        None
-                    })
-                    .map(|(dummy, name)| {
-                        Diagnostic::from_node(
-                            Self {
-                                entity: entity.to_string(),
-                                name: name.to_string(),
-                            },
-                            &dummy,
-                        )
-                    })
-                    .collect::<Vec<Diagnostic>>()
+        // Procedure arguments can't have `intent`, and `value` arguments don't need
+        // it. Dummy arguments without a declaration are caught by the implicit typing
+        // rules instead.
+        let violations = scope
+            .dummy_arguments()
+            .filter(|arg| arg.kind() == SymbolKind::DummyArgument)
+            .filter(|arg| arg.declaration().is_some())
+            .filter(|arg| arg.intent().is_none() && !arg.has_attribute("value"))
+            .map(|arg| {
+                Diagnostic::new(
+                    Self {
+                        entity: entity.clone(),
+                        name: arg.name().to_string(),
+                    },
+                    arg.range(),
+                )
             })
             .collect();
 
@@ -1,11 +1,13 @@
 use crate::ast::FortitudeNode;
 use crate::rules::utilities::literal_as_io_unit;
+use crate::semantic::{Binding, SemanticModel, SymbolKind};
 use crate::settings::Settings;
-use crate::{AstRule, FromAstNode};
+use crate::{AstRule, FromAstNode, SemanticAstRule};
 use itertools::Itertools;
 use ruff_diagnostics::{Diagnostic, Violation};
 use ruff_macros::{derive_message_formats, ViolationMetadata};
 use ruff_source_file::SourceFile;
+use settings::MagicNumberContext;
 use tree_sitter::Node;
 
 /// ## What it does
@@ -19,8 +21,6 @@ use tree_sitter::Node;
 ///
 /// The values `0, 1, 2, 3, 4` are ignored by default.
 ///
-/// TODO: Add user settings
-///
 /// ## Examples
 /// Instead of:
 /// ```f90
@@ -31,9 +31,12 @@ use tree_sitter::Node;
 /// integer, parameter :: NUM_SPLINE_POINTS = 10
 /// integer, dimension(NUM_SPLINE_POINTS) :: x, y
 /// ```
+///
+/// ## Options
+/// - `check.magic-numbers.allowed-values`
 #[derive(ViolationMetadata)]
 pub(crate) struct MagicNumberInArraySize {
-    value: i32,
+    value: String,
 }
 
 impl Violation for MagicNumberInArraySize {
@@ -44,10 +47,8 @@ impl Violation for MagicNumberInArraySize {
     }
 }
 
-const DEFAULT_ALLOWED_LITERALS: &[i32] = &[0, 1, 2, 3, 4];
-
 impl AstRule for MagicNumberInArraySize {
-    fn check(_settings: &Settings, node: &Node, source: &SourceFile) -> Option<Vec<Diagnostic>> {
+    fn check(settings: &Settings, node: &Node, source: &SourceFile) -> Option<Vec<Diagnostic>> {
         // We're either looking for `type, dimension(X) :: variable` or `type :: variable(X)`
         let size = if node.kind() == "type_qualifier" {
             if node.child(0)?.to_text(source.source_text())?.to_lowercase() != "dimension" {
@@ -74,17 +75,7 @@ impl AstRule for MagicNumberInArraySize {
                 _ => None,
             })
             .flatten()
-            .filter_map(|literal| {
-                let value = literal
-                    .to_text(source.source_text())?
-                    .parse::<i32>()
-                    .unwrap();
-                if DEFAULT_ALLOWED_LITERALS.contains(&value) {
-                    None
-                } else {
-                    Some((literal, value))
-                }
-            })
+            .filter_map(|literal| magic_number(&literal, source, settings))
             .map(|(child, value)| Diagnostic::from_node(Self { value }, &child))
             .collect();
 
@@ -158,3 +149,383 @@ impl AstRule for MagicIoUnit {
         ]
     }
 }
+
+/// ## What it does
+/// Checks for literal numbers in expressions in executable statements, such as
+/// assignments, arithmetic, and arguments to procedures.
+///
+/// ## Why is this bad?
+/// Unnamed numbers in the middle of a calculation don't explain what they're for,
+/// and if the same value is used in several places, it's easy to change some of
+/// them but not others. Prefer named constants, which can be documented and
+/// changed in one place.
+///
+/// The values `0, 1, 2, 3, 4` are ignored by default, as are numbers used as
+/// `stop` codes. Numbers in array subscripts and loop bounds can also be ignored.
+///
+/// Numbers in comparisons and `allocate` statements are checked by
+/// [`magic-number-in-comparison`](magic-number-in-comparison.md) and
+/// [`magic-number-in-allocate-shape`](magic-number-in-allocate-shape.md) instead.
+///
+/// ## Examples
+/// Instead of:
+/// ```f90
+/// energy = 0.5 * mass * velocity**2
+/// force = mass * 9.81
+/// ```
+/// prefer:
+/// ```f90
+/// real(dp), parameter :: STANDARD_GRAVITY = 9.81_dp
+/// ...
+/// force = mass * STANDARD_GRAVITY
+/// ```
+///
+/// ## Options
+/// - `check.magic-numbers.allowed-values`
+/// - `check.magic-numbers.allowed-contexts`
+#[derive(ViolationMetadata)]
+pub(crate) struct MagicNumberInExpression {
+    value: String,
+}
+
+impl Violation for MagicNumberInExpression {
+    #[derive_message_formats]
+    fn message(&self) -> String {
+        let Self { value } = self;
+        format!("Magic number in expression, consider replacing {value} with named `parameter`")
+    }
+}
+
+impl SemanticAstRule for MagicNumberInExpression {
+    fn check(
+        settings: &Settings,
+        node: &Node,
+        source: &SourceFile,
+        semantic: &SemanticModel,
+    ) -> Option<Vec<Diagnostic>> {
+        // Comparisons are checked by `magic-number-in-comparison`
+        if outermost_expression(node).parent()?.kind() == "relational_expression" {
+            return None;
+        }
+
+        for ancestor in node.ancestors() {
+            match ancestor.kind() {
+                // Declarations are checked by `magic-number-in-array-size`, and the
+                // values of `parameter`s are the named constants we'd like to see.
+                // Numbers in `allocate` statements are checked by
+                // `magic-number-in-allocate-shape`
+                "variable_declaration"
+                | "derived_type_definition"
+                | "parameter_statement"
+                | "data_statement"
+                | "enumerator_statement"
+                | "format_statement"
+                | "case_statement"
+                | "allocate_statement" => return None,
+                // Units are checked by `magic-io-unit`
+                "read_statement" | "write_statement" | "open_statement" | "close_statement" => {
+                    if literal_as_io_unit(&ancestor, source) == Some(*node) {
+                        return None;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let allowed_contexts = &settings.check.magic_numbers.allowed_contexts;
+        if allowed_contexts
+            .iter()
+            .any(|context| in_context(node, *context, source, semantic))
+        {
+            return None;
+        }
+
+        let (literal, value) = magic_number(node, source, settings)?;
+        some_vec!(Diagnostic::from_node(Self { value }, &literal))
+    }
+
+    fn entrypoints() -> Vec<&'static str> {
+        vec!["number_literal"]
+    }
+}
+
+/// ## What it does
+/// Checks for literal numbers in comparisons.
+///
+/// ## Why is this bad?
+/// Comparing against an unnamed number hides the meaning of the test, such as
+/// whether it's checking for a particular status, a limit, or a tolerance. Prefer
+/// named constants, which make the intent clear and keep related tests in step
+/// with each other.
+///
+/// The values `0, 1, 2, 3, 4` are ignored by default.
+///
+/// ## Examples
+/// Instead of:
+/// ```f90
+/// if (status == 17) then
+/// ```
+/// prefer:
+/// ```f90
+/// integer, parameter :: STATUS_CONVERGED = 17
+/// ...
+/// if (status == STATUS_CONVERGED) then
+/// ```
+///
+/// ## Options
+/// - `check.magic-numbers.allowed-values`
+#[derive(ViolationMetadata)]
+pub(crate) struct MagicNumberInComparison {
+    value: String,
+}
+
+impl Violation for MagicNumberInComparison {
+    #[derive_message_formats]
+    fn message(&self) -> String {
+        let Self { value } = self;
+        format!("Magic number in comparison, consider replacing {value} with named `parameter`")
+    }
+}
+
+impl AstRule for MagicNumberInComparison {
+    fn check(settings: &Settings, node: &Node, source: &SourceFile) -> Option<Vec<Diagnostic>> {
+        let violations: Vec<_> = [node.child(0), node.child(2)]
+            .into_iter()
+            .flatten()
+            .filter_map(|operand| innermost_literal(&operand))
+            .filter_map(|literal| magic_number(&literal, source, settings))
+            .map(|(literal, value)| Diagnostic::from_node(Self { value }, &literal))
+            .collect();
+
+        Some(violations)
+    }
+
+    fn entrypoints() -> Vec<&'static str> {
+        vec!["relational_expression"]
+    }
+}
+
+/// ## What it does
+/// Checks for literal numbers in the shapes of arrays in `allocate` statements.
+///
+/// ## Why is this bad?
+/// As with [`magic-number-in-array-size`](magic-number-in-array-size.md), arrays
+/// that are allocated with the same literal size in different places can easily
+/// drift apart, and the size on its own doesn't say what the array holds. Prefer
+/// named constants or variables.
+///
+/// The values `0, 1, 2, 3, 4` are ignored by default.
+///
+/// ## Examples
+/// Instead of:
+/// ```f90
+/// allocate(x(100), y(100))
+/// ```
+/// prefer:
+/// ```f90
+/// integer, parameter :: NUM_SAMPLES = 100
+/// ...
+/// allocate(x(NUM_SAMPLES), y(NUM_SAMPLES))
+/// ```
+///
+/// ## Options
+/// - `check.magic-numbers.allowed-values`
+#[derive(ViolationMetadata)]
+pub(crate) struct MagicNumberInAllocateShape {
+    value: String,
+}
+
+impl Violation for MagicNumberInAllocateShape {
+    #[derive_message_formats]
+    fn message(&self) -> String {
+        let Self { value } = self;
+        format!(
+            "Magic number in `allocate` shape, consider replacing {value} with named `parameter`"
+        )
+    }
+}
+
+impl AstRule for MagicNumberInAllocateShape {
+    fn check(settings: &Settings, node: &Node, source: &SourceFile) -> Option<Vec<Diagnostic>> {
+        // Skip `stat=`, `source=`, and so on
+        let violations: Vec<_> = node
+            .named_descendants_except(["keyword_argument"])
+            .filter(|child| child.kind() == "number_literal")
+            .filter_map(|literal| magic_number(&literal, source, settings))
+            .map(|(literal, value)| Diagnostic::from_node(Self { value }, &literal))
+            .collect();
+
+        Some(violations)
+    }
+
+    fn entrypoints() -> Vec<&'static str> {
+        vec!["allocate_statement"]
+    }
+}
+
+/// Returns the literal and its text if it isn't one of the allowed values. Real
+/// literals are allowed if they're equal to an allowed integer, such as `2.0`.
+fn magic_number<'a>(
+    literal: &Node<'a>,
+    source: &SourceFile,
+    settings: &Settings,
+) -> Option<(Node<'a>, String)> {
+    let text = literal.to_text(source.source_text())?;
+    // Ignore any kind suffix, such as `_dp`
+    let number = text.split('_').next()?.to_lowercase();
+
+    let value = match number.parse::<i64>() {
+        Ok(value) => Some(value),
+        Err(_) => number
+            .replace(['d', 'q'], "e")
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.fract() == 0.0)
+            .map(|value| value as i64),
+    };
+
+    let allowed_values = &settings.check.magic_numbers.allowed_values;
+    if value.is_some_and(|value| allowed_values.contains(&value)) {
+        return None;
+    }
+    Some((*literal, text.to_string()))
+}
+
+/// Returns the largest expression that `node` is the value of, skipping over any
+/// parentheses and signs
+fn outermost_expression<'a>(node: &Node<'a>) -> Node<'a> {
+    let mut expression = *node;
+    while let Some(parent) = expression.parent() {
+        if !matches!(
+            parent.kind(),
+            "unary_expression" | "parenthesized_expression"
+        ) {
+            break;
+        }
+        expression = parent;
+    }
+    expression
+}
+
+/// Returns the number literal inside `node`, skipping over any parentheses and signs
+fn innermost_literal<'a>(node: &Node<'a>) -> Option<Node<'a>> {
+    match node.kind() {
+        "number_literal" => Some(*node),
+        "unary_expression" | "parenthesized_expression" => {
+            innermost_literal(&node.named_children(&mut node.walk()).last()?)
+        }
+        _ => None,
+    }
+}
+
+fn in_context(
+    node: &Node,
+    context: MagicNumberContext,
+    source: &SourceFile,
+    semantic: &SemanticModel,
+) -> bool {
+    match context {
+        MagicNumberContext::ArrayBounds => is_array_subscript(node, source, semantic),
+        MagicNumberContext::LoopBounds => node
+            .ancestors()
+            .any(|ancestor| ancestor.kind() == "loop_control_expression"),
+        MagicNumberContext::StopCodes => node
+            .ancestors()
+            .any(|ancestor| ancestor.kind() == "stop_statement"),
+    }
+}
+
+/// Returns true if `node` is part of a subscript or section of an array, such as
+/// `x(10)` or `x(2:n - 1)`. Array references and function calls look the same, so
+/// this checks that the name refers to a variable.
+fn is_array_subscript(node: &Node, source: &SourceFile, semantic: &SemanticModel) -> bool {
+    let Some(arguments) = node
+        .ancestors()
+        .find(|ancestor| ancestor.kind() == "argument_list")
+    else {
+        return false;
+    };
+    let Some(name) = arguments
+        .parent()
+        .filter(|parent| parent.kind() == "call_expression")
+        .and_then(|call| call.named_child(0))
+        .filter(|name| name.kind() == "identifier")
+        .and_then(|name| name.to_text(source.source_text()))
+    else {
+        return false;
+    };
+
+    let scope = semantic.enclosing_scope(node);
+    let symbol = match semantic.resolve(scope, name) {
+        Some(Binding::Local(symbol) | Binding::Host(_, symbol)) => symbol,
+        _ => return false,
+    };
+    matches!(
+        symbol.kind(),
+        SymbolKind::Variable
+            | SymbolKind::NamedConstant
+            | SymbolKind::DummyArgument
+            | SymbolKind::FunctionResult
+    )
+}
+
+pub(crate) mod settings {
+    use crate::display_settings;
+    use ruff_macros::CacheKey;
+    use serde::{Deserialize, Serialize};
+    use std::fmt::{Display, Formatter};
+
+    /// Places where any number is allowed by `magic-number-in-expression`
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, CacheKey)]
+    #[serde(deny_unknown_fields, rename_all = "kebab-case")]
+    pub enum MagicNumberContext {
+        /// Subscripts and sections of arrays, such as `x(10)`.
+        ArrayBounds,
+        /// The bounds and step of `do` loops.
+        LoopBounds,
+        /// The codes of `stop` and `error stop` statements.
+        StopCodes,
+    }
+
+    impl Display for MagicNumberContext {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::ArrayBounds => write!(f, "array-bounds"),
+                Self::LoopBounds => write!(f, "loop-bounds"),
+                Self::StopCodes => write!(f, "stop-codes"),
+            }
+        }
+    }
+
+    pub const DEFAULT_ALLOWED_VALUES: &[i64] = &[0, 1, 2, 3, 4];
+    pub const DEFAULT_ALLOWED_CONTEXTS: &[MagicNumberContext] = &[MagicNumberContext::StopCodes];
+
+    #[derive(Debug, Clone, CacheKey)]
+    pub struct Settings {
+        pub allowed_values: Vec<i64>,
+        pub allowed_contexts: Vec<MagicNumberContext>,
+    }
+
+    impl Default for Settings {
+        fn default() -> Self {
+            Self {
+                allowed_values: DEFAULT_ALLOWED_VALUES.to_vec(),
+                allowed_contexts: DEFAULT_ALLOWED_CONTEXTS.to_vec(),
+            }
+        }
+    }
+
+    impl Display for Settings {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            display_settings! {
+                formatter = f,
+                namespace = "check.magic_numbers",
+                fields = [
+                    self.allowed_values | array,
+                    self.allowed_contexts | array,
+                ]
+            }
+            Ok(())
+        }
+    }
+}
@@ -11,6 +11,7 @@ pub mod intent;
 pub mod kind_suffixes;
 pub mod magic_numbers;
 pub mod missing_io_specifier;
+pub mod modules;
 pub mod nonportable_shortcircuit_inquiry;
 pub mod select_default;
 pub mod split_escaped_quote;
@@ -33,6 +34,7 @@ mod tests {
     use crate::apply_common_filters;
     use crate::registry::Rule;
     use crate::rules::correctness::exit_labels;
+    use crate::rules::correctness::magic_numbers::{self, settings::MagicNumberContext};
     use crate::settings::{CheckSettings, Settings};
     use crate::test::test_path;
 
@@ -44,6 +46,9 @@ mod tests {
     #[test_case(Rule::ImplicitRealKind, Path::new("C022.f90"))]
     #[test_case(Rule::MagicNumberInArraySize, Path::new("C031.f90"))]
     #[test_case(Rule::MagicIoUnit, Path::new("C032.f90"))]
+    #[test_case(Rule::MagicNumberInExpression, Path::new("C033.f90"))]
+    #[test_case(Rule::MagicNumberInComparison, Path::new("C034.f90"))]
+    #[test_case(Rule::MagicNumberInAllocateShape, Path::new("C035.f90"))]
     #[test_case(Rule::MissingActionSpecifier, Path::new("C041.f90"))]
     #[test_case(Rule::TrailingBackslash, Path::new("C051.F90"))]
     #[test_case(Rule::MissingIntent, Path::new("C061.f90"))]
@@ -122,6 +127,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn c033_allowed_values_and_contexts() -> Result<()> {
+        let rule_code = Rule::MagicNumberInExpression;
+        let path = Path::new("C033.f90");
+        let snapshot = format!(
+            "{}_{}_allowed_values_and_contexts",
+            rule_code.as_ref(),
+            path.to_string_lossy()
+        );
+        let default = Settings::default();
+        let settings = Settings {
+            check: CheckSettings {
+                magic_numbers: magic_numbers::settings::Settings {
+                    allowed_values: vec![0, 1, 2, 100],
+                    allowed_contexts: vec![
+                        MagicNumberContext::ArrayBounds,
+                        MagicNumberContext::LoopBounds,
+                        MagicNumberContext::StopCodes,
+                    ],
+                },
+                ..default.check
+            },
+            ..default
+        };
+        let diagnostics = test_path(
+            Path::new("correctness").join(path).as_path(),
+            &[rule_code],
+            &settings,
+        )?;
+        apply_common_filters!();
+        assert_snapshot!(snapshot, diagnostics);
+        Ok(())
+    }
+
     #[test]
     fn c151_fix_multiple_inline_if() -> Result<()> {
         let tempdir = TempDir::new()?;
@@ -0,0 +1,92 @@
+use ruff_diagnostics::Violation;
+use ruff_macros::{derive_message_formats, ViolationMetadata};
+
+// These rules need to see every file in the project at once, and so are checked by
+// `crate::project` after all files have been checked individually.
+
+/// ## What it does
+/// Checks for `use` statements of modules that aren't defined in any checked file.
+///
+/// ## Why is this bad?
+/// The module may have been renamed or deleted, or the file defining it may have
+/// been excluded from the project by mistake. Intrinsic modules, such as
+/// `iso_fortran_env`, are never reported.
+///
+/// This rule is only checked when linting a whole project, and will report modules
+/// provided by external libraries. These can be silenced with `per-file-ignores`
+/// or an allow comment.
+#[derive(ViolationMetadata)]
+pub(crate) struct UndefinedModule {
+    pub(crate) name: String,
+}
+
+impl Violation for UndefinedModule {
+    #[derive_message_formats]
+    fn message(&self) -> String {
+        let Self { name } = self;
+        format!("module '{name}' is not defined in any checked file")
+    }
+}
+
+/// ## What it does
+/// Checks for modules that depend on themselves through a chain of `use` statements.
+///
+/// ## Why is this bad?
+/// Modules must be compiled before any module that uses them, so a circular
+/// dependency can't be compiled at all. This often happens when a large module is
+/// split up, and can usually be resolved by moving shared code into a new module,
+/// or by moving procedure implementations into submodules.
+///
+/// ## Example
+/// ```f90
+/// module a
+///   use b
+/// end module a
+///
+/// module b
+///   use a  ! 'a' depends on 'b', which depends on 'a'
+/// end module b
+/// ```
+#[derive(ViolationMetadata)]
+pub(crate) struct CircularModuleDependency {
+    pub(crate) cycle: String,
+}
+
+impl Violation for CircularModuleDependency {
+    #[derive_message_formats]
+    fn message(&self) -> String {
+        let Self { cycle } = self;
+        format!("circular module dependency: {cycle}")
+    }
+}
+
+/// ## What it does
+/// Checks for public entities in a module that are never used outside of it.
+///
+/// ## Why is this bad?
+/// Public entities form the interface of a module, and making more entities public
+/// than necessary makes it harder to change the module without breaking code that
+/// uses it. Entities that are only used inside the module, or by its submodules,
+/// should be made `private`.
+///
+/// This rule only knows about the checked files, so it will report the public
+/// interface of libraries that are used by other projects. Entities are considered
+/// used by any program unit that uses the module, directly or through another
+/// module, and mentions their name.
+#[derive(ViolationMetadata)]
+pub(crate) struct UnusedPublicEntity {
+    pub(crate) module: String,
+    pub(crate) name: String,
+}
+
+impl Violation for UnusedPublicEntity {
+    #[derive_message_formats]
+    fn message(&self) -> String {
+        let Self { module, name } = self;
+        format!("public entity '{name}' of module '{module}' is never used outside of it")
+    }
+
+    fn fix_title(&self) -> Option<String> {
+        Some("Make it `private`".to_string())
+    }
+}
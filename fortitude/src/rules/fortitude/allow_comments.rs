@@ -79,6 +79,9 @@ impl AlwaysFixableViolation for RedirectedAllowComment {
 /// ## Why is this bad?
 /// An `allow` comment that no longer matches any diagnostic violations
 /// is likely included by mistake, and should be removed to avoid confusion.
+/// This also applies to `fortitude: disable-file` and `fortitude: disable`
+/// comments, and to `fortitude: enable` comments that don't end a region
+/// started by a `fortitude: disable` comment.
 ///
 /// ## Example
 /// ```f90
@@ -0,0 +1,50 @@
+use ruff_diagnostics::{FixAvailability, Violation};
+use ruff_macros::{derive_message_formats, ViolationMetadata};
+
+/// ## What it does
+/// Reports violations of the custom rules defined in the
+/// [`check.custom-rules`](../settings.md#check_custom-rules) table, or provided by the
+/// plugins listed in [`check.plugins`](../settings.md#check_plugins).
+///
+/// ## Why is this bad?
+/// Most projects have conventions of their own that aren't covered by Fortitude's
+/// built-in rules, such as banning particular intrinsics or requiring a prefix on
+/// module names. Custom rules let you enforce these by matching a
+/// [tree-sitter query](https://tree-sitter.github.io/tree-sitter/using-parsers/queries/)
+/// against each file. Rules that need more than a query can be written in Rust and
+/// loaded as plugins.
+///
+/// Each custom rule has its own code, which is reported in place of `FORT101`, and
+/// which can be used to select or ignore the rule, or to allow it with an `allow`
+/// comment. Custom rules are enabled unless they are excluded by `select` or
+/// `ignore`, and they aren't affected by selecting or ignoring `FORT101` itself.
+///
+/// ## Example
+/// Forbid `stop` statements:
+/// ```toml
+/// [check.custom-rules.no-stop]
+/// code = "ACME001"
+/// query = "(stop_statement) @violation"
+/// message = "Use `error stop` or return an error code instead of `{violation}`"
+/// ```
+#[derive(ViolationMetadata)]
+pub(crate) struct CustomRule {
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+/// FORT101
+impl Violation for CustomRule {
+    const FIX_AVAILABILITY: FixAvailability = FixAvailability::Sometimes;
+
+    #[derive_message_formats]
+    fn message(&self) -> String {
+        let CustomRule { message, .. } = self;
+        format!("{message}")
+    }
+
+    fn fix_title(&self) -> Option<String> {
+        let CustomRule { suggestion, .. } = self;
+        suggestion.clone()
+    }
+}
@@ -1,4 +1,5 @@
 pub(crate) mod allow_comments;
+pub(crate) mod custom_rules;
 
 #[cfg(test)]
 mod tests {
@@ -85,13 +85,16 @@ pub fn code_to_rule(category: Category, code: &str) -> Option<(RuleGroup, Rule)>
         (Correctness, "022") => (RuleGroup::Stable, Ast, Optional, correctness::implicit_kinds::ImplicitRealKind),
         (Correctness, "031") => (RuleGroup::Preview, Ast, Optional, correctness::magic_numbers::MagicNumberInArraySize),
         (Correctness, "032") => (RuleGroup::Preview, Ast, Optional, correctness::magic_numbers::MagicIoUnit),
+        (Correctness, "033") => (RuleGroup::Preview, Semantic, Optional, correctness::magic_numbers::MagicNumberInExpression),
+        (Correctness, "034") => (RuleGroup::Preview, Ast, Optional, correctness::magic_numbers::MagicNumberInComparison),
+        (Correctness, "035") => (RuleGroup::Preview, Ast, Optional, correctness::magic_numbers::MagicNumberInAllocateShape),
         (Correctness, "043") => (RuleGroup::Preview, Ast, Optional, correctness::missing_io_specifier::MissingActionSpecifier),
         (Correctness, "051") => (RuleGroup::Preview, Ast, Default, correctness::trailing_backslash::TrailingBackslash),
-        (Correctness, "061") => (RuleGroup::Stable, Ast, Default, correctness::intent::MissingIntent),
+        (Correctness, "061") => (RuleGroup::Stable, Semantic, Default, correctness::intent::MissingIntent),
         (Correctness, "071") => (RuleGroup::Stable, Ast, Default, correctness::assumed_size::AssumedSize),
         (Correctness, "072") => (RuleGroup::Stable, Ast, Default, correctness::assumed_size::AssumedSizeCharacterIntent),
-        (Correctness, "081") => (RuleGroup::Stable, Ast, Default, correctness::init_decls::InitialisationInDeclaration),
-        (Correctness, "082") => (RuleGroup::Preview, Ast, Default, correctness::init_decls::PointerInitialisationInDeclaration),
+        (Correctness, "081") => (RuleGroup::Stable, Semantic, Default, correctness::init_decls::InitialisationInDeclaration),
+        (Correctness, "082") => (RuleGroup::Preview, Semantic, Default, correctness::init_decls::PointerInitialisationInDeclaration),
         (Correctness, "091") => (RuleGroup::Stable, Ast, Default, correctness::external::ExternalProcedure),
         (Correctness, "092") => (RuleGroup::Stable, Ast, Default, correctness::external::ProcedureNotInModule),
         (Correctness, "101") => (RuleGroup::Preview, Ast, Default, correctness::derived_default_init::MissingDefaultPointerInitalisation),
@@ -106,6 +109,9 @@ pub fn code_to_rule(category: Category, code: &str) -> Option<(RuleGroup, Rule)>
         (Correctness, "152") => (RuleGroup::Preview, Ast, Default, correctness::conditionals::MisleadingInlineIfContinuation),
         (Correctness, "161") => (RuleGroup::Preview, Ast, Default, correctness::nonportable_shortcircuit_inquiry::NonportableShortcircuitInquiry),
         (Correctness, "171") => (RuleGroup::Preview, Text, Optional, correctness::split_escaped_quote::SplitEscapedQuote),
+        (Correctness, "181") => (RuleGroup::Preview, None, Optional, correctness::modules::UndefinedModule),
+        (Correctness, "182") => (RuleGroup::Preview, None, Default, correctness::modules::CircularModuleDependency),
+        (Correctness, "183") => (RuleGroup::Preview, None, Optional, correctness::modules::UnusedPublicEntity),
 
         // modernisation
         (Modernisation, "001") => (RuleGroup::Stable, Ast, Optional, modernisation::double_precision::DoublePrecision),
@@ -147,6 +153,7 @@ pub fn code_to_rule(category: Category, code: &str) -> Option<(RuleGroup, Rule)>
         (Obsolescent, "041") => (RuleGroup::Preview, Ast, Default, obsolescent::computed_goto::ComputedGoTo),
         (Obsolescent, "051") => (RuleGroup::Stable, Ast, Default, obsolescent::pause_statement::PauseStatement),
         (Obsolescent, "061") => (RuleGroup::Stable, Ast, Default, obsolescent::deprecated_character_syntax::DeprecatedCharacterSyntax),
+        (Obsolescent, "071") => (RuleGroup::Preview, Text, Optional, obsolescent::fixed_form::FixedFormSource),
 
         // fortitude
         (Fortitude, "001") => (RuleGroup::Stable, None, Default, fortitude::allow_comments::InvalidRuleCodeOrName),
@@ -154,6 +161,7 @@ pub fn code_to_rule(category: Category, code: &str) -> Option<(RuleGroup, Rule)>
         (Fortitude, "003") => (RuleGroup::Stable, None, Default, fortitude::allow_comments::RedirectedAllowComment),
         (Fortitude, "004") => (RuleGroup::Stable, None, Default, fortitude::allow_comments::DuplicatedAllowComment),
         (Fortitude, "005") => (RuleGroup::Stable, None, Default, fortitude::allow_comments::DisabledAllowComment),
+        (Fortitude, "101") => (RuleGroup::Stable, None, Default, fortitude::custom_rules::CustomRule),
 
         // Rules for testing fortitude
         // Couldn't get a separate `Testing` category working for some reason
@@ -0,0 +1,50 @@
+use crate::fixed_form::is_fixed_form;
+use crate::settings::Settings;
+use crate::TextRule;
+use ruff_diagnostics::{Diagnostic, Violation};
+use ruff_macros::{derive_message_formats, ViolationMetadata};
+use ruff_source_file::SourceFile;
+use ruff_text_size::TextRange;
+use std::path::Path;
+
+/// ## What it does
+/// Checks for files written in fixed-form source.
+///
+/// ## Why is this bad?
+/// Fixed-form source dates back to punched cards, and has been obsolescent since
+/// Fortran 95. Its column-based layout is easy to get wrong: anything after column 72
+/// is silently ignored, and a stray character in column 6 turns a line into a
+/// continuation of the previous statement. Free-form source doesn't have these
+/// pitfalls, and allows longer lines and inline comments.
+///
+/// Fixed-form files can be converted to free-form with `fortitude convert`, which
+/// also renames them with a `.f90` or `.F90` extension. Use `fortitude convert
+/// --diff` to preview the changes first.
+///
+/// ## References
+/// - Metcalf, M., Reid, J. and Cohen, M., 2018, _Modern Fortran Explained:
+///   Incorporating Fortran 2018, Oxford University Press, Appendix B
+///   'Obsolescent and Deleted Features'
+#[derive(ViolationMetadata)]
+pub(crate) struct FixedFormSource {}
+
+impl Violation for FixedFormSource {
+    #[derive_message_formats]
+    fn message(&self) -> String {
+        "file uses fixed-form source".to_string()
+    }
+
+    fn fix_title(&self) -> Option<String> {
+        Some("Convert to free-form with `fortitude convert`".to_string())
+    }
+}
+
+impl TextRule for FixedFormSource {
+    fn check(settings: &Settings, source_file: &SourceFile) -> Vec<Diagnostic> {
+        if is_fixed_form(Path::new(source_file.name()), &settings.check) {
+            vec![Diagnostic::new(Self {}, TextRange::default())]
+        } else {
+            vec![]
+        }
+    }
+}
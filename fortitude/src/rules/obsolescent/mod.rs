@@ -2,6 +2,7 @@ pub mod common_blocks;
 pub mod computed_goto;
 pub mod deprecated_character_syntax;
 pub mod entry_statement;
+pub mod fixed_form;
 pub mod pause_statement;
 pub mod specific_names;
 pub mod statement_functions;
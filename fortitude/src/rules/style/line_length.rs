@@ -1,4 +1,5 @@
 /// Defines rules that govern line length.
+use crate::fixed_form::{is_comment_line, is_fixed_form, FIXED_FORM_COLUMNS};
 use crate::settings::Settings;
 use crate::TextRule;
 use lazy_regex::regex_is_match;
@@ -7,6 +8,7 @@ use ruff_macros::{derive_message_formats, ViolationMetadata};
 use ruff_source_file::SourceFile;
 use ruff_source_file::UniversalNewlines;
 use ruff_text_size::{TextLen, TextRange, TextSize};
+use std::path::Path;
 
 /// ## What does it do?
 /// Checks line length isn't too long
@@ -30,6 +32,11 @@ use ruff_text_size::{TextLen, TextRange, TextSize};
 /// Note that the Fortran standard states a maximum line length of 132 characters,
 /// and while some modern compilers will support longer lines, for portability it
 /// is recommended to stay beneath this limit.
+///
+/// In fixed-form files, anything after column 72 is silently ignored by the compiler,
+/// so any code line longer than this is reported, regardless of the maximum line
+/// length. This includes lines ending in strings, which would be truncated. Comment
+/// lines may be any length.
 #[derive(ViolationMetadata)]
 pub(crate) struct LineTooLong {
     max_length: usize,
@@ -50,16 +57,27 @@ impl Violation for LineTooLong {
 impl TextRule for LineTooLong {
     fn check(settings: &Settings, source_file: &SourceFile) -> Vec<Diagnostic> {
         let source = source_file.to_source_code();
-        let max_length = settings.check.line_length;
+        let fixed_form = is_fixed_form(Path::new(source_file.name()), &settings.check);
+        let max_length = if fixed_form {
+            FIXED_FORM_COLUMNS
+        } else {
+            settings.check.line_length
+        };
         let mut violations = Vec::new();
         for line in source.text().universal_newlines() {
             // Note: Can't use string.len(), as that gives byte length, not char length
             let actual_length = line.chars().count();
             if actual_length > max_length {
-                // Are we ending on a string or comment? If so, we'll allow it through, as it may
-                // contain something like a long URL that cannot be reasonably split across multiple
-                // lines.
-                if regex_is_match!(r#"(["']\w*&?$)|(!.*$)|(^\w*&)"#, line.as_str()) {
+                let allowed = if fixed_form {
+                    // Truncating a comment line is harmless
+                    is_comment_line(line.as_str())
+                } else {
+                    // Are we ending on a string or comment? If so, we'll allow it through, as it may
+                    // contain something like a long URL that cannot be reasonably split across multiple
+                    // lines.
+                    regex_is_match!(r#"(["']\w*&?$)|(!.*$)|(^\w*&)"#, line.as_str())
+                };
+                if allowed {
                     continue;
                 }
                 // Get the byte range from the first character that oversteps the limit
@@ -0,0 +1,905 @@
+//! A lightweight semantic model of a single file.
+//!
+//! The model is built once per file from the syntax tree, and maps each program unit,
+//! procedure, and `block` construct to the symbols declared in it. Rules can use it to
+//! look up what a name refers to, rather than re-walking the declarations themselves.
+//!
+//! Names in Fortran are case-insensitive, so all lookups are too.
+
+use std::cell::OnceCell;
+use std::fmt;
+use std::iter::once;
+
+use ruff_text_size::TextRange;
+use rustc_hash::FxHashMap;
+use tree_sitter::{Node, Tree};
+
+use crate::ast::FortitudeNode;
+
+/// Index of a [`Scope`] in a [`SemanticModel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScopeId(u32);
+
+impl ScopeId {
+    /// The scope containing the whole file.
+    pub const fn global() -> Self {
+        Self(0)
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    /// The whole file, containing external procedures and program units.
+    Global,
+    Program,
+    Module,
+    Submodule,
+    Function,
+    Subroutine,
+    /// A separate module procedure, `module procedure foo`.
+    ModuleProcedure,
+    /// A `block` construct.
+    Block,
+}
+
+impl ScopeKind {
+    fn from_node_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "program" => Some(Self::Program),
+            "module" => Some(Self::Module),
+            "submodule" => Some(Self::Submodule),
+            "function" => Some(Self::Function),
+            "subroutine" => Some(Self::Subroutine),
+            "module_procedure" => Some(Self::ModuleProcedure),
+            "block_construct" => Some(Self::Block),
+            _ => None,
+        }
+    }
+
+    /// Returns true for scopes with dummy arguments.
+    pub const fn is_procedure(self) -> bool {
+        matches!(
+            self,
+            Self::Function | Self::Subroutine | Self::ModuleProcedure
+        )
+    }
+}
+
+impl fmt::Display for ScopeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::Global => "file",
+            Self::Program => "program",
+            Self::Module => "module",
+            Self::Submodule => "submodule",
+            Self::Function => "function",
+            Self::Subroutine => "subroutine",
+            Self::ModuleProcedure => "module procedure",
+            Self::Block => "block",
+        };
+        f.write_str(kind)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    /// A variable with the `parameter` attribute.
+    NamedConstant,
+    DummyArgument,
+    /// A dummy argument declared with `procedure`, or with the `external` attribute.
+    DummyProcedure,
+    /// The result variable of a function.
+    FunctionResult,
+    /// A procedure defined or declared in this scope, including interface bodies,
+    /// `procedure` declarations, and `external` procedures.
+    Procedure,
+    DerivedType,
+    /// A named generic interface.
+    Interface,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intent {
+    In,
+    Out,
+    InOut,
+}
+
+impl Intent {
+    fn from_attribute(attribute: &str) -> Option<Self> {
+        match attribute.strip_prefix("intent(")?.strip_suffix(')')? {
+            "in" => Some(Self::In),
+            "out" => Some(Self::Out),
+            "inout" => Some(Self::InOut),
+            _ => None,
+        }
+    }
+}
+
+/// A name declared in a scope.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    name: String,
+    kind: SymbolKind,
+    type_spec: Option<String>,
+    attributes: Vec<String>,
+    intent: Option<Intent>,
+    range: TextRange,
+    declaration: Option<TextRange>,
+}
+
+impl Symbol {
+    /// The name as first written in the source.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> SymbolKind {
+        self.kind
+    }
+
+    /// The declared type, lowercased and without whitespace, such as `real(dp)` or
+    /// `type(foo)`. This is `None` for implicitly typed symbols, and for symbols
+    /// without a type, such as derived types.
+    pub fn type_spec(&self) -> Option<&str> {
+        self.type_spec.as_deref()
+    }
+
+    /// The attributes from all declarations of the symbol, lowercased and without
+    /// whitespace, such as `intent(in)` or `dimension(:)`.
+    pub fn attributes(&self) -> &[String] {
+        &self.attributes
+    }
+
+    /// Returns true if the symbol has the given attribute, ignoring any arguments,
+    /// so that `dimension` matches `dimension(:)`.
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attributes.iter().any(|attribute| {
+            attribute
+                .strip_prefix(name)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('('))
+        })
+    }
+
+    pub fn intent(&self) -> Option<Intent> {
+        self.intent
+    }
+
+    /// The range of the symbol's first declarator, or of its name if it isn't declared.
+    pub fn range(&self) -> TextRange {
+        self.range
+    }
+
+    /// The range of the statement first declaring the symbol, if it has one. Dummy
+    /// arguments and function results that are implicitly typed have no declaration.
+    pub fn declaration(&self) -> Option<TextRange> {
+        self.declaration
+    }
+}
+
+/// A `use` statement in a scope.
+#[derive(Debug, Clone)]
+pub struct UseStatement {
+    module: String,
+    intrinsic: bool,
+    only: Option<Vec<UseItem>>,
+    range: TextRange,
+}
+
+impl UseStatement {
+    /// The module name, lowercased.
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// Returns true for `use, intrinsic` statements.
+    pub fn is_intrinsic(&self) -> bool {
+        self.intrinsic
+    }
+
+    /// The names in the `only` clause, or `None` if all public names are imported.
+    pub fn only(&self) -> Option<&[UseItem]> {
+        self.only.as_deref()
+    }
+
+    pub fn range(&self) -> TextRange {
+        self.range
+    }
+
+    /// Find the item importing `name`, if it is listed in the `only` clause.
+    fn item(&self, name: &str) -> Option<&UseItem> {
+        self.only.as_ref()?.iter().find(|item| item.local == name)
+    }
+}
+
+/// A name imported by a `use` statement, possibly renamed: `local => remote`.
+#[derive(Debug, Clone)]
+pub struct UseItem {
+    local: String,
+    remote: String,
+    range: TextRange,
+}
+
+impl UseItem {
+    /// The name in this scope, lowercased.
+    pub fn local(&self) -> &str {
+        &self.local
+    }
+
+    /// The name in the used module, lowercased.
+    pub fn remote(&self) -> &str {
+        &self.remote
+    }
+
+    pub fn range(&self) -> TextRange {
+        self.range
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    kind: ScopeKind,
+    name: Option<String>,
+    range: TextRange,
+    parent: Option<ScopeId>,
+    host: Option<ScopeId>,
+    symbols: Vec<Symbol>,
+    symbol_ids: FxHashMap<String, usize>,
+    dummy_arguments: Vec<String>,
+    uses: Vec<UseStatement>,
+}
+
+impl Scope {
+    fn new(
+        kind: ScopeKind,
+        name: Option<String>,
+        range: TextRange,
+        parent: Option<ScopeId>,
+        host: Option<ScopeId>,
+    ) -> Self {
+        Self {
+            kind,
+            name,
+            range,
+            parent,
+            host,
+            symbols: Vec::new(),
+            symbol_ids: FxHashMap::default(),
+            dummy_arguments: Vec::new(),
+            uses: Vec::new(),
+        }
+    }
+
+    pub fn kind(&self) -> ScopeKind {
+        self.kind
+    }
+
+    /// The name of the program unit or procedure, if it has one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn range(&self) -> TextRange {
+        self.range
+    }
+
+    /// The scope this one is nested in, if any.
+    pub fn parent(&self) -> Option<ScopeId> {
+        self.parent
+    }
+
+    /// The scope whose names are accessible from this one through host association.
+    /// This is usually the parent, but interface bodies have no host, and `block`
+    /// constructs have their enclosing scope as host.
+    pub fn host(&self) -> Option<ScopeId> {
+        self.host
+    }
+
+    /// All symbols declared in this scope, in order of first declaration.
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter()
+    }
+
+    /// Find a symbol declared in this scope, ignoring host and use association.
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbol_ids
+            .get(&name.to_lowercase())
+            .map(|index| &self.symbols[*index])
+    }
+
+    /// The dummy arguments of a procedure, in order.
+    pub fn dummy_arguments(&self) -> impl Iterator<Item = &Symbol> {
+        self.dummy_arguments
+            .iter()
+            .filter_map(|name| self.symbol(name))
+    }
+
+    pub fn uses(&self) -> &[UseStatement] {
+        &self.uses
+    }
+
+    fn declare(&mut self, name: &str, range: TextRange) -> &mut Symbol {
+        let key = name.to_lowercase();
+        let index = *self.symbol_ids.entry(key).or_insert_with(|| {
+            self.symbols.push(Symbol {
+                name: name.to_string(),
+                kind: SymbolKind::Variable,
+                type_spec: None,
+                attributes: Vec::new(),
+                intent: None,
+                range,
+                declaration: None,
+            });
+            self.symbols.len() - 1
+        });
+        &mut self.symbols[index]
+    }
+}
+
+/// What a name resolves to, see [`SemanticModel::resolve`].
+#[derive(Debug, Clone, Copy)]
+pub enum Binding<'a> {
+    /// A symbol declared in the scope the name was resolved from.
+    Local(&'a Symbol),
+    /// A symbol declared in an enclosing scope, accessed through host association.
+    Host(ScopeId, &'a Symbol),
+    /// A name imported from another module by a `use` statement with an `only` clause.
+    Use(&'a UseStatement, &'a UseItem),
+}
+
+/// A [`SemanticModel`] that's only built the first time it's needed, so that files
+/// aren't modelled unless a rule that uses the model is enabled.
+pub struct LazySemanticModel<'a> {
+    tree: &'a Tree,
+    source: &'a str,
+    model: OnceCell<SemanticModel>,
+}
+
+impl<'a> LazySemanticModel<'a> {
+    pub fn new(tree: &'a Tree, source: &'a str) -> Self {
+        Self {
+            tree,
+            source,
+            model: OnceCell::new(),
+        }
+    }
+
+    pub fn get(&self) -> &SemanticModel {
+        self.model
+            .get_or_init(|| SemanticModel::new(self.tree, self.source))
+    }
+}
+
+/// Per-scope symbol tables for a file.
+#[derive(Debug, Clone)]
+pub struct SemanticModel {
+    scopes: Vec<Scope>,
+    scope_ids: FxHashMap<usize, ScopeId>,
+}
+
+impl SemanticModel {
+    pub fn new(tree: &Tree, source: &str) -> Self {
+        let root = tree.root_node();
+        let mut model = Self {
+            scopes: vec![Scope::new(
+                ScopeKind::Global,
+                None,
+                root.textrange(),
+                None,
+                None,
+            )],
+            scope_ids: FxHashMap::default(),
+        };
+        model.scope_ids.insert(root.id(), ScopeId::global());
+        model.visit_body(&root, ScopeId::global(), source);
+        model
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id.index()]
+    }
+
+    pub fn scopes(&self) -> impl Iterator<Item = (ScopeId, &Scope)> {
+        self.scopes
+            .iter()
+            .enumerate()
+            .map(|(index, scope)| (ScopeId(index as u32), scope))
+    }
+
+    /// Iterate over `id` and the scopes it is nested in, innermost first.
+    pub fn ancestors(&self, id: ScopeId) -> impl Iterator<Item = &Scope> {
+        std::iter::successors(Some(self.scope(id)), |scope| {
+            scope.parent.map(|parent| self.scope(parent))
+        })
+    }
+
+    /// The scope introduced by `node`, if it is a program unit, procedure, or `block`.
+    pub fn scope_of(&self, node: &Node) -> Option<ScopeId> {
+        self.scope_ids.get(&node.id()).copied()
+    }
+
+    /// The innermost scope containing `node`. Statements opening a scope, such as
+    /// `subroutine foo(x)`, are part of the scope they open.
+    pub fn enclosing_scope(&self, node: &Node) -> ScopeId {
+        once(*node)
+            .chain(node.ancestors())
+            .find_map(|node| self.scope_of(&node))
+            .unwrap_or(ScopeId::global())
+    }
+
+    /// Resolve `name` as seen from `scope`, looking at local declarations, then names
+    /// imported by `use` statements, then through host association.
+    ///
+    /// Names imported by `use` statements without an `only` clause can't be resolved
+    /// without looking at the used module, so this returns `None` if the name isn't
+    /// declared in, or explicitly imported into, a scope with such a statement, as it
+    /// may shadow any names from further out.
+    pub fn resolve(&self, from: ScopeId, name: &str) -> Option<Binding<'_>> {
+        let name = name.to_lowercase();
+        let mut current = Some(from);
+        while let Some(id) = current {
+            let scope = self.scope(id);
+            if let Some(symbol) = scope.symbol(&name) {
+                return Some(if id == from {
+                    Binding::Local(symbol)
+                } else {
+                    Binding::Host(id, symbol)
+                });
+            }
+            for statement in &scope.uses {
+                if let Some(item) = statement.item(&name) {
+                    return Some(Binding::Use(statement, item));
+                }
+            }
+            if scope.uses.iter().any(|statement| statement.only.is_none()) {
+                return None;
+            }
+            current = scope.host;
+        }
+        None
+    }
+
+    fn push_scope(&mut self, node: &Node, kind: ScopeKind, parent: ScopeId, host: bool) -> ScopeId {
+        let id = ScopeId(self.scopes.len() as u32);
+        let host = host.then_some(parent);
+        self.scopes
+            .push(Scope::new(kind, None, node.textrange(), Some(parent), host));
+        self.scope_ids.insert(node.id(), id);
+        id
+    }
+
+    fn scope_mut(&mut self, id: ScopeId) -> &mut Scope {
+        &mut self.scopes[id.index()]
+    }
+
+    /// Visit the children of a node introducing a scope.
+    fn visit_body(&mut self, node: &Node, scope: ScopeId, src: &str) {
+        for child in node.named_children(&mut node.walk()) {
+            match child.kind() {
+                "variable_declaration" => self.visit_declaration(&child, scope, src),
+                "use_statement" => self.visit_use(&child, scope, src),
+                "derived_type_definition" => {
+                    if let Some(name) = child
+                        .child_with_name("derived_type_statement")
+                        .and_then(|statement| statement.child_with_name("type_name"))
+                    {
+                        self.declare_named(&name, &child, scope, SymbolKind::DerivedType, src);
+                    }
+                }
+                "interface" => self.visit_interface(&child, scope, src),
+                "internal_procedures" => self.visit_body(&child, scope, src),
+                kind => {
+                    if let Some(kind) = ScopeKind::from_node_kind(kind) {
+                        self.visit_scope(&child, kind, scope, true, src);
+                    } else if child.named_child_count() > 0 {
+                        // `block` constructs may be nested in other constructs, such
+                        // as `do` loops
+                        self.visit_nested_blocks(&child, scope, src);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Find `block` constructs nested inside executable constructs.
+    fn visit_nested_blocks(&mut self, node: &Node, scope: ScopeId, src: &str) {
+        for child in node.named_children(&mut node.walk()) {
+            if child.kind() == "block_construct" {
+                self.visit_scope(&child, ScopeKind::Block, scope, true, src);
+            } else if child.named_child_count() > 0 {
+                self.visit_nested_blocks(&child, scope, src);
+            }
+        }
+    }
+
+    fn visit_scope(
+        &mut self,
+        node: &Node,
+        kind: ScopeKind,
+        parent: ScopeId,
+        host: bool,
+        src: &str,
+    ) {
+        let id = self.push_scope(node, kind, parent, host);
+        let statement = node
+            .named_child(0)
+            .filter(|child| child.kind().ends_with("_statement"));
+
+        if let Some(statement) = statement.filter(|_| kind != ScopeKind::Block) {
+            if let Some(name) = statement.child_with_name("name") {
+                self.scope_mut(id).name = name.to_text(src).map(str::to_string);
+                if kind.is_procedure() {
+                    self.declare_named(&name, node, parent, SymbolKind::Procedure, src);
+                }
+            }
+
+            if let Some(parameters) = statement.child_by_field_name("parameters") {
+                for parameter in parameters.named_children(&mut parameters.walk()) {
+                    if parameter.kind() != "identifier" {
+                        continue;
+                    }
+                    let Some(name) = parameter.to_text(src) else {
+                        continue;
+                    };
+                    let scope = self.scope_mut(id);
+                    scope.dummy_arguments.push(name.to_lowercase());
+                    scope.declare(name, parameter.textrange()).kind = SymbolKind::DummyArgument;
+                }
+            }
+
+            if kind == ScopeKind::Function {
+                let result = statement
+                    .child_with_name("function_result")
+                    .and_then(|result| result.child_with_name("identifier"))
+                    .or_else(|| statement.child_with_name("name"));
+                if let Some(result) = result {
+                    if let Some(name) = result.to_text(src) {
+                        self.scope_mut(id).declare(name, result.textrange()).kind =
+                            SymbolKind::FunctionResult;
+                    }
+                }
+                // The type may also be given as a prefix: `integer function foo()`
+                if let Some(type_) = statement
+                    .child_with_name("intrinsic_type")
+                    .or_else(|| statement.child_with_name("derived_type"))
+                {
+                    let type_spec = type_spec(&type_, src);
+                    let scope = self.scope_mut(id);
+                    if let Some(symbol) = scope
+                        .symbols
+                        .iter_mut()
+                        .find(|symbol| symbol.kind == SymbolKind::FunctionResult)
+                    {
+                        symbol.type_spec = type_spec;
+                    }
+                }
+            }
+        }
+
+        self.visit_body(node, id, src);
+    }
+
+    fn visit_interface(&mut self, node: &Node, scope: ScopeId, src: &str) {
+        if let Some(name) = node
+            .child_with_name("interface_statement")
+            .and_then(|statement| statement.child_with_name("name"))
+        {
+            self.declare_named(&name, node, scope, SymbolKind::Interface, src);
+        }
+        for child in node.named_children(&mut node.walk()) {
+            if let Some(kind @ (ScopeKind::Function | ScopeKind::Subroutine)) =
+                ScopeKind::from_node_kind(child.kind())
+            {
+                // Interface bodies don't have access to their host
+                self.visit_scope(&child, kind, scope, false, src);
+            }
+        }
+    }
+
+    fn visit_declaration(&mut self, node: &Node, scope: ScopeId, src: &str) {
+        let type_node = node.child_by_field_name("type");
+        let is_procedure = type_node.is_some_and(|type_| type_.kind() == "procedure");
+        let type_spec = type_node
+            .filter(|_| !is_procedure)
+            .and_then(|type_| type_spec(&type_, src));
+        let attributes: Vec<String> = node
+            .children_by_field_name("attribute", &mut node.walk())
+            .filter_map(|attribute| attribute.to_text(src))
+            .map(normalise)
+            .collect();
+        let intent = attributes
+            .iter()
+            .find_map(|attribute| Intent::from_attribute(attribute));
+        let is_constant = attributes.iter().any(|attribute| attribute == "parameter");
+        let is_external = attributes.iter().any(|attribute| attribute == "external");
+
+        for declarator in node.children_by_field_name("declarator", &mut node.walk()) {
+            let Some(name) = declarator_name(&declarator).and_then(|name| name.to_text(src)) else {
+                continue;
+            };
+            let symbol = self.scope_mut(scope).declare(name, declarator.textrange());
+            if symbol.declaration.is_none() {
+                symbol.range = declarator.textrange();
+                symbol.declaration = Some(node.textrange());
+            }
+            if symbol.type_spec.is_none() {
+                symbol.type_spec.clone_from(&type_spec);
+            }
+            symbol.attributes.extend(attributes.iter().cloned());
+            symbol.intent = symbol.intent.or(intent);
+            symbol.kind = match symbol.kind {
+                SymbolKind::DummyArgument if is_procedure || is_external => {
+                    SymbolKind::DummyProcedure
+                }
+                SymbolKind::Variable if is_procedure || is_external => SymbolKind::Procedure,
+                SymbolKind::Variable if is_constant => SymbolKind::NamedConstant,
+                kind => kind,
+            };
+        }
+    }
+
+    fn visit_use(&mut self, node: &Node, scope: ScopeId, src: &str) {
+        let Some(module) = node
+            .child_with_name("module_name")
+            .and_then(|module| module.to_text(src))
+        else {
+            return;
+        };
+        let intrinsic = node.children(&mut node.walk()).any(|child| {
+            child
+                .to_text(src)
+                .is_some_and(|text| text.eq_ignore_ascii_case("intrinsic"))
+        });
+        let only = node.child_with_name("included_items").map(|items| {
+            items
+                .named_children(&mut items.walk())
+                .filter_map(|item| {
+                    let (local, remote) = if item.kind() == "use_alias" {
+                        let mut names = item.named_children(&mut item.walk());
+                        (names.next()?, names.next()?)
+                    } else {
+                        (item, item)
+                    };
+                    Some(UseItem {
+                        local: normalise(local.to_text(src)?),
+                        remote: normalise(remote.to_text(src)?),
+                        range: item.textrange(),
+                    })
+                })
+                .collect()
+        });
+        self.scope_mut(scope).uses.push(UseStatement {
+            module: module.to_lowercase(),
+            intrinsic,
+            only,
+            range: node.textrange(),
+        });
+    }
+
+    /// Declare a symbol named by the `name` node, defined by `definition`.
+    fn declare_named(
+        &mut self,
+        name: &Node,
+        definition: &Node,
+        scope: ScopeId,
+        kind: SymbolKind,
+        src: &str,
+    ) {
+        let Some(text) = name.to_text(src) else {
+            return;
+        };
+        let symbol = self.scope_mut(scope).declare(text, name.textrange());
+        symbol.kind = kind;
+        symbol.declaration.get_or_insert(definition.textrange());
+    }
+}
+
+/// The identifier declared by a declarator, such as `x` in `x(:) = 1`.
+fn declarator_name<'a>(declarator: &Node<'a>) -> Option<Node<'a>> {
+    match declarator.kind() {
+        "identifier" => Some(*declarator),
+        "sized_declarator" => declarator.child_with_name("identifier"),
+        "init_declarator" | "pointer_init_declarator" => {
+            declarator_name(&declarator.child_by_field_name("left")?)
+        }
+        _ => None,
+    }
+}
+
+/// The text of a type, including its kind, such as `real(kind=dp)`.
+fn type_spec(type_: &Node, src: &str) -> Option<String> {
+    type_.to_text(src).map(normalise)
+}
+
+fn normalise(text: &str) -> String {
+    text.split_whitespace().collect::<String>().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use textwrap::dedent;
+    use tree_sitter::Parser;
+
+    use super::{Binding, Intent, ScopeId, ScopeKind, SemanticModel, SymbolKind};
+
+    fn model(source: &str) -> Result<SemanticModel> {
+        let source = dedent(source);
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_fortran::LANGUAGE.into())?;
+        let tree = parser.parse(&source, None).unwrap();
+        Ok(SemanticModel::new(&tree, &source))
+    }
+
+    fn find_scope(model: &SemanticModel, name: &str) -> ScopeId {
+        model
+            .scopes()
+            .find(|(_, scope)| scope.name() == Some(name))
+            .map(|(id, _)| id)
+            .unwrap()
+    }
+
+    #[test]
+    fn procedure_symbols() -> Result<()> {
+        let model = model(
+            "
+            module test
+              integer, parameter :: dp = kind(1.0d0)
+            contains
+              real(dp) function foo(a, b, p) result(res)
+                integer, intent(in) :: a
+                REAL(DP), DIMENSION(:), INTENT(IN OUT) :: B
+                procedure(bar) :: p
+                integer :: local = 1
+              end function foo
+            end module test
+            ",
+        )?;
+
+        let module = model.scope(find_scope(&model, "test"));
+        assert_eq!(module.kind(), ScopeKind::Module);
+        assert_eq!(
+            module.symbol("dp").map(|symbol| symbol.kind()),
+            Some(SymbolKind::NamedConstant)
+        );
+        assert_eq!(
+            module.symbol("FOO").map(|symbol| symbol.kind()),
+            Some(SymbolKind::Procedure)
+        );
+
+        let foo = model.scope(find_scope(&model, "foo"));
+        assert_eq!(foo.kind(), ScopeKind::Function);
+        assert_eq!(foo.parent(), Some(find_scope(&model, "test")));
+        let args: Vec<_> = foo
+            .dummy_arguments()
+            .map(|symbol| (symbol.name(), symbol.kind(), symbol.intent()))
+            .collect();
+        assert_eq!(
+            args,
+            [
+                ("a", SymbolKind::DummyArgument, Some(Intent::In)),
+                ("b", SymbolKind::DummyArgument, Some(Intent::InOut)),
+                ("p", SymbolKind::DummyProcedure, None),
+            ]
+        );
+
+        let b = foo.symbol("b").unwrap();
+        assert_eq!(b.type_spec(), Some("real(dp)"));
+        assert!(b.has_attribute("dimension"));
+        assert!(!b.has_attribute("dim"));
+
+        let result = foo.symbol("res").unwrap();
+        assert_eq!(result.kind(), SymbolKind::FunctionResult);
+        assert!(result.declaration().is_none());
+        assert_eq!(
+            foo.symbol("local").map(|symbol| symbol.kind()),
+            Some(SymbolKind::Variable)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn host_and_use_association() -> Result<()> {
+        let model = model(
+            "
+            program test
+              use, intrinsic :: iso_fortran_env, only: wp => real64
+              use other
+              implicit none
+              integer :: x
+              interface
+                subroutine ext(x)
+                  real :: x
+                end subroutine ext
+              end interface
+            contains
+              subroutine inner()
+                block
+                  integer :: y
+                end block
+              end subroutine inner
+            end program test
+            ",
+        )?;
+
+        let program = find_scope(&model, "test");
+        let uses = model.scope(program).uses();
+        assert_eq!(uses.len(), 2);
+        assert!(uses[0].is_intrinsic());
+        assert_eq!(uses[0].module(), "iso_fortran_env");
+        assert!(uses[1].only().is_none());
+
+        let inner = find_scope(&model, "inner");
+        assert!(matches!(
+            model.resolve(inner, "X"),
+            Some(Binding::Host(id, symbol)) if id == program && symbol.type_spec() == Some("integer")
+        ));
+        assert!(matches!(
+            model.resolve(inner, "wp"),
+            Some(Binding::Use(statement, item))
+                if statement.module() == "iso_fortran_env" && item.remote() == "real64"
+        ));
+        // Could come from `use other`, so can't be resolved
+        assert!(model.resolve(inner, "z").is_none());
+
+        // Interface bodies don't have access to their host
+        let ext = find_scope(&model, "ext");
+        assert_eq!(model.scope(ext).host(), None);
+        assert!(matches!(
+            model.resolve(ext, "x"),
+            Some(Binding::Local(symbol)) if symbol.type_spec() == Some("real")
+        ));
+        assert!(model.resolve(ext, "wp").is_none());
+
+        let (block, _) = model
+            .scopes()
+            .find(|(_, scope)| scope.kind() == ScopeKind::Block)
+            .unwrap();
+        assert_eq!(model.scope(block).parent(), Some(inner));
+        assert!(model.scope(block).symbol("y").is_some());
+        assert!(matches!(
+            model.resolve(block, "x"),
+            Some(Binding::Host(id, _)) if id == program
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn use_without_only_shadows_host() -> Result<()> {
+        let model = model(
+            "
+            module test
+              implicit none
+              integer :: x
+            contains
+              subroutine inner()
+                use other
+                print *, x
+              end subroutine inner
+              subroutine restricted()
+                use other, only: y
+                print *, x
+              end subroutine restricted
+            end module test
+            ",
+        )?;
+
+        // `x` may come from `other`, rather than the host
+        let inner = find_scope(&model, "inner");
+        assert!(model.resolve(inner, "x").is_none());
+
+        // Only `y` is imported, so `x` must come from the host
+        let module = find_scope(&model, "test");
+        let restricted = find_scope(&model, "restricted");
+        assert!(matches!(
+            model.resolve(restricted, "x"),
+            Some(Binding::Host(id, _)) if id == module
+        ));
+        Ok(())
+    }
+}
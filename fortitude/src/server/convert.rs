@@ -0,0 +1,154 @@
+//! Conversions between Fortitude's byte offsets and LSP positions, which count UTF-16
+//! code units from the start of a line.
+
+use lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+use ruff_source_file::SourceFile;
+use ruff_text_size::{TextRange, TextSize};
+use tree_sitter::{InputEdit, Point};
+
+/// Convert a byte range in `file` to an LSP [`Range`].
+pub(crate) fn to_lsp_range(range: TextRange, file: &SourceFile) -> Range {
+    Range::new(
+        to_lsp_position(range.start(), file),
+        to_lsp_position(range.end(), file),
+    )
+}
+
+/// Convert a byte offset in `file` to an LSP [`Position`].
+pub(crate) fn to_lsp_position(offset: TextSize, file: &SourceFile) -> Position {
+    let src = file.to_source_code();
+    let line = src.line_index(offset);
+    let line_start = src.line_start(line);
+    let character = src
+        .slice(TextRange::new(line_start, offset))
+        .encode_utf16()
+        .count();
+    Position::new(
+        u32::try_from(line.to_zero_indexed()).unwrap_or(u32::MAX),
+        u32::try_from(character).unwrap_or(u32::MAX),
+    )
+}
+
+/// Convert an LSP [`Position`] to a byte offset in `text`. Positions past the end of a
+/// line or the end of the text are clamped.
+pub(crate) fn to_offset(position: Position, text: &str) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return text.len(),
+        }
+    }
+
+    let line = &text[line_start..];
+    let line = &line[..line.find(['\r', '\n']).unwrap_or(line.len())];
+
+    let mut character = 0;
+    for (offset, char) in line.char_indices() {
+        if character >= position.character as usize {
+            return line_start + offset;
+        }
+        character += char.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// Apply a change sent by the client to the contents of a document, returning the
+/// edit needed to update a syntax tree of the old contents. Changes that replace the
+/// whole document have no edit, as the tree has to be rebuilt from scratch.
+pub(crate) fn apply_change(
+    text: &mut String,
+    change: TextDocumentContentChangeEvent,
+) -> Option<InputEdit> {
+    match change.range {
+        Some(range) => {
+            let start = to_offset(range.start, text);
+            let end = to_offset(range.end, text).max(start);
+            let start_position = to_point(start, text);
+            let old_end_position = to_point(end, text);
+            text.replace_range(start..end, &change.text);
+            let new_end = start + change.text.len();
+            Some(InputEdit {
+                start_byte: start,
+                old_end_byte: end,
+                new_end_byte: new_end,
+                start_position,
+                old_end_position,
+                new_end_position: to_point(new_end, text),
+            })
+        }
+        None => {
+            *text = change.text;
+            None
+        }
+    }
+}
+
+/// Convert a byte offset in `text` to a tree-sitter [`Point`], which counts columns
+/// in bytes.
+fn to_point(offset: usize, text: &str) -> Point {
+    let before = &text[..offset];
+    match before.rfind('\n') {
+        Some(last_newline) => Point {
+            row: before.matches('\n').count(),
+            column: offset - last_newline - 1,
+        },
+        None => Point {
+            row: 0,
+            column: offset,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Context, Result};
+    use lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+    use tree_sitter::Parser;
+
+    use super::apply_change;
+
+    #[test]
+    fn incremental_reparse() -> Result<()> {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_fortran::LANGUAGE.into())?;
+
+        let mut text = "program test\n  x = 1\nend program\n".to_string();
+        let mut tree = parser.parse(&text, None).context("Failed to parse")?;
+        let changes = [
+            // Insert a new line, in the middle of the file
+            (
+                Range::new(Position::new(1, 0), Position::new(1, 0)),
+                "  implicit none\n",
+            ),
+            // Replace text on a single line
+            (Range::new(Position::new(2, 6), Position::new(2, 7)), "42"),
+            // Remove text spanning several lines
+            (Range::new(Position::new(1, 15), Position::new(2, 8)), ""),
+        ];
+        for (range, new_text) in changes {
+            let edit = apply_change(
+                &mut text,
+                TextDocumentContentChangeEvent {
+                    range: Some(range),
+                    range_length: None,
+                    text: new_text.to_string(),
+                },
+            )
+            .context("Expected an edit")?;
+            tree.edit(&edit);
+            tree = parser
+                .parse(&text, Some(&tree))
+                .context("Failed to parse")?;
+
+            let fresh = parser.parse(&text, None).context("Failed to parse")?;
+            assert_eq!(
+                tree.root_node().to_sexp(),
+                fresh.root_node().to_sexp(),
+                "{text}"
+            );
+        }
+        assert_eq!(text, "program test\n  implicit none\nend program\n");
+        Ok(())
+    }
+}
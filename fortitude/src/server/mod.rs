@@ -0,0 +1,576 @@
+mod convert;
+mod session;
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{CodeActionRequest, Request as RequestTrait};
+use lsp_types::{
+    CodeActionKind, CodeActionOptions, CodeActionParams, CodeActionProviderCapability,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    InitializeParams, PublishDiagnosticsParams, ServerCapabilities, ServerInfo,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use path_absolutize::path_dedot;
+
+use crate::cli::{CheckArgs, GlobalConfigArgs};
+use crate::configuration::{self, find_settings_toml, parse_config_file, Configuration};
+use crate::options::Options;
+use crate::resolver::Resolver;
+use crate::VERSION;
+
+use session::Session;
+
+/// Run the language server, communicating with the client over stdin and stdout.
+pub fn server(global_options: &GlobalConfigArgs) -> Result<ExitCode> {
+    let (connection, io_threads) = Connection::stdio();
+    run(connection, global_options.config_file.clone())?;
+    io_threads.join()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Initialise the connection, then handle messages until the client shuts us down.
+fn run(connection: Connection, config_file: Option<PathBuf>) -> Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+            code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+            ..CodeActionOptions::default()
+        })),
+        ..ServerCapabilities::default()
+    };
+    let (id, params) = connection.initialize_start()?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+    connection.initialize_finish(
+        id,
+        serde_json::json!({
+            "capabilities": capabilities,
+            "serverInfo": ServerInfo {
+                name: "fortitude".to_string(),
+                version: Some(VERSION.to_string()),
+            },
+        }),
+    )?;
+
+    let root = workspace_root(&params);
+    let resolver = load_resolver(&root, config_file)?;
+    let mut session = Session::new(resolver);
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = handle_request(&mut session, request);
+                connection.sender.send(response.into())?;
+            }
+            Message::Notification(notification) => {
+                // A bad notification shouldn't take down the whole server, as the client
+                // has no way to find out that it's stopped
+                let method = notification.method.clone();
+                match handle_notification(&mut session, notification) {
+                    Ok(Some(uri)) => publish_diagnostics(&connection, &mut session, uri)?,
+                    Ok(None) => {}
+                    Err(err) => warn!("Failed to handle {method} notification: {err:#}"),
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// The directory used to find the configuration file: the first workspace folder, if
+/// the client sent any, otherwise the current directory.
+fn workspace_root(params: &InitializeParams) -> PathBuf {
+    #[allow(deprecated)]
+    let root_uri = params
+        .workspace_folders
+        .as_ref()
+        .and_then(|folders| folders.first())
+        .map(|folder| &folder.uri)
+        .or(params.root_uri.as_ref());
+
+    root_uri
+        .and_then(|uri| uri.to_file_path().ok())
+        .unwrap_or_else(|| path_dedot::CWD.to_path_buf())
+}
+
+/// Read the settings for the workspace. As on the command line, configuration files in
+/// subdirectories are also used, unless a configuration file was given explicitly.
+fn load_resolver(root: &Path, config_file: Option<PathBuf>) -> Result<Resolver> {
+    let hierarchical = config_file.is_none();
+    let config_file = match config_file {
+        Some(config_file) => Some(config_file),
+        None => find_settings_toml(root)?,
+    };
+    let options = match config_file {
+        Some(_) => parse_config_file(&config_file)?,
+        None => Options::default(),
+    };
+    let project_root = configuration::project_root(root)?;
+    let args = CheckArgs::default();
+    let settings =
+        Configuration::from_options(options, &project_root).into_settings(&project_root, &args)?;
+    Ok(Resolver::from_settings(
+        settings,
+        config_file,
+        hierarchical,
+        &args,
+    ))
+}
+
+fn handle_request(session: &mut Session, request: Request) -> Response {
+    match request.method.as_str() {
+        CodeActionRequest::METHOD => {
+            let result = serde_json::from_value::<CodeActionParams>(request.params)
+                .context("Invalid code action parameters")
+                .and_then(|params| session.code_actions(&params.text_document.uri, params.range));
+            match result {
+                Ok(actions) => Response::new_ok(request.id, actions),
+                Err(err) => {
+                    Response::new_err(request.id, ErrorCode::InternalError as i32, err.to_string())
+                }
+            }
+        }
+        method => Response::new_err(
+            request.id,
+            ErrorCode::MethodNotFound as i32,
+            format!("Unsupported request: {method}"),
+        ),
+    }
+}
+
+/// Update the session from a notification, returning the document whose diagnostics
+/// should be republished, if any.
+fn handle_notification(session: &mut Session, notification: Notification) -> Result<Option<Url>> {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: DidOpenTextDocumentParams = serde_json::from_value(notification.params)?;
+            let document = params.text_document;
+            session.open(document.uri.clone(), document.text, document.version)?;
+            Ok(Some(document.uri))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: DidChangeTextDocumentParams = serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            let Some(document) = session.document_mut(&uri) else {
+                warn!("Received changes for unopened document {uri}");
+                return Ok(None);
+            };
+            for change in params.content_changes {
+                document.apply_change(change);
+            }
+            document.version = params.text_document.version;
+            Ok(Some(uri))
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: DidCloseTextDocumentParams = serde_json::from_value(notification.params)?;
+            session.close(&params.text_document.uri);
+            Ok(Some(params.text_document.uri))
+        }
+        method => {
+            debug!("Ignoring notification: {method}");
+            Ok(None)
+        }
+    }
+}
+
+fn publish_diagnostics(connection: &Connection, session: &mut Session, uri: Url) -> Result<()> {
+    let (diagnostics, version) = match session.diagnostics(&uri) {
+        Ok(result) => result,
+        Err(err) => {
+            warn!("Failed to check {uri}: {err:#}");
+            return Ok(());
+        }
+    };
+    let params = PublishDiagnosticsParams::new(uri, diagnostics, version);
+    connection
+        .sender
+        .send(Notification::new(PublishDiagnostics::METHOD.to_string(), params).into())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::{self, JoinHandle};
+
+    use anyhow::Result;
+    use lsp_server::{Connection, Message, Notification, Request, RequestId};
+    use lsp_types::notification::{
+        DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized,
+        Notification as NotificationTrait, PublishDiagnostics,
+    };
+    use lsp_types::request::{CodeActionRequest, Initialize, Request as RequestTrait, Shutdown};
+    use lsp_types::{
+        CodeActionContext, CodeActionOrCommand, CodeActionParams, DiagnosticSeverity,
+        DidChangeTextDocumentParams, DidOpenTextDocumentParams, InitializeParams,
+        InitializedParams, NumberOrString, Position, PublishDiagnosticsParams, Range,
+        TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem, Url,
+        VersionedTextDocumentIdentifier,
+    };
+    use serde_json::Value;
+    use tempfile::TempDir;
+
+    use super::run;
+
+    /// A minimal LSP client, talking to a server running in another thread.
+    struct Client {
+        connection: Connection,
+        next_id: i32,
+    }
+
+    impl Client {
+        /// Start a server for the workspace at `root`, and initialise it.
+        fn start(root: Url) -> Result<(Self, JoinHandle<Result<()>>)> {
+            let (server, client) = Connection::memory();
+            let server = thread::spawn(move || run(server, None));
+            let mut client = Client {
+                connection: client,
+                next_id: 0,
+            };
+
+            #[allow(deprecated)]
+            client.request::<Initialize>(InitializeParams {
+                root_uri: Some(root),
+                ..InitializeParams::default()
+            })?;
+            client.notify::<Initialized>(InitializedParams {})?;
+            Ok((client, server))
+        }
+
+        fn shutdown(mut self, server: JoinHandle<Result<()>>) -> Result<()> {
+            self.request::<Shutdown>(())?;
+            self.notify::<Exit>(())?;
+            server.join().unwrap()
+        }
+
+        fn request<R: RequestTrait>(&mut self, params: R::Params) -> Result<Value> {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            self.connection
+                .sender
+                .send(Request::new(id.clone(), R::METHOD.to_string(), params).into())?;
+            for message in &self.connection.receiver {
+                if let Message::Response(response) = message {
+                    assert_eq!(response.id, id);
+                    assert!(response.error.is_none(), "{:?}", response.error);
+                    return Ok(response.result.unwrap_or_default());
+                }
+            }
+            anyhow::bail!("Server disconnected")
+        }
+
+        fn notify<N: NotificationTrait>(&self, params: N::Params) -> Result<()> {
+            self.connection
+                .sender
+                .send(Notification::new(N::METHOD.to_string(), params).into())?;
+            Ok(())
+        }
+
+        fn diagnostics(&self) -> Result<PublishDiagnosticsParams> {
+            for message in &self.connection.receiver {
+                if let Message::Notification(notification) = message {
+                    if notification.method == PublishDiagnostics::METHOD {
+                        return Ok(serde_json::from_value(notification.params)?);
+                    }
+                }
+            }
+            anyhow::bail!("Server disconnected")
+        }
+    }
+
+    #[test]
+    fn diagnostics_and_code_actions() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        std::fs::write(
+            tempdir.path().join("fortitude.toml"),
+            "[check]\nselect = [\"C001\", \"S061\"]\n\n[check.severity]\n\"S\" = \"warning\"\n",
+        )?;
+        let root = Url::from_directory_path(tempdir.path()).unwrap();
+        let uri = root.join("test.f90")?;
+
+        let (mut client, server) = Client::start(root)?;
+
+        // Invalid notifications are logged, rather than stopping the server
+        client.connection.sender.send(
+            Notification::new(
+                DidOpenTextDocument::METHOD.to_string(),
+                serde_json::json!({}),
+            )
+            .into(),
+        )?;
+
+        client.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri.clone(),
+                "fortran".to_string(),
+                1,
+                "program test\nend program\n".to_string(),
+            ),
+        })?;
+        let published = client.diagnostics()?;
+        assert_eq!(published.uri, uri);
+        assert_eq!(published.version, Some(1));
+        let summary: Vec<_> = published
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.code.clone().unwrap(),
+                    diagnostic.range.start.line,
+                    diagnostic.message.clone(),
+                )
+            })
+            .collect();
+        insta::assert_debug_snapshot!(summary, @r#"
+        [
+            (
+                String(
+                    "C001",
+                ),
+                0,
+                "program missing 'implicit none'",
+            ),
+            (
+                String(
+                    "S061",
+                ),
+                1,
+                "end statement should be named.",
+            ),
+        ]
+        "#);
+
+        let severities: Vec<_> = published
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.severity.unwrap())
+            .collect();
+        assert_eq!(
+            severities,
+            [DiagnosticSeverity::ERROR, DiagnosticSeverity::WARNING]
+        );
+
+        let actions = client.request::<CodeActionRequest>(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            range: Range::new(Position::new(1, 0), Position::new(1, 11)),
+            context: CodeActionContext::default(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })?;
+        let actions: Vec<CodeActionOrCommand> = serde_json::from_value(actions)?;
+        let actions: Vec<_> = actions
+            .into_iter()
+            .map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => {
+                    let edits = action.edit.unwrap().changes.unwrap().remove(&uri).unwrap();
+                    let edits: Vec<_> = edits
+                        .into_iter()
+                        .map(|edit| (edit.range.start.line, edit.new_text))
+                        .collect();
+                    (action.title, edits)
+                }
+                CodeActionOrCommand::Command(command) => (command.title, vec![]),
+            })
+            .collect();
+        insta::assert_debug_snapshot!(actions, @r#"
+        [
+            (
+                "Fortitude (S061): Write as 'end program test'.",
+                [
+                    (
+                        1,
+                        "end program test",
+                    ),
+                ],
+            ),
+            (
+                "Fortitude (S061): Allow `unnamed-end-statement` for this statement",
+                [
+                    (
+                        1,
+                        "! allow(unnamed-end-statement)\n",
+                    ),
+                ],
+            ),
+        ]
+        "#);
+
+        client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+            content_changes: vec![
+                TextDocumentContentChangeEvent {
+                    range: Some(Range::new(Position::new(1, 11), Position::new(1, 11))),
+                    range_length: None,
+                    text: " test".to_string(),
+                },
+                TextDocumentContentChangeEvent {
+                    range: Some(Range::new(Position::new(1, 0), Position::new(1, 0))),
+                    range_length: None,
+                    text: "implicit none\n".to_string(),
+                },
+            ],
+        })?;
+        let published = client.diagnostics()?;
+        assert_eq!(published.version, Some(2));
+        assert_eq!(published.diagnostics, vec![]);
+
+        client.shutdown(server)
+    }
+
+    #[test]
+    fn nested_config_files() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        let legacy = tempdir.path().join("legacy");
+        std::fs::create_dir(&legacy)?;
+        std::fs::write(
+            tempdir.path().join("fortitude.toml"),
+            "[check]\nselect = [\"C001\", \"S061\"]\n",
+        )?;
+        std::fs::write(
+            legacy.join("fortitude.toml"),
+            "extend = \"../fortitude.toml\"\n\n[check]\nignore = [\"C001\"]\n",
+        )?;
+        let root = Url::from_directory_path(tempdir.path()).unwrap();
+        let uri = root.join("legacy/test.f90")?;
+
+        let (client, server) = Client::start(root)?;
+
+        client.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri.clone(),
+                "fortran".to_string(),
+                1,
+                "program test\nend program\n".to_string(),
+            ),
+        })?;
+        let published = client.diagnostics()?;
+        assert_eq!(published.uri, uri);
+        let codes: Vec<_> = published
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.code.unwrap())
+            .collect();
+        assert_eq!(codes, [NumberOrString::String("S061".to_string())]);
+
+        client.shutdown(server)
+    }
+
+    #[test]
+    fn preprocessor_branch_changes() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        std::fs::write(
+            tempdir.path().join("fortitude.toml"),
+            "[check]\nselect = [\"C001\"]\npreprocess = true\n",
+        )?;
+        let root = Url::from_directory_path(tempdir.path()).unwrap();
+        let uri = root.join("test.F90")?;
+        let (client, server) = Client::start(root)?;
+
+        client.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri.clone(),
+                "fortran".to_string(),
+                1,
+                "program test\n  implicit none\nend program test\n".to_string(),
+            ),
+        })?;
+        assert_eq!(client.diagnostics()?.diagnostics, vec![]);
+
+        // Surrounding a line with a conditional changes text outside of the edits, so
+        // the old tree can't be reused
+        let insert = |line, text: &str| TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(line, 0), Position::new(line, 0))),
+            range_length: None,
+            text: text.to_string(),
+        };
+        client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+            content_changes: vec![insert(2, "#endif\n"), insert(1, "#if 0\n")],
+        })?;
+        let codes: Vec<_> = client
+            .diagnostics()?
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.code.unwrap())
+            .collect();
+        assert_eq!(codes, [NumberOrString::String("C001".to_string())]);
+
+        client.shutdown(server)
+    }
+
+    #[test]
+    fn allow_custom_rule() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        std::fs::write(
+            tempdir.path().join("fortitude.toml"),
+            r#"
+[check]
+select = ["ACME001"]
+
+[check.custom-rules]
+no-stop = { code = "ACME001", query = "(stop_statement) @violation", message = "Don't stop" }
+"#,
+        )?;
+        let root = Url::from_directory_path(tempdir.path()).unwrap();
+        let uri = root.join("test.f90")?;
+        let (mut client, server) = Client::start(root)?;
+
+        client.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri.clone(),
+                "fortran".to_string(),
+                1,
+                "program test\n  implicit none\n  stop\nend program test\n".to_string(),
+            ),
+        })?;
+        let codes: Vec<_> = client
+            .diagnostics()?
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.code.unwrap())
+            .collect();
+        assert_eq!(codes, [NumberOrString::String("ACME001".to_string())]);
+
+        // Custom rules are allowed by their own name
+        let actions = client.request::<CodeActionRequest>(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            range: Range::new(Position::new(2, 2), Position::new(2, 6)),
+            context: CodeActionContext::default(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })?;
+        let actions: Vec<CodeActionOrCommand> = serde_json::from_value(actions)?;
+        let actions: Vec<_> = actions
+            .into_iter()
+            .map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => {
+                    let edits = action.edit.unwrap().changes.unwrap().remove(&uri).unwrap();
+                    let edits: Vec<_> = edits.into_iter().map(|edit| edit.new_text).collect();
+                    (action.title, edits)
+                }
+                CodeActionOrCommand::Command(command) => (command.title, vec![]),
+            })
+            .collect();
+        assert_eq!(
+            actions,
+            [(
+                "Fortitude (ACME001): Allow `no-stop` for this statement".to_string(),
+                vec!["  ! allow(no-stop)\n".to_string()]
+            )]
+        );
+
+        client.shutdown(server)
+    }
+}
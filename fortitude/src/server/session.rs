@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, DiagnosticSeverity,
+    NumberOrString, Range, TextDocumentContentChangeEvent, TextEdit, Url, WorkspaceEdit,
+};
+use ruff_diagnostics::{Applicability, Edit};
+use ruff_source_file::{SourceFile, SourceFileBuilder};
+use ruff_text_size::Ranged;
+use rustc_hash::FxHashMap;
+use tree_sitter::{Parser, Tree};
+
+use crate::allow_comments::allow_comment_edit;
+use crate::check::{apply_per_file_ignores, check_path, parse_source};
+use crate::fixed_form::is_fixed_form;
+use crate::message::DiagnosticMessage;
+use crate::resolver::Resolver;
+use crate::rules::Rule;
+use crate::severity::Severity;
+
+use super::convert::{apply_change, to_lsp_range};
+
+/// An open text document, as last sent by the client.
+pub(crate) struct Document {
+    text: String,
+    pub(crate) version: i32,
+    /// The syntax tree from the last check, edited to match any changes made since so
+    /// that only the changed regions need to be re-parsed
+    tree: Option<Tree>,
+    /// The results of checking the current text, if it hasn't changed since
+    checked: Option<Checked>,
+}
+
+impl Document {
+    /// Apply a change sent by the client.
+    pub(crate) fn apply_change(&mut self, change: TextDocumentContentChangeEvent) {
+        match apply_change(&mut self.text, change) {
+            Some(edit) => {
+                if let Some(tree) = &mut self.tree {
+                    tree.edit(&edit);
+                }
+            }
+            None => self.tree = None,
+        }
+        self.checked = None;
+    }
+}
+
+/// The results of checking a [`Document`].
+struct Checked {
+    file: SourceFile,
+    messages: Vec<DiagnosticMessage>,
+}
+
+/// State of the language server: the resolved settings and all open documents.
+pub(crate) struct Session {
+    resolver: Resolver,
+    documents: FxHashMap<Url, Document>,
+}
+
+impl Session {
+    pub(crate) fn new(resolver: Resolver) -> Self {
+        Self {
+            resolver,
+            documents: FxHashMap::default(),
+        }
+    }
+
+    /// Open a document, reading any configuration files in the directories above it.
+    pub(crate) fn open(&mut self, uri: Url, text: String, version: i32) -> Result<()> {
+        self.resolver.discover([&document_path(&uri)])?;
+        self.documents.insert(
+            uri,
+            Document {
+                text,
+                version,
+                tree: None,
+                checked: None,
+            },
+        );
+        Ok(())
+    }
+
+    pub(crate) fn close(&mut self, uri: &Url) {
+        self.documents.remove(uri);
+    }
+
+    pub(crate) fn document_mut(&mut self, uri: &Url) -> Option<&mut Document> {
+        self.documents.get_mut(uri)
+    }
+
+    /// Check an open document, returning the diagnostics to publish and the document
+    /// version they apply to.
+    pub(crate) fn diagnostics(&mut self, uri: &Url) -> Result<(Vec<Diagnostic>, Option<i32>)> {
+        let Some((document, checked)) = self.check(uri)? else {
+            return Ok((vec![], None));
+        };
+        let diagnostics = checked
+            .messages
+            .iter()
+            .map(|message| to_lsp_diagnostic(message, &checked.file))
+            .collect();
+        Ok((diagnostics, Some(document.version)))
+    }
+
+    /// Create quick fixes for the diagnostics in an open document that overlap `range`.
+    /// Each diagnostic may be fixed using its own fix, if it has one, or silenced with
+    /// an allow comment.
+    pub(crate) fn code_actions(
+        &mut self,
+        uri: &Url,
+        range: Range,
+    ) -> Result<Vec<CodeActionOrCommand>> {
+        let required_applicability = self
+            .resolver
+            .resolve(&document_path(uri))
+            .settings
+            .check
+            .unsafe_fixes
+            .required_applicability();
+        let Some((document, checked)) = self.check(uri)? else {
+            return Ok(vec![]);
+        };
+        let tree = document
+            .tree
+            .as_ref()
+            .context("Document hasn't been parsed")?;
+
+        let mut actions = Vec::new();
+        for message in &checked.messages {
+            let diagnostic = to_lsp_diagnostic(message, &checked.file);
+            if !overlaps(&diagnostic.range, &range) {
+                continue;
+            }
+            let Some(rule) = message.rule() else {
+                continue;
+            };
+            let code = message.code();
+
+            if let Some(fix) = message.fix() {
+                if fix.applies(required_applicability) {
+                    let title = message.suggestion().unwrap_or(message.body());
+                    actions.push(quick_fix(
+                        format!("Fortitude ({code}): {title}"),
+                        uri,
+                        fix.edits(),
+                        &checked.file,
+                        &diagnostic,
+                        fix.applicability() == Applicability::Safe,
+                    ));
+                }
+            }
+
+            if rule == Rule::SyntaxError {
+                continue;
+            }
+            // Custom rules are allowed by their own name, rather than the rule they share
+            let name = message.rule_name();
+            if let Some(edit) =
+                allow_comment_edit(&[name], message.range(), &tree.root_node(), &checked.file)
+            {
+                actions.push(quick_fix(
+                    format!("Fortitude ({code}): Allow `{name}` for this statement"),
+                    uri,
+                    &[edit],
+                    &checked.file,
+                    &diagnostic,
+                    false,
+                ));
+            }
+        }
+        Ok(actions)
+    }
+
+    /// Check an open document, reusing the results of the last check if it hasn't
+    /// changed since.
+    fn check(&mut self, uri: &Url) -> Result<Option<(&Document, &Checked)>> {
+        let Some(document) = self.documents.get_mut(uri) else {
+            return Ok(None);
+        };
+        if document.checked.is_none() {
+            let path = document_path(uri);
+            let resolved = self.resolver.resolve(&path);
+            let settings = &resolved.settings;
+            let file =
+                SourceFileBuilder::new(path.to_string_lossy(), document.text.as_str()).finish();
+
+            // Only the regions changed since the last check are re-parsed. As when fixing,
+            // fixed-form and preprocessed source are always parsed from scratch, as an
+            // edit can change how lines outside of it are converted, or which branches of
+            // conditionals are blanked out.
+            let old_tree = document
+                .tree
+                .as_ref()
+                .filter(|_| !(is_fixed_form(&path, &settings.check) || settings.check.preprocess));
+            let mut parser = Parser::new();
+            parser
+                .set_language(&tree_sitter_fortran::LANGUAGE.into())
+                .context("Error loading Fortran grammar")?;
+            let tree = parser
+                .parse(
+                    parse_source(&path, file.source_text(), &settings.check).as_ref(),
+                    old_tree,
+                )
+                .context("Failed to parse")?;
+
+            let violations = check_path(
+                &settings.check.rules,
+                &resolved.path_rules,
+                &resolved.text_rules,
+                &resolved.ast_entrypoints,
+                &path,
+                &file,
+                settings,
+                &tree,
+                settings.check.ignore_allow_comments,
+            );
+            let mut messages = violations
+                .into_iter()
+                .map(|violation| DiagnosticMessage::from_violation(&file, violation))
+                .collect();
+            apply_per_file_ignores(&mut messages, &path, settings);
+            for message in &mut messages {
+                message.set_severity(settings.check.severity.get(message));
+            }
+
+            document.tree = Some(tree);
+            document.checked = Some(Checked { file, messages });
+        }
+        let document = &self.documents[uri];
+        Ok(document.checked.as_ref().map(|checked| (document, checked)))
+    }
+}
+
+/// The path of a document, used to find the settings that apply to it.
+fn document_path(uri: &Url) -> PathBuf {
+    uri.to_file_path()
+        .unwrap_or_else(|()| PathBuf::from(uri.path()))
+}
+
+fn to_lsp_diagnostic(message: &DiagnosticMessage, file: &SourceFile) -> Diagnostic {
+    let severity = match message.severity() {
+        Severity::Info => DiagnosticSeverity::INFORMATION,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Error => DiagnosticSeverity::ERROR,
+    };
+    Diagnostic {
+        range: to_lsp_range(message.range(), file),
+        severity: Some(severity),
+        code: Some(NumberOrString::String(message.code().to_string())),
+        source: Some("fortitude".to_string()),
+        message: message.body().to_string(),
+        ..Diagnostic::default()
+    }
+}
+
+fn quick_fix(
+    title: String,
+    uri: &Url,
+    edits: &[Edit],
+    file: &SourceFile,
+    diagnostic: &Diagnostic,
+    is_preferred: bool,
+) -> CodeActionOrCommand {
+    let edits = edits
+        .iter()
+        .map(|edit| TextEdit {
+            range: to_lsp_range(edit.range(), file),
+            new_text: edit.content().unwrap_or_default().to_string(),
+        })
+        .collect();
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..WorkspaceEdit::default()
+        }),
+        is_preferred: is_preferred.then_some(true),
+        ..CodeAction::default()
+    })
+}
+
+fn overlaps(a: &Range, b: &Range) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
@@ -3,30 +3,41 @@
 // SPDX-License-Identifier: MIT
 
 /// A collection of user-modifiable settings. Should be expanded as new features are added.
+use std::collections::BTreeMap;
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use clap::builder::{PossibleValue, TypedValueParser, ValueParserFactory};
+use clap::ValueEnum;
 use lazy_static::lazy_static;
 use path_absolutize::path_dedot;
+use ruff_cache::{CacheKey, CacheKeyHasher};
 use ruff_diagnostics::Applicability;
 use ruff_macros::CacheKey;
 use serde::{de, Deserialize, Deserializer, Serialize};
 use strum::IntoEnumIterator;
 
+use crate::cache::DEFAULT_CACHE_DIR;
+use crate::cli::CheckArgs;
+use crate::configuration::{load_options, Configuration};
+use crate::custom_rules::CustomRules;
 use crate::display_settings;
-use crate::fs::{FilePatternSet, EXCLUDE_BUILTINS, FORTRAN_EXTS};
+use crate::fs::{normalize_path, FilePatternSet, EXCLUDE_BUILTINS, FIXED_FORM_EXTS, FORTRAN_EXTS};
+use crate::options::Options;
 use crate::registry::Rule;
 use crate::rule_selector::{CompiledPerFileIgnoreList, PreviewOptions, RuleSelector};
 use crate::rule_table::RuleTable;
-use crate::rules::correctness::exit_labels;
+use crate::rules::correctness::{exit_labels, magic_numbers};
 use crate::rules::portability::{self};
 use crate::rules::style::{keywords, strings};
+use crate::severity::SeverityTable;
 
 #[derive(Debug)]
 pub struct Settings {
     pub check: CheckSettings,
     pub file_resolver: FileResolverSettings,
+    pub format: FormatSettings,
 }
 
 impl Default for Settings {
@@ -35,6 +46,7 @@ impl Default for Settings {
         Self {
             check: CheckSettings::new(project_root),
             file_resolver: FileResolverSettings::new(project_root),
+            format: FormatSettings::default(),
         }
     }
 }
@@ -47,18 +59,67 @@ impl fmt::Display for Settings {
             fields = [
                 self.check         | nested,
                 self.file_resolver | nested,
+                self.format        | nested,
             ]
         }
         Ok(())
     }
 }
 
+/// Builds [`Settings`] from [`Options`], in the same way as from a configuration file,
+/// for use with [`Linter`](crate::linter::Linter).
+#[derive(Debug, Default)]
+pub struct SettingsBuilder {
+    options: Options,
+    project_root: Option<PathBuf>,
+}
+
+impl SettingsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use these options, replacing any set previously
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Read options from a `fortitude.toml` or `fpm.toml` file, following any `extend`
+    /// settings. Unless set with [`project_root`](Self::project_root), the directory
+    /// containing the file is used as the project root.
+    pub fn config_file<P: AsRef<Path>>(mut self, path: P) -> anyhow::Result<Self> {
+        let path = normalize_path(path);
+        self.options = load_options(&path)?;
+        if self.project_root.is_none() {
+            self.project_root = path.parent().map(Path::to_path_buf);
+        }
+        Ok(self)
+    }
+
+    /// The directory that relative paths in the options are resolved against. Defaults
+    /// to the current working directory.
+    pub fn project_root<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.project_root = Some(normalize_path(path));
+        self
+    }
+
+    pub fn build(self) -> anyhow::Result<Settings> {
+        let project_root = self
+            .project_root
+            .unwrap_or_else(|| path_dedot::CWD.to_path_buf());
+        Configuration::from_options(self.options, &project_root)
+            .into_settings(&project_root, &CheckArgs::default())
+    }
+}
+
 #[derive(Debug)]
 pub struct CheckSettings {
     pub project_root: PathBuf,
 
     pub rules: RuleTable,
     pub per_file_ignores: CompiledPerFileIgnoreList,
+    pub severity: SeverityTable,
 
     pub line_length: usize,
 
@@ -67,13 +128,21 @@ pub struct CheckSettings {
     pub show_fixes: bool,
     pub unsafe_fixes: UnsafeFixes,
     pub output_format: OutputFormat,
+    pub output_files: Vec<OutputTarget>,
     pub progress_bar: ProgressBar,
     pub preview: PreviewMode,
     pub ignore_allow_comments: IgnoreAllowComments,
+    pub cache_dir: PathBuf,
+    pub baseline: Option<PathBuf>,
+    pub fixed_form_extensions: Vec<String>,
+    pub preprocess: bool,
+    pub define: BTreeMap<String, String>,
+    pub custom_rules: CustomRules,
 
     // Individual rule settings
     pub exit_unlabelled_loops: exit_labels::settings::Settings,
     pub keyword_whitespace: keywords::settings::Settings,
+    pub magic_numbers: magic_numbers::settings::Settings,
     pub strings: strings::settings::Settings,
     pub portability: portability::settings::Settings,
 }
@@ -87,17 +156,26 @@ impl CheckSettings {
                 .flat_map(|selector| selector.rules(&PreviewOptions::default()))
                 .collect(),
             per_file_ignores: CompiledPerFileIgnoreList::default(),
+            severity: SeverityTable::default(),
             line_length: 100,
             fix: false,
             fix_only: false,
             show_fixes: false,
             unsafe_fixes: UnsafeFixes::default(),
             output_format: OutputFormat::default(),
+            output_files: Vec::new(),
             progress_bar: ProgressBar::default(),
             preview: PreviewMode::default(),
             ignore_allow_comments: IgnoreAllowComments::default(),
+            cache_dir: project_root.join(DEFAULT_CACHE_DIR),
+            baseline: None,
+            fixed_form_extensions: FIXED_FORM_EXTS.iter().map(|ext| ext.to_string()).collect(),
+            preprocess: false,
+            define: BTreeMap::new(),
+            custom_rules: CustomRules::default(),
             exit_unlabelled_loops: exit_labels::settings::Settings::default(),
             keyword_whitespace: keywords::settings::Settings::default(),
+            magic_numbers: magic_numbers::settings::Settings::default(),
             strings: strings::settings::Settings::default(),
             portability: portability::settings::Settings::default(),
         }
@@ -114,13 +192,21 @@ impl fmt::Display for CheckSettings {
                 self.project_root | path,
                 self.rules | nested,
                 self.per_file_ignores,
+                self.severity,
                 self.line_length,
                 self.fix,
                 self.fix_only,
                 self.show_fixes,
                 self.output_format,
+                self.output_files | array,
                 self.progress_bar,
                 self.preview,
+                self.cache_dir | path,
+                self.baseline | debug,
+                self.fixed_form_extensions | array,
+                self.preprocess,
+                self.define | debug,
+                self.custom_rules,
             ]
         }
         writeln!(f, "\n# Individual rules")?;
@@ -130,6 +216,7 @@ impl fmt::Display for CheckSettings {
             fields = [
                 self.exit_unlabelled_loops | nested,
                 self.keyword_whitespace | nested,
+                self.magic_numbers | nested,
                 self.strings | nested,
                 self.portability | nested,
             ]
@@ -137,6 +224,32 @@ impl fmt::Display for CheckSettings {
         Ok(())
     }
 }
+
+/// Only settings that can change the diagnostics raised for a file contribute to the
+/// key, so that the same cache can be reused between, for example, `--fix` and
+/// `--no-fix` runs.
+impl CacheKey for CheckSettings {
+    fn cache_key(&self, state: &mut CacheKeyHasher) {
+        self.project_root.cache_key(state);
+        self.rules.cache_key(state);
+        self.per_file_ignores.cache_key(state);
+        self.line_length.cache_key(state);
+        self.preview.cache_key(state);
+        self.ignore_allow_comments.cache_key(state);
+        self.fixed_form_extensions.cache_key(state);
+        self.preprocess.cache_key(state);
+        self.define.cache_key(state);
+        self.custom_rules.cache_key(state);
+
+        // Individual rule settings
+        self.exit_unlabelled_loops.cache_key(state);
+        self.keyword_whitespace.cache_key(state);
+        self.magic_numbers.cache_key(state);
+        self.strings.cache_key(state);
+        self.portability.cache_key(state);
+    }
+}
+
 #[derive(Debug, CacheKey)]
 pub struct FileResolverSettings {
     pub excludes: FilePatternSet,
@@ -179,6 +292,66 @@ impl FileResolverSettings {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct FormatSettings {
+    pub indent_width: usize,
+    pub continuation_indent: usize,
+    pub keyword_case: KeywordCase,
+    pub operator_spacing: bool,
+}
+
+impl Default for FormatSettings {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            continuation_indent: 4,
+            keyword_case: KeywordCase::default(),
+            operator_spacing: true,
+        }
+    }
+}
+
+impl fmt::Display for FormatSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "\n# Format Settings")?;
+        display_settings! {
+            formatter = f,
+            namespace = "format",
+            fields = [
+                self.indent_width,
+                self.continuation_indent,
+                self.keyword_case,
+                self.operator_spacing,
+            ]
+        }
+        Ok(())
+    }
+}
+
+/// How the formatter should write keywords
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug, Hash, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeywordCase {
+    #[default]
+    Lower,
+    Upper,
+    Preserve,
+}
+
+impl fmt::Display for KeywordCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Lower => "lower",
+                Self::Upper => "upper",
+                Self::Preserve => "preserve",
+            }
+        )
+    }
+}
+
 /// Toggle for rules still in preview
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, CacheKey, is_macro::Is)]
 pub enum PreviewMode {
@@ -265,7 +438,7 @@ impl UnsafeFixes {
     }
 }
 
-#[derive(Debug, Copy, Clone, Hash, Default, is_macro::Is)]
+#[derive(Debug, Copy, Clone, Hash, Default, CacheKey, is_macro::Is)]
 pub enum IgnoreAllowComments {
     Enabled,
     #[default]
@@ -414,6 +587,10 @@ pub enum OutputFormat {
     Rdjson,
     Azure,
     Sarif,
+    Checkstyle,
+    Codeclimate,
+    Sonarqube,
+    Html,
 }
 
 impl fmt::Display for OutputFormat {
@@ -431,7 +608,127 @@ impl fmt::Display for OutputFormat {
             Self::Rdjson => write!(f, "rdjson"),
             Self::Azure => write!(f, "azure"),
             Self::Sarif => write!(f, "sarif"),
+            Self::Checkstyle => write!(f, "checkstyle"),
+            Self::Codeclimate => write!(f, "codeclimate"),
+            Self::Sonarqube => write!(f, "sonarqube"),
+            Self::Html => write!(f, "html"),
+        }
+    }
+}
+
+/// An output format, optionally followed by the file to write it to, as
+/// `format[:path]`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputTarget {
+    pub format: OutputFormat,
+    pub path: Option<PathBuf>,
+}
+
+impl OutputTarget {
+    const EXPECTED_PATTERN: &'static str = "<OutputFormat>[:<Path>] pattern";
+}
+
+impl fmt::Display for OutputTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = self
+            .format
+            .to_possible_value()
+            .expect("No output formats are skipped");
+        write!(f, "{}", format.get_name())?;
+        if let Some(path) = &self.path {
+            write!(f, ":{}", path.display())?;
         }
+        Ok(())
+    }
+}
+
+impl Serialize for OutputTarget {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for OutputTarget {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let str_result = String::deserialize(deserializer)?;
+        Self::from_str(str_result.as_str()).map_err(|_| {
+            de::Error::invalid_value(
+                de::Unexpected::Str(str_result.as_str()),
+                &Self::EXPECTED_PATTERN,
+            )
+        })
+    }
+}
+
+impl FromStr for OutputTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Output formats never contain a colon, but paths on Windows might
+        let (format, path) = match s.split_once(':') {
+            Some((format, path)) => {
+                let path = path.trim();
+                if path.is_empty() {
+                    anyhow::bail!("Expected a path after `{format}:`");
+                }
+                (format, Some(PathBuf::from(path)))
+            }
+            None => (s, None),
+        };
+        let format = format.trim();
+        let format = <OutputFormat as ValueEnum>::from_str(format, false)
+            .map_err(|_| anyhow::anyhow!("Unknown output format `{format}`"))?;
+        Ok(Self { format, path })
+    }
+}
+
+/// Parses `--output-format`, while listing the available formats in the help
+#[derive(Clone)]
+pub struct OutputTargetParser;
+
+impl ValueParserFactory for OutputTarget {
+    type Parser = OutputTargetParser;
+
+    fn value_parser() -> Self::Parser {
+        OutputTargetParser
+    }
+}
+
+impl TypedValueParser for OutputTargetParser {
+    type Value = OutputTarget;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &std::ffi::OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        let value = value
+            .to_str()
+            .ok_or_else(|| clap::Error::new(clap::error::ErrorKind::InvalidUtf8))?;
+
+        value.parse().map_err(|err| {
+            let arg = arg.map_or_else(|| "...".to_string(), ToString::to_string);
+            clap::Error::raw(
+                clap::error::ErrorKind::ValueValidation,
+                format!("invalid value '{value}' for '{arg}': {err}\n"),
+            )
+            .with_cmd(cmd)
+        })
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            OutputFormat::value_variants()
+                .iter()
+                .filter_map(ValueEnum::to_possible_value),
+        ))
     }
 }
 
@@ -439,7 +736,6 @@ impl fmt::Display for OutputFormat {
 pub enum FixMode {
     Generate,
     Apply,
-    #[allow(dead_code)]
     Diff,
 }
 
@@ -0,0 +1,180 @@
+//! How seriously violations of each rule should be treated
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::custom_rules::CustomRules;
+use crate::message::DiagnosticMessage;
+use crate::registry::Rule;
+use crate::rule_selector::RuleSelector;
+
+/// The severity of a violation, in increasing order of importance.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Info,
+    Warning,
+    #[default]
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Info => write!(f, "info"),
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// The severity of every rule, from the `severity` setting. Rules that aren't referred
+/// to by any selector are errors.
+#[derive(Debug, Default)]
+pub struct SeverityTable {
+    selectors: BTreeMap<RuleSelector, Severity>,
+    rules: FxHashMap<Rule, Severity>,
+    /// Custom rules, keyed by code
+    custom_rules: FxHashMap<String, Severity>,
+}
+
+impl SeverityTable {
+    /// Work out the severity of each rule. Where several selectors refer to the same
+    /// rule, the most specific one wins.
+    pub fn resolve(
+        selectors: BTreeMap<RuleSelector, Severity>,
+        custom_rules: &CustomRules,
+    ) -> Self {
+        let mut by_specificity: Vec<_> = selectors
+            .iter()
+            .map(|(selector, severity)| (selector.clone(), *severity))
+            .collect();
+        by_specificity.sort_by_key(|(selector, _)| selector.specificity());
+
+        let mut rules = FxHashMap::default();
+        for (selector, severity) in &by_specificity {
+            for rule in selector.all_rules() {
+                rules.insert(rule, *severity);
+            }
+        }
+
+        let custom_rules = custom_rules
+            .most_specific(&by_specificity)
+            .map(|(code, severity)| (code.to_string(), severity))
+            .collect();
+
+        Self {
+            selectors,
+            rules,
+            custom_rules,
+        }
+    }
+
+    /// The severity of a violation
+    pub fn get(&self, message: &DiagnosticMessage) -> Severity {
+        self.custom_rules
+            .get(message.code())
+            .or_else(|| message.rule().and_then(|rule| self.rules.get(&rule)))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl Display for SeverityTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.selectors.is_empty() {
+            write!(f, "{{}}")?;
+        } else {
+            writeln!(f, "{{")?;
+            for (selector, severity) in &self.selectors {
+                let (prefix, code) = selector.prefix_and_code();
+                writeln!(f, "\t{prefix}{code} = {severity}")?;
+            }
+            write!(f, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    use anyhow::Result;
+    use ruff_diagnostics::{Diagnostic, DiagnosticKind};
+    use ruff_source_file::SourceFileBuilder;
+    use ruff_text_size::TextRange;
+
+    use super::{Severity, SeverityTable};
+    use crate::custom_rules::CustomRules;
+    use crate::message::DiagnosticMessage;
+    use crate::rule_selector::RuleSelector;
+
+    fn message(name: &str) -> DiagnosticMessage {
+        let file = SourceFileBuilder::new("test.f90", "").finish();
+        let diagnostic = Diagnostic::new(
+            DiagnosticKind {
+                name: name.to_string(),
+                body: String::new(),
+                suggestion: None,
+            },
+            TextRange::default(),
+        );
+        DiagnosticMessage::from_ruff(&file, diagnostic)
+    }
+
+    fn table(selectors: &[(&str, Severity)]) -> Result<SeverityTable> {
+        let selectors = selectors
+            .iter()
+            .map(|(selector, severity)| Ok((RuleSelector::from_str(selector)?, *severity)))
+            .collect::<Result<BTreeMap<_, _>>>()?;
+        Ok(SeverityTable::resolve(selectors, &CustomRules::default()))
+    }
+
+    #[test]
+    fn unselected_rules_are_errors() -> Result<()> {
+        let table = table(&[("S", Severity::Warning)])?;
+        assert_eq!(table.get(&message("StarKind")), Severity::Error);
+        assert_eq!(
+            table.get(&message("SuperfluousImplicitNone")),
+            Severity::Warning
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn most_specific_selector_wins() -> Result<()> {
+        let table = table(&[
+            ("ALL", Severity::Info),
+            ("S", Severity::Warning),
+            ("S201", Severity::Error),
+        ])?;
+        assert_eq!(table.get(&message("StarKind")), Severity::Info);
+        assert_eq!(
+            table.get(&message("UnnamedEndStatement")),
+            Severity::Warning
+        );
+        assert_eq!(
+            table.get(&message("SuperfluousImplicitNone")),
+            Severity::Error
+        );
+        Ok(())
+    }
+}
@@ -1,16 +1,19 @@
 use std::io::Write;
+use std::path::PathBuf;
 
 use anyhow::Result;
 use itertools::Itertools;
+use rustc_hash::FxHashSet;
 
 use crate::{fs::get_files, settings::FileResolverSettings};
 
 pub(crate) fn show_files(
     resolver: &FileResolverSettings,
     is_stdin: bool,
+    changed_files: Option<&FxHashSet<PathBuf>>,
     writer: &mut impl Write,
 ) -> Result<()> {
-    let files = get_files(resolver, is_stdin);
+    let files = get_files(resolver, is_stdin, changed_files);
 
     for file in files.into_iter().flatten().sorted_unstable() {
         writeln!(writer, "{}", file.to_string_lossy())?;
@@ -1,15 +1,19 @@
 use std::io::Write;
+use std::path::Path;
 
 use anyhow::Result;
 
-use crate::settings::Settings;
+use crate::resolver::ResolvedSettings;
 
-pub(crate) fn show_settings(settings: &Settings, writer: &mut impl Write) -> Result<()> {
-    writeln!(
-        writer,
-        "Resolved settings for \"{}\"",
-        settings.check.project_root.display()
-    )?;
-    write!(writer, "{settings}")?;
+pub(crate) fn show_settings(
+    path: &Path,
+    resolved: &ResolvedSettings,
+    writer: &mut impl Write,
+) -> Result<()> {
+    writeln!(writer, "Resolved settings for \"{}\"", path.display())?;
+    if let Some(config_file) = &resolved.config_file {
+        writeln!(writer, "Settings path: \"{}\"", config_file.display())?;
+    }
+    write!(writer, "{}", resolved.settings)?;
     Ok(())
 }
@@ -61,6 +61,7 @@ pub(crate) fn test_contents(
         settings,
         FixMode::Generate,
         settings::IgnoreAllowComments::Disabled,
+        false,
     ) {
         Ok(violations) => {
             if violations.messages.is_empty() {
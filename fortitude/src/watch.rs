@@ -0,0 +1,438 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use log::debug;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use path_absolutize::path_dedot;
+use rustc_hash::FxHashSet;
+
+use crate::baseline::Baseline;
+use crate::check::{apply_severity, check_files, fix_mode, is_stdin, printer, CheckResults};
+use crate::cli::{CheckArgs, GlobalConfigArgs};
+use crate::diagnostics::{Diagnostics, FixMap};
+use crate::fs::{self, get_files};
+use crate::logging::LogLevel;
+use crate::message::DiagnosticMessage;
+use crate::project::{project_rule_codes, PROJECT_RULES};
+use crate::resolver::Resolver;
+use crate::settings::FixMode;
+use crate::warn_user;
+
+/// How long to wait for further events after a change, so that a burst of changes,
+/// such as an editor saving several files, only triggers a single re-check.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Names of files that may contain Fortitude's configuration.
+const CONFIG_FILE_NAMES: &[&str] = &["fortitude.toml", "fpm.toml"];
+
+/// Check all files, then keep re-checking them as they change until interrupted.
+pub(crate) fn watch(args: &CheckArgs, global_options: &GlobalConfigArgs) -> Result<ExitCode> {
+    if is_stdin(
+        args.files.as_deref().unwrap_or_default(),
+        args.stdin_filename.as_deref(),
+    ) {
+        bail!("Watch mode can't be used when reading from standard input");
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+
+    let mut session = WatchSession::new(args, global_options)?;
+    // Project rules need every file to be checked together, so can't be updated
+    // incrementally as individual files change
+    if session
+        .resolver
+        .settings()
+        .any(|settings| settings.check.rules.any_enabled(PROJECT_RULES))
+    {
+        warn_user!(
+            "Project rules ({}) aren't checked in watch mode",
+            project_rule_codes()
+        );
+    }
+    session.watch(&mut watcher)?;
+    session.check_all()?;
+    session.report(args, global_options, "Starting linter in watch mode...")?;
+
+    loop {
+        let mut events = vec![rx.recv()??];
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            events.push(event?);
+        }
+
+        let Some(change) = events
+            .iter()
+            .filter_map(|event| session.classify(event))
+            .reduce(Change::merge)
+        else {
+            continue;
+        };
+        debug!("Detected change: {change:?}");
+
+        match change {
+            Change::Configuration => match WatchSession::new(args, global_options) {
+                Ok(new_session) => {
+                    session.unwatch(&mut watcher);
+                    session = new_session;
+                    session.watch(&mut watcher)?;
+                    session.check_all()?;
+                    session.report(args, global_options, "Configuration change detected...")?;
+                }
+                Err(err) => {
+                    print_error(global_options, &err)?;
+                }
+            },
+            Change::Discovery(paths) => {
+                session.discover_files()?;
+                let added = session.untracked_or(&paths);
+                session.check(&added)?;
+                session.report(args, global_options, "File change detected...")?;
+            }
+            Change::Modified(paths) => {
+                let modified = paths
+                    .into_iter()
+                    .filter(|path| session.files.contains(path))
+                    .collect::<Vec<_>>();
+                if modified.is_empty() {
+                    continue;
+                }
+                session.check(&modified)?;
+                session.report(args, global_options, "File change detected...")?;
+            }
+        }
+    }
+}
+
+/// The kind of work needed to respond to a change on disk.
+#[derive(Debug, PartialEq, Eq)]
+enum Change {
+    /// A configuration file changed, so settings must be re-resolved and all files
+    /// re-checked.
+    Configuration,
+    /// Files were created, removed, or renamed, so files must be rediscovered. The paths
+    /// involved should be re-checked if they are still included.
+    Discovery(Vec<PathBuf>),
+    /// Existing files were modified and should be re-checked.
+    Modified(Vec<PathBuf>),
+}
+
+impl Change {
+    /// Combine two changes into one that handles both.
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Configuration, _) | (_, Self::Configuration) => Self::Configuration,
+            (Self::Discovery(mut a), Self::Discovery(b) | Self::Modified(b))
+            | (Self::Modified(b), Self::Discovery(mut a)) => {
+                a.extend(b);
+                Self::Discovery(a)
+            }
+            (Self::Modified(mut a), Self::Modified(b)) => {
+                a.extend(b);
+                Self::Modified(a)
+            }
+        }
+    }
+}
+
+/// Everything needed to re-check files without resolving settings or discovering files
+/// again, along with the latest diagnostics for each file.
+struct WatchSession {
+    resolver: Resolver,
+    fix_mode: FixMode,
+    /// Violations that shouldn't be reported.
+    baseline: Option<Baseline>,
+    /// Files included in the check.
+    files: FxHashSet<PathBuf>,
+    /// Paths registered with the file watcher.
+    watched: Vec<PathBuf>,
+    /// The latest diagnostics for each file.
+    messages: BTreeMap<PathBuf, Vec<DiagnosticMessage>>,
+    /// Fixes applied since the last report.
+    fixed: FixMap,
+}
+
+impl WatchSession {
+    fn new(args: &CheckArgs, global_options: &GlobalConfigArgs) -> Result<Self> {
+        let resolver = Resolver::new(args, global_options)?;
+        let settings = &resolver.root().settings;
+        let baseline = settings
+            .check
+            .baseline
+            .as_deref()
+            .map(|path| Baseline::read(path, &settings.check.project_root))
+            .transpose()?;
+
+        let mut session = Self {
+            fix_mode: fix_mode(args, &settings.check),
+            baseline,
+            files: FxHashSet::default(),
+            watched: Vec::new(),
+            messages: BTreeMap::new(),
+            fixed: FixMap::default(),
+            resolver,
+        };
+        session.discover_files()?;
+        Ok(session)
+    }
+
+    /// Find the files to check, forgetting the results of any that are no longer
+    /// included.
+    fn discover_files(&mut self) -> Result<()> {
+        self.files = get_files(&self.resolver.root().settings.file_resolver, false, None)?
+            .into_iter()
+            .collect();
+        self.resolver.discover(&self.files)?;
+        let files = &self.files;
+        self.messages.retain(|path, _| files.contains(path));
+        Ok(())
+    }
+
+    /// Watch the paths passed on the command line and the configuration file.
+    fn watch(&mut self, watcher: &mut RecommendedWatcher) -> Result<()> {
+        let project = self.resolver.root();
+        let resolver = &project.settings.file_resolver;
+        let roots = if resolver.files.is_empty() {
+            vec![path_dedot::CWD.to_path_buf()]
+        } else {
+            resolver.files.iter().map(fs::normalize_path).collect()
+        };
+        for root in roots {
+            watcher.watch(&root, RecursiveMode::Recursive)?;
+            self.watched.push(root);
+        }
+
+        // Watch the directory containing the configuration file, rather than the file
+        // itself, as editors often save files by replacing them.
+        let config_dir = match &project.config_file {
+            Some(config_file) => config_file.parent().map(Path::to_path_buf),
+            None => Some(project.settings.check.project_root.clone()),
+        };
+        if let Some(config_dir) = config_dir {
+            if !self.watched.iter().any(|root| config_dir.starts_with(root)) {
+                watcher.watch(&config_dir, RecursiveMode::NonRecursive)?;
+                self.watched.push(config_dir);
+            }
+        }
+        Ok(())
+    }
+
+    fn unwatch(&mut self, watcher: &mut RecommendedWatcher) {
+        for path in self.watched.drain(..) {
+            if let Err(err) = watcher.unwatch(&path) {
+                debug!("Failed to stop watching {}: {err}", path.display());
+            }
+        }
+    }
+
+    /// Decide what needs to be done in response to a file system event, if anything.
+    fn classify(&self, event: &Event) -> Option<Change> {
+        let structural = match event.kind {
+            EventKind::Create(_)
+            | EventKind::Remove(_)
+            | EventKind::Modify(ModifyKind::Name(_)) => true,
+            EventKind::Modify(_) | EventKind::Any => false,
+            EventKind::Access(_) | EventKind::Other => return None,
+        };
+
+        if event.paths.iter().any(|path| self.is_config_file(path)) {
+            return Some(Change::Configuration);
+        }
+
+        let paths: Vec<_> = event
+            .paths
+            .iter()
+            .filter(|path| self.files.contains(*path) || self.is_candidate(path))
+            .cloned()
+            .collect();
+        if paths.is_empty() {
+            None
+        } else if structural {
+            Some(Change::Discovery(paths))
+        } else {
+            Some(Change::Modified(paths))
+        }
+    }
+
+    /// Could a change to this path affect which configuration files are used, or what
+    /// they contain?
+    fn is_config_file(&self, path: &Path) -> bool {
+        if self
+            .resolver
+            .config_files()
+            .any(|config_file| config_file == path)
+        {
+            return true;
+        }
+        let is_config_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| CONFIG_FILE_NAMES.contains(&name));
+        // Configuration files can be added in any directory above the working
+        // directory, or in any subdirectory of the project
+        is_config_name
+            && path.parent().is_some_and(|parent| {
+                path_dedot::CWD.starts_with(parent)
+                    || parent.starts_with(&self.resolver.root().settings.check.project_root)
+            })
+    }
+
+    /// Could this path be, or contain, a file that should be checked?
+    fn is_candidate(&self, path: &Path) -> bool {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => self
+                .resolver
+                .root()
+                .settings
+                .file_resolver
+                .file_extensions
+                .iter()
+                .any(|allowed| allowed == ext),
+            // Possibly a directory, which may have been removed
+            None => true,
+        }
+    }
+
+    /// Of the given paths, return those that are checked, along with any checked files
+    /// that don't have results yet.
+    fn untracked_or(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|file| paths.contains(file) || !self.messages.contains_key(*file))
+            .cloned()
+            .collect()
+    }
+
+    fn check_all(&mut self) -> Result<()> {
+        let files: Vec<_> = self.files.iter().cloned().collect();
+        self.messages.clear();
+        self.check(&files)
+    }
+
+    /// Re-check the given files, replacing any previous results for them.
+    fn check(&mut self, files: &[PathBuf]) -> Result<()> {
+        let mut results = check_files(files, &self.resolver, self.fix_mode, None, false)?;
+        apply_severity(&mut results.diagnostics.messages, &self.resolver);
+
+        for file in files {
+            self.messages.insert(file.clone(), Vec::new());
+        }
+        for message in results.diagnostics.messages {
+            self.messages
+                .entry(PathBuf::from(message.filename()))
+                .or_default()
+                .push(message);
+        }
+        self.fixed += results.diagnostics.fixed;
+        Ok(())
+    }
+
+    /// Clear the terminal and print the latest results for all files.
+    fn report(
+        &mut self,
+        args: &CheckArgs,
+        global_options: &GlobalConfigArgs,
+        reason: &str,
+    ) -> Result<()> {
+        let mut stdout = BufWriter::new(io::stdout().lock());
+        if io::stdout().is_terminal() {
+            // Clear the screen and move the cursor to the top left
+            write!(stdout, "\x1B[2J\x1B[1;1H")?;
+        }
+        if global_options.log_level() >= LogLevel::Default {
+            writeln!(stdout, "{} {}", timestamp(), reason.bold())?;
+        }
+
+        let mut messages: Vec<_> = self.messages.values().flatten().cloned().collect();
+        messages.sort_unstable();
+        if let Some(baseline) = &self.baseline {
+            baseline.apply(&mut messages, &[]);
+        }
+        let results = CheckResults {
+            diagnostics: Diagnostics {
+                messages,
+                fixed: std::mem::take(&mut self.fixed),
+                diffs: Vec::new(),
+                indexed: Vec::new(),
+            },
+            files_checked: self.files.len(),
+            files_skipped: 0,
+        };
+
+        let printer = printer(
+            args,
+            &self.resolver.root().settings.check,
+            self.fix_mode,
+            global_options,
+        );
+        if args.statistics {
+            printer.write_statistics(&results, &mut stdout)?;
+        } else {
+            printer.write_once(&results, &mut stdout)?;
+        }
+        printer.write_output_files(&results, &self.resolver.root().settings.check.output_files)?;
+
+        if global_options.log_level() >= LogLevel::Default {
+            writeln!(
+                stdout,
+                "{} {}",
+                timestamp(),
+                "Watching for file changes.".bold()
+            )?;
+        }
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+fn timestamp() -> String {
+    format!("[{}]", chrono::Local::now().format("%H:%M:%S"))
+        .dimmed()
+        .to_string()
+}
+
+fn print_error(global_options: &GlobalConfigArgs, err: &anyhow::Error) -> Result<()> {
+    if global_options.log_level() >= LogLevel::Default {
+        let mut stderr = io::stderr().lock();
+        writeln!(
+            stderr,
+            "{} {} {err:#}",
+            timestamp(),
+            "Failed to reload configuration:".red().bold()
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::Change;
+
+    fn modified(path: &str) -> Change {
+        Change::Modified(vec![PathBuf::from(path)])
+    }
+
+    #[test]
+    fn merge_changes() {
+        assert_eq!(
+            modified("a.f90").merge(modified("b.f90")),
+            Change::Modified(vec![PathBuf::from("a.f90"), PathBuf::from("b.f90")])
+        );
+        assert_eq!(
+            modified("a.f90").merge(Change::Discovery(vec![PathBuf::from("b.f90")])),
+            Change::Discovery(vec![PathBuf::from("b.f90"), PathBuf::from("a.f90")])
+        );
+        assert_eq!(
+            modified("a.f90").merge(Change::Configuration),
+            Change::Configuration
+        );
+    }
+}
@@ -2111,6 +2111,55 @@ end program test
     Ok(())
 }
 
+#[test]
+fn show_statistics_custom_rules() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(
+        tempdir.path().join("fortitude.toml"),
+        r#"
+[check.custom-rules]
+no-stop = { code = "ACME001", query = "(stop_statement) @violation", message = "Use `error stop` instead of `stop`" }
+no-print = { code = "ACME002", query = "(print_statement) @violation", message = "Don't print" }
+"#,
+    )?;
+    fs::write(
+        tempdir.path().join("test.f90"),
+        r#"
+program test
+  implicit none
+  print *, "hello"
+  print *, "world"
+  stop
+end program test
+"#,
+    )?;
+
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("test.f90")
+                         .arg("--select=ACME001,ACME002")
+                         .arg("--statistics")
+                         .current_dir(tempdir.path()),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    2	ACME002	no-print
+    1	ACME001	no-stop
+    fortitude: 1 files scanned.
+    Number of errors: 3
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+
+    ----- stderr -----
+    ");
+
+    Ok(())
+}
+
 /// Check that fixing in stdin mode outputs the fixed file and nothing else.
 #[test]
 fn stdin_fix_mode() -> anyhow::Result<()> {
@@ -2207,3 +2256,1332 @@ end program foo
 
     Ok(())
 }
+
+#[test]
+fn cache_reused_and_invalidated() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let test_file = tempdir.path().join("test.f90");
+    fs::write(
+        &test_file,
+        r#"
+program test
+end program test
+"#,
+    )?;
+
+    let check = || -> anyhow::Result<Command> {
+        let mut command = Command::cargo_bin(BIN_NAME)?;
+        command
+            .arg("check")
+            .arg("--select=C001")
+            .arg("--output-format=concise")
+            .arg("test.f90")
+            .current_dir(tempdir.path());
+        Ok(command)
+    };
+
+    assert_cmd_snapshot!(check()?, @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    test.f90:2:1: C001 program missing 'implicit none'
+    fortitude: 1 files scanned.
+    Number of errors: 1
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+
+    ----- stderr -----
+    ");
+    assert!(tempdir.path().join(".fortitude_cache").is_dir());
+
+    // Second run should give identical results from the cache
+    assert_cmd_snapshot!(check()?, @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    test.f90:2:1: C001 program missing 'implicit none'
+    fortitude: 1 files scanned.
+    Number of errors: 1
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+
+    ----- stderr -----
+    ");
+
+    // Changing the file contents invalidates the cached results
+    fs::write(
+        &test_file,
+        r#"
+program test
+  implicit none
+end program test
+"#,
+    )?;
+    assert_cmd_snapshot!(check()?, @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    fortitude: 1 files scanned.
+    All checks passed!
+
+
+    ----- stderr -----
+    ");
+    Ok(())
+}
+
+#[test]
+fn cache_not_used_for_fixes() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let test_file = tempdir.path().join("test.f90");
+    fs::write(
+        &test_file,
+        r#"
+program test
+  implicit none
+end program
+"#,
+    )?;
+
+    let check = |fix: bool| -> anyhow::Result<Command> {
+        let mut command = Command::cargo_bin(BIN_NAME)?;
+        command
+            .arg("check")
+            .arg("--select=S061")
+            .arg("--output-format=concise")
+            .arg("test.f90")
+            .current_dir(tempdir.path());
+        if fix {
+            command.arg("--fix");
+        }
+        Ok(command)
+    };
+
+    // Populate the cache, then check that `--fix` still applies the cached fixes
+    check(false)?.assert().failure();
+    check(true)?.assert().success();
+    assert_eq!(
+        fs::read_to_string(&test_file)?,
+        r#"
+program test
+  implicit none
+end program test
+"#
+    );
+
+    assert_cmd_snapshot!(check(false)?, @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    fortitude: 1 files scanned.
+    All checks passed!
+
+
+    ----- stderr -----
+    ");
+    Ok(())
+}
+
+#[test]
+fn no_cache() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(
+        tempdir.path().join("test.f90"),
+        "program test\nend program test\n",
+    )?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--no-cache")
+        .arg("test.f90")
+        .current_dir(tempdir.path())
+        .assert()
+        .failure();
+    assert!(!tempdir.path().join(".fortitude_cache").exists());
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--cache-dir=custom_cache")
+        .arg("test.f90")
+        .current_dir(tempdir.path())
+        .assert()
+        .failure();
+    assert!(!tempdir.path().join(".fortitude_cache").exists());
+    assert!(tempdir.path().join("custom_cache").is_dir());
+    Ok(())
+}
+
+#[test]
+fn clean_removes_cache() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(
+        tempdir.path().join("test.f90"),
+        "program test\nend program test\n",
+    )?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("test.f90")
+        .current_dir(tempdir.path())
+        .assert()
+        .failure();
+    assert!(tempdir.path().join(".fortitude_cache").is_dir());
+
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("clean")
+                         .current_dir(tempdir.path()),
+                         @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+
+    ----- stderr -----
+    Removing cache at: .fortitude_cache
+    ");
+    assert!(!tempdir.path().join(".fortitude_cache").exists());
+    Ok(())
+}
+
+#[test]
+fn check_diff() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let test_file = tempdir.path().join("test.f90");
+    let source = r#"program test
+  implicit none
+end program
+"#;
+    fs::write(&test_file, source)?;
+
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--select=S061")
+                         .arg("--diff")
+                         .arg("test.f90")
+                         .current_dir(tempdir.path()),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    --- test.f90
+    +++ test.f90
+    @@ -1,3 +1,3 @@
+     program test
+       implicit none
+    -end program
+    +end program test
+
+
+    ----- stderr -----
+    Would fix 1 error.
+    ");
+
+    // File should be untouched
+    assert_eq!(fs::read_to_string(&test_file)?, source);
+    Ok(())
+}
+
+#[test]
+fn check_diff_multiple_files() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    for name in ["c", "a", "b"] {
+        fs::write(
+            tempdir.path().join(format!("{name}.f90")),
+            format!("program {name}\n  implicit none\nend program\n"),
+        )?;
+    }
+
+    // Diffs are always printed in order of file name, regardless of which file was
+    // checked first
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--select=S061")
+                         .arg("--diff")
+                         .arg("--no-cache")
+                         .arg(".")
+                         .current_dir(tempdir.path()),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    --- a.f90
+    +++ a.f90
+    @@ -1,3 +1,3 @@
+     program a
+       implicit none
+    -end program
+    +end program a
+
+    --- b.f90
+    +++ b.f90
+    @@ -1,3 +1,3 @@
+     program b
+       implicit none
+    -end program
+    +end program b
+
+    --- c.f90
+    +++ c.f90
+    @@ -1,3 +1,3 @@
+     program c
+       implicit none
+    -end program
+    +end program c
+
+
+    ----- stderr -----
+    Would fix 3 errors.
+    ");
+    Ok(())
+}
+
+#[test]
+fn check_diff_no_fixes() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let test_file = tempdir.path().join("test.f90");
+    fs::write(
+        &test_file,
+        r#"program test
+  implicit none
+end program test
+"#,
+    )?;
+
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--select=S061")
+                         .arg("--diff")
+                         .arg("test.f90")
+                         .current_dir(tempdir.path()),
+                         @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+
+    ----- stderr -----
+    ");
+    Ok(())
+}
+
+#[test]
+fn stdin_diff() -> anyhow::Result<()> {
+    let input_file = r#"program test
+  implicit none
+end program
+"#;
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--select=S061")
+                         .arg("--diff")
+                         .arg("--stdin-filename=test.f90")
+                         .pass_stdin(input_file),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    --- test.f90
+    +++ test.f90
+    @@ -1,3 +1,3 @@
+     program test
+       implicit none
+    -end program
+    +end program test
+
+
+    ----- stderr -----
+    Would fix 1 error.
+    ");
+    Ok(())
+}
+
+#[test]
+fn baseline() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let test_file = tempdir.path().join("test.f90");
+    fs::write(
+        &test_file,
+        r#"
+program test
+end program test
+"#,
+    )?;
+
+    let check = || -> anyhow::Result<Command> {
+        let mut command = Command::cargo_bin(BIN_NAME)?;
+        command
+            .arg("check")
+            .arg("--select=C001")
+            .arg("--output-format=concise")
+            .arg("test.f90")
+            .current_dir(tempdir.path());
+        Ok(command)
+    };
+
+    assert_cmd_snapshot!(check()?.arg("--generate-baseline=baseline.json"), @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+
+    ----- stderr -----
+    Wrote 1 violation(s) to baseline file baseline.json
+    ");
+    assert!(tempdir.path().join("baseline.json").is_file());
+
+    fs::write(
+        tempdir.path().join("fortitude.toml"),
+        "[check]\nbaseline = \"baseline.json\"\n",
+    )?;
+
+    // Existing violations are still suppressed after moving, but new ones are reported
+    fs::write(
+        &test_file,
+        r#"
+module mod
+end module mod
+
+program test
+end program test
+"#,
+    )?;
+    assert_cmd_snapshot!(check()?, @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    test.f90:2:1: C001 module missing 'implicit none'
+    fortitude: 1 files scanned.
+    Number of errors: 1
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+
+    ----- stderr -----
+    ");
+    assert_cmd_snapshot!(check()?.arg("--show-fixed-baseline"), @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    No fixed baseline entries found.
+
+    ----- stderr -----
+    ");
+
+    // Fixed violations are reported so they can be removed from the baseline
+    fs::write(
+        &test_file,
+        r#"
+program test
+  implicit none
+end program test
+"#,
+    )?;
+    assert_cmd_snapshot!(check()?.arg("--show-fixed-baseline"), @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    test.f90: C001 `program test`
+    Found 1 fixed baseline entry.
+
+    ----- stderr -----
+    ");
+    Ok(())
+}
+
+#[test]
+fn preprocess() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(
+        tempdir.path().join("test.F90"),
+        r#"
+program test
+#ifdef MPI
+  implicit none
+#else
+  ! no implicit none
+#endif
+end program test
+"#,
+    )?;
+
+    let check = || -> anyhow::Result<Command> {
+        let mut command = Command::cargo_bin(BIN_NAME)?;
+        command
+            .arg("check")
+            .arg("--select=C001")
+            .arg("--output-format=concise")
+            .arg("--preprocess")
+            .arg("test.F90")
+            .current_dir(tempdir.path());
+        Ok(command)
+    };
+
+    assert_cmd_snapshot!(check()?, @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    test.F90:2:1: C001 program missing 'implicit none'
+    fortitude: 1 files scanned.
+    Number of errors: 1
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+
+    ----- stderr -----
+    ");
+    assert_cmd_snapshot!(check()?.arg("-DMPI"), @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    fortitude: 1 files scanned.
+    All checks passed!
+
+
+    ----- stderr -----
+    ");
+
+    // Definitions from the config file are also used
+    fs::write(
+        tempdir.path().join("fortitude.toml"),
+        "[check]\ndefine = [\"MPI=1\"]\n",
+    )?;
+    assert_cmd_snapshot!(check()?, @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    fortitude: 1 files scanned.
+    All checks passed!
+
+
+    ----- stderr -----
+    ");
+    Ok(())
+}
+
+#[test]
+fn fixed_form() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(
+        tempdir.path().join("test.f"),
+        r#"
+      PROGRAM TEST
+C     This comment line is longer than seventy-two columns, which is fine here
+      X = 1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10 + 11 + 12 + 13 + 14 + 15
+      Y = X +
+     &    1
+      END
+"#,
+    )?;
+
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--select=C001,S001,E001")
+                         .arg("--output-format=concise")
+                         .arg("test.f")
+                         .current_dir(tempdir.path()),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    test.f:2:7: C001 program missing 'implicit none'
+    test.f:4:73: S001 line length of 73, exceeds maximum 72
+    fortitude: 1 files scanned.
+    Number of errors: 2
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+
+    ----- stderr -----
+    ");
+    Ok(())
+}
+
+#[test]
+fn preprocess_fix_changes_active_branch() -> anyhow::Result<()> {
+    use predicates::boolean::PredicateBooleanExt;
+
+    let tempdir = TempDir::new()?;
+    fs::write(
+        tempdir.path().join("fortitude.toml"),
+        r##"
+[check.custom-rules]
+no-print = { code = "ACME001", query = "(print_statement) @violation", message = "Don't print", replacement = "#if 0\nprint *, 'start'" }
+no-stop = { code = "ACME002", query = "(stop_statement) @violation", message = "Use `error stop` instead of `stop`" }
+"##,
+    )?;
+    let test_file = tempdir.path().join("test.F90");
+    fs::write(
+        &test_file,
+        r#"program test
+  implicit none
+  print *, 'start'
+  stop
+#endif
+end program test
+"#,
+    )?;
+
+    // The fix moves the `stop` into an inactive branch, so it shouldn't be reported
+    // once the fixed file is checked again
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--select=ACME001,ACME002")
+        .arg("--preprocess")
+        .arg("--fix")
+        .arg("--unsafe-fixes")
+        .arg("--output-format=concise")
+        .arg("test.F90")
+        .current_dir(tempdir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("ACME002").not());
+
+    let expected = r#"program test
+  implicit none
+  #if 0
+print *, 'start'
+  stop
+#endif
+end program test
+"#;
+    assert_eq!(fs::read_to_string(&test_file)?, expected);
+    Ok(())
+}
+
+#[test]
+fn fixed_form_skips_fixes_across_continuations() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(
+        tempdir.path().join("fortitude.toml"),
+        r#"
+[check.custom-rules]
+no-dble = { code = "ACME001", query = '(call_expression (identifier) @name (argument_list (_) @arg) (#match? @name "(?i)^dble$")) @violation', message = "Use `real({arg}, dp)` instead", replacement = "real({arg}, dp)" }
+"#,
+    )?;
+    let test_file = tempdir.path().join("test.f");
+    fs::write(
+        &test_file,
+        r#"
+      PROGRAM TEST
+      X = DBLE(1)
+      Y = DBLE(
+     &  2)
+      END
+"#,
+    )?;
+
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--select=ACME001")
+                         .arg("--fix")
+                         .arg("--unsafe-fixes")
+                         .arg("--output-format=concise")
+                         .arg("test.f")
+                         .current_dir(tempdir.path()),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    test.f:4:11: ACME001 Use `real(2, dp)` instead
+    fortitude: 1 files scanned.
+    Number of errors: 2 (1 fixed, 1 remaining)
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+
+    ----- stderr -----
+    ");
+
+    let expected = r#"
+      PROGRAM TEST
+      X = real(1, dp)
+      Y = DBLE(
+     &  2)
+      END
+"#;
+    assert_eq!(fs::read_to_string(&test_file)?, expected);
+    Ok(())
+}
+
+#[test]
+fn allow_comment_forms() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(
+        tempdir.path().join("test.f90"),
+        r#"
+! fortitude: disable-file(implicit-typing)
+program test
+  integer*4 :: a  ! allow(star-kind)
+  integer*4 :: b
+  ! fortitude: disable(star-kind)
+  integer*4 :: c
+  integer*4 :: d
+  ! fortitude: enable(star-kind)
+  integer*4 :: e
+  ! fortitude: enable(star-kind)
+end program test
+"#,
+    )?;
+
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--select=C001,PORT021,FORT002")
+                         .arg("--output-format=concise")
+                         .arg("test.f90")
+                         .current_dir(tempdir.path()),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    test.f90:5:10: PORT021 'integer*4' uses non-standard syntax
+    test.f90:10:10: PORT021 'integer*4' uses non-standard syntax
+    test.f90:11:23: FORT002 [*] Unused rule `star-kind` in allow comment
+    fortitude: 1 files scanned.
+    Number of errors: 3
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+    [*] 1 fixable with the `--fix` option (2 hidden fixes can be enabled with the `--unsafe-fixes` option).
+
+    ----- stderr -----
+    ");
+    Ok(())
+}
+
+#[test]
+fn add_allow() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let test_file = tempdir.path().join("test.f90");
+    fs::write(
+        &test_file,
+        r#"program test
+  ! allow(literal-kind)
+  integer*4 :: a
+  integer*4 :: b
+end program
+"#,
+    )?;
+
+    let check = || -> anyhow::Result<Command> {
+        let mut command = Command::cargo_bin(BIN_NAME)?;
+        command
+            .arg("check")
+            .arg("--select=C001,PORT011,PORT021,S061")
+            .arg("--output-format=concise")
+            .arg("test.f90")
+            .current_dir(tempdir.path());
+        Ok(command)
+    };
+
+    assert_cmd_snapshot!(check()?.arg("--add-allow"), @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+
+    ----- stderr -----
+    Added or updated 4 allow comment(s)
+    ");
+
+    assert_eq!(
+        fs::read_to_string(&test_file)?,
+        r#"! allow(implicit-typing)
+program test
+  ! allow(literal-kind, star-kind)
+  integer*4 :: a
+  ! allow(star-kind, literal-kind)
+  integer*4 :: b
+! allow(unnamed-end-statement)
+end program
+"#
+    );
+
+    assert_cmd_snapshot!(check()?, @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    fortitude: 1 files scanned.
+    All checks passed!
+
+
+    ----- stderr -----
+    ");
+    Ok(())
+}
+
+#[test]
+fn nested_config_files() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let legacy = tempdir.path().join("legacy");
+    fs::create_dir(&legacy)?;
+    let source = r#"program test
+end program
+"#;
+    fs::write(tempdir.path().join("a.f90"), source)?;
+    fs::write(legacy.join("b.f90"), source)?;
+    fs::write(
+        tempdir.path().join("fortitude.toml"),
+        r#"
+[check]
+select = ["C001", "S061"]
+"#,
+    )?;
+    fs::write(
+        legacy.join("fortitude.toml"),
+        r#"
+extend = "../fortitude.toml"
+
+[check]
+ignore = ["C001"]
+"#,
+    )?;
+
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--output-format=concise")
+                         .current_dir(tempdir.path()),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    a.f90:1:1: C001 program missing 'implicit none'
+    a.f90:2:1: S061 [*] end statement should be named.
+    legacy/b.f90:2:1: S061 [*] end statement should be named.
+    fortitude: 2 files scanned.
+    Number of errors: 3
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+    [*] 2 fixable with the `--fix` option.
+
+    ----- stderr -----
+    ");
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--show-settings")
+        .arg("legacy/b.f90")
+        .current_dir(tempdir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(
+            r#"Settings path: ".*legacy.fortitude\.toml""#,
+        )?);
+    Ok(())
+}
+
+#[test]
+fn nested_config_files_project_rules() -> anyhow::Result<()> {
+    use predicates::boolean::PredicateBooleanExt;
+
+    let tempdir = TempDir::new()?;
+    let legacy = tempdir.path().join("legacy");
+    fs::create_dir(&legacy)?;
+    fs::write(
+        tempdir.path().join("a.f90"),
+        r#"module a
+  use missing_a
+end module a
+"#,
+    )?;
+    fs::write(
+        legacy.join("b.f90"),
+        r#"module b
+  use missing_b
+end module b
+"#,
+    )?;
+    fs::write(
+        tempdir.path().join("fortitude.toml"),
+        r#"
+[check]
+preview = true
+select = ["C181"]
+"#,
+    )?;
+    fs::write(
+        legacy.join("fortitude.toml"),
+        r#"
+extend = "../fortitude.toml"
+
+[check]
+ignore = ["C181"]
+"#,
+    )?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--output-format=concise")
+        .current_dir(tempdir.path())
+        .assert()
+        .failure()
+        .stdout(predicates::str::is_match(
+            r"(?m)^a\.f90:2:\d+: C181 module 'missing_a' is not defined in any checked file$",
+        )?)
+        .stdout(predicates::str::contains("missing_b").not())
+        .stdout(predicates::str::contains("Number of errors: 1"));
+    Ok(())
+}
+
+#[test]
+fn severity_exit_on() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(
+        tempdir.path().join("a.f90"),
+        r#"program test
+end program
+"#,
+    )?;
+    fs::write(
+        tempdir.path().join("fortitude.toml"),
+        r#"
+[check]
+select = ["C001", "S061"]
+
+[check.severity]
+"S" = "warning"
+"#,
+    )?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--exit-on=error")
+        .current_dir(tempdir.path())
+        .assert()
+        .failure();
+
+    // Warnings are still reported, but don't cause a failure
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--output-format=concise")
+                         .arg("--exit-on=error")
+                         .arg("--ignore=C001")
+                         .current_dir(tempdir.path()),
+                         @r"
+    success: true
+    exit_code: 0
+    ----- stdout -----
+    a.f90:2:1: S061 [*] end statement should be named.
+    fortitude: 1 files scanned.
+    Number of errors: 1
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+    [*] 1 fixable with the `--fix` option.
+
+    ----- stderr -----
+    ");
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--ignore=C001")
+        .current_dir(tempdir.path())
+        .assert()
+        .failure();
+    Ok(())
+}
+
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<()> {
+    Command::new("git")
+        .args([
+            "-c",
+            "user.name=Fortitude",
+            "-c",
+            "user.email=fortitude@example.com",
+            "-c",
+            "commit.gpgsign=false",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .assert()
+        .success();
+    Ok(())
+}
+
+#[test]
+fn diff_base() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let path = tempdir.path();
+    fs::write(
+        path.join("fortitude.toml"),
+        r#"
+[check]
+select = ["C001", "S061"]
+"#,
+    )?;
+    fs::write(
+        path.join("a.f90"),
+        r#"module a
+  implicit none
+end module
+"#,
+    )?;
+    fs::write(
+        path.join("c.f90"),
+        r#"program c
+end program
+"#,
+    )?;
+    git(path, &["init", "--quiet"])?;
+    git(path, &["add", "."])?;
+    git(path, &["commit", "--quiet", "--message", "Initial commit"])?;
+
+    // Add a new module to a tracked file, and create an untracked file
+    fs::write(
+        path.join("a.f90"),
+        r#"module a
+  implicit none
+end module
+
+module a2
+end module
+"#,
+    )?;
+    fs::write(
+        path.join("b.f90"),
+        r#"program b
+end program
+"#,
+    )?;
+
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--output-format=concise")
+                         .arg("--diff-base=HEAD")
+                         .current_dir(path),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    a.f90:5:1: C001 module missing 'implicit none'
+    a.f90:6:1: S061 [*] end statement should be named.
+    b.f90:1:1: C001 program missing 'implicit none'
+    b.f90:2:1: S061 [*] end statement should be named.
+    fortitude: 2 files scanned.
+    Number of errors: 4
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+    [*] 2 fixable with the `--fix` option.
+
+    ----- stderr -----
+    ");
+
+    // Paths given explicitly are always checked, but only changed lines are reported
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--output-format=concise")
+                         .arg("--diff-base=HEAD")
+                         .arg("a.f90")
+                         .arg("c.f90")
+                         .current_dir(path),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    a.f90:5:1: C001 module missing 'implicit none'
+    a.f90:6:1: S061 [*] end statement should be named.
+    fortitude: 2 files scanned.
+    Number of errors: 2
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+    [*] 1 fixable with the `--fix` option.
+
+    ----- stderr -----
+    ");
+
+    // Nothing has changed since the latest commit
+    git(path, &["add", "."])?;
+    git(path, &["commit", "--quiet", "--message", "Add modules"])?;
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--diff-base=HEAD")
+        .current_dir(path)
+        .assert()
+        .success();
+    Ok(())
+}
+
+#[test]
+fn diff_base_project_rules() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let path = tempdir.path();
+    fs::write(
+        path.join("fortitude.toml"),
+        r#"
+[check]
+preview = true
+select = ["C181"]
+"#,
+    )?;
+    fs::write(
+        path.join("a.f90"),
+        r#"module a
+end module a
+"#,
+    )?;
+    git(path, &["init", "--quiet"])?;
+    git(path, &["add", "."])?;
+    git(path, &["commit", "--quiet", "--message", "Initial commit"])?;
+
+    // The module used by the changed file is defined in an unchanged one
+    fs::write(
+        path.join("b.f90"),
+        r#"module b
+  use a
+end module b
+"#,
+    )?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--diff-base=HEAD")
+        .current_dir(path)
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "Project rules (C181, C182, C183) aren't checked with `--diff-base`",
+        ));
+    Ok(())
+}
+
+#[test]
+fn multiple_output_formats() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    let path = tempdir.path();
+    fs::write(
+        path.join("fortitude.toml"),
+        r#"
+[check]
+select = ["C001", "S061"]
+extend-output-format = ["junit:reports/fortitude.xml"]
+"#,
+    )?;
+    fs::write(
+        path.join("a.f90"),
+        r#"program a
+end program
+"#,
+    )?;
+
+    assert_cmd_snapshot!(Command::cargo_bin(BIN_NAME)?
+                         .arg("check")
+                         .arg("--output-format=concise")
+                         .arg("--output-format=json:fortitude.json")
+                         .current_dir(path),
+                         @r"
+    success: false
+    exit_code: 1
+    ----- stdout -----
+    a.f90:1:1: C001 program missing 'implicit none'
+    a.f90:2:1: S061 [*] end statement should be named.
+    fortitude: 1 files scanned.
+    Number of errors: 2
+
+    For more information about specific rules, run:
+
+        fortitude explain X001,Y002,...
+
+    [*] 1 fixable with the `--fix` option.
+
+    ----- stderr -----
+    ");
+
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(path.join("fortitude.json"))?)?;
+    let codes: Vec<_> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| message["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, ["C001", "S061"]);
+
+    let junit = fs::read_to_string(path.join("reports").join("fortitude.xml"))?;
+    assert!(junit.contains(r#"<testsuites name="fortitude" tests="2" failures="2" errors="0">"#));
+
+    // Files get the full report when only statistics are shown, whatever their format
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--statistics")
+        .arg("--output-format=sarif:fortitude.sarif")
+        .current_dir(path)
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("1\tC001"));
+    let sarif: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(path.join("fortitude.sarif"))?)?;
+    let rules: Vec<_> = sarif["runs"][0]["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["ruleId"].as_str().unwrap())
+        .collect();
+    assert_eq!(rules, ["C001", "S061"]);
+
+    // Only one format can be written to stdout
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--output-format=concise")
+        .arg("--output-format=full")
+        .current_dir(path)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Only one `--output-format` can be given without a path",
+        ));
+    Ok(())
+}
+
+/// Read lines from the output of a `--watch` process until it starts waiting for
+/// changes, returning everything it printed since it last did so.
+fn watch_output(lines: &std::sync::mpsc::Receiver<String>) -> anyhow::Result<Vec<String>> {
+    let mut output = Vec::new();
+    loop {
+        let line = lines.recv_timeout(std::time::Duration::from_secs(30))?;
+        if line.contains("Watching for file changes.") {
+            return Ok(output);
+        }
+        output.push(line);
+    }
+}
+
+#[test]
+fn watch_rechecks_changes() -> anyhow::Result<()> {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    let tempdir = TempDir::new()?;
+    let test_file = tempdir.path().join("test.f90");
+    fs::write(&test_file, "program test\n  implicit none\nend program\n")?;
+    let config_file = tempdir.path().join("fortitude.toml");
+    fs::write(&config_file, "[check]\nselect = [\"S061\"]\n")?;
+
+    let mut child = Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--watch")
+        .arg("--no-cache")
+        .arg("--output-format=concise")
+        .current_dir(tempdir.path())
+        .env("NO_COLOR", "1")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().unwrap();
+    let (sender, lines) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let result = (|| -> anyhow::Result<()> {
+        let output = watch_output(&lines)?;
+        assert!(
+            output.iter().any(|line| line.contains("S061")),
+            "{output:?}"
+        );
+
+        // Fixing the violation should be picked up
+        fs::write(
+            &test_file,
+            "program test\n  implicit none\nend program test\n",
+        )?;
+        let output = watch_output(&lines)?;
+        assert!(
+            output
+                .iter()
+                .any(|line| line.contains("File change detected")),
+            "{output:?}"
+        );
+        assert!(
+            !output.iter().any(|line| line.contains("S061")),
+            "{output:?}"
+        );
+
+        // As should changing the configuration
+        fs::write(&config_file, "[check]\nselect = [\"C001\"]\n")?;
+        fs::write(&test_file, "program test\nend program test\n")?;
+        let output = loop {
+            let output = watch_output(&lines)?;
+            if output.iter().any(|line| line.contains("C001")) {
+                break output;
+            }
+        };
+        assert!(
+            output
+                .iter()
+                .any(|line| line.contains("Configuration change detected")
+                    || line.contains("File change detected")),
+            "{output:?}"
+        );
+        Ok(())
+    })();
+
+    child.kill()?;
+    child.wait()?;
+    result
+}
+
+#[test]
+fn watch_rejects_diff_base() -> anyhow::Result<()> {
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .arg("--watch")
+        .arg("--diff-base=HEAD")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn plugin_load_failure() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(
+        tempdir.path().join("fortitude.toml"),
+        r#"
+[check]
+plugins = ["libmissing.so"]
+"#,
+    )?;
+    fs::write(
+        tempdir.path().join("test.f90"),
+        r#"program test
+  implicit none
+end program test
+"#,
+    )?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("check")
+        .current_dir(tempdir.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Failed to load plugin"))
+        .stderr(predicates::str::contains("libmissing.so"));
+    Ok(())
+}
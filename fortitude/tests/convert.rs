@@ -0,0 +1,89 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::{fs, process::Command};
+use tempfile::TempDir;
+
+const BIN_NAME: &str = "fortitude";
+
+const FIXED_FORM: &str = "C     A comment
+      PROGRAM TEST
+      X = 1 +
+     &    2
+      END
+";
+
+const FREE_FORM: &str = "!     A comment
+      PROGRAM TEST
+      X = 1 + &
+     &    2
+      END
+";
+
+#[test]
+fn convert_file() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(tempdir.path().join("test.f"), FIXED_FORM)?;
+    fs::write(tempdir.path().join("free.f90"), FREE_FORM)?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("convert")
+        .current_dir(tempdir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Converted 1 file(s)"));
+
+    assert!(!tempdir.path().join("test.f").exists());
+    assert_eq!(
+        fs::read_to_string(tempdir.path().join("test.f90"))?,
+        FREE_FORM
+    );
+
+    Ok(())
+}
+
+#[test]
+fn convert_diff() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(tempdir.path().join("test.F"), FIXED_FORM)?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("convert")
+        .arg("--diff")
+        .arg("test.F")
+        .current_dir(tempdir.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("+++ test.F90"))
+        .stdout(predicate::str::contains("+      X = 1 + &"))
+        .stderr(predicate::str::contains("1 file(s) would be converted"));
+
+    // Nothing should have been written
+    assert_eq!(
+        fs::read_to_string(tempdir.path().join("test.F"))?,
+        FIXED_FORM
+    );
+    assert!(!tempdir.path().join("test.F90").exists());
+
+    Ok(())
+}
+
+#[test]
+fn convert_existing_file() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(tempdir.path().join("test.f"), FIXED_FORM)?;
+    fs::write(tempdir.path().join("test.f90"), FREE_FORM)?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("convert")
+        .current_dir(tempdir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("already exists"));
+
+    assert_eq!(
+        fs::read_to_string(tempdir.path().join("test.f"))?,
+        FIXED_FORM
+    );
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::{fs, process::Command};
+use tempfile::TempDir;
+
+const BIN_NAME: &str = "fortitude";
+
+const UNFORMATTED: &str = "PROGRAM TEST
+IMPLICIT NONE
+INTEGER::X
+X=1
+END PROGRAM TEST
+";
+
+const FORMATTED: &str = "program TEST
+  implicit none
+  integer :: X
+  X = 1
+end program TEST
+";
+
+#[test]
+fn format_file() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(tempdir.path().join("test.f90"), UNFORMATTED)?;
+    fs::write(tempdir.path().join("formatted.f90"), FORMATTED)?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("format")
+        .current_dir(tempdir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "1 file(s) reformatted, 1 file(s) left unchanged",
+        ));
+
+    assert_eq!(
+        fs::read_to_string(tempdir.path().join("test.f90"))?,
+        FORMATTED
+    );
+
+    Ok(())
+}
+
+#[test]
+fn format_check() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(tempdir.path().join("test.f90"), UNFORMATTED)?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("format")
+        .arg("--check")
+        .current_dir(tempdir.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Would reformat: test.f90"));
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("format")
+        .arg("--diff")
+        .current_dir(tempdir.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("+  integer :: X"));
+
+    // Nothing should have been written
+    assert_eq!(
+        fs::read_to_string(tempdir.path().join("test.f90"))?,
+        UNFORMATTED
+    );
+
+    Ok(())
+}
+
+#[test]
+fn format_options() -> anyhow::Result<()> {
+    let tempdir = TempDir::new()?;
+    fs::write(tempdir.path().join("test.f90"), UNFORMATTED)?;
+    fs::write(
+        tempdir.path().join("fortitude.toml"),
+        "[format]\nindent-width = 4\nkeyword-case = \"upper\"\n",
+    )?;
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("format")
+        .current_dir(tempdir.path())
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(tempdir.path().join("test.f90"))?,
+        "PROGRAM TEST
+    IMPLICIT NONE
+    INTEGER :: X
+    X = 1
+END PROGRAM TEST
+"
+    );
+
+    Ok(())
+}
@@ -2,7 +2,7 @@
 
 use anyhow::Result;
 
-use crate::{generate_cli_help, generate_docs, generate_options};
+use crate::{generate_cli_help, generate_docs, generate_json_schema, generate_options};
 
 pub(crate) const REGENERATE_ALL_COMMAND: &str = "cargo dev generate-all";
 
@@ -35,5 +35,6 @@ pub(crate) fn main(args: &Args) -> Result<()> {
     generate_cli_help::main(&generate_cli_help::Args { mode: args.mode })?;
     generate_docs::main(&generate_docs::Args { mode: args.mode })?;
     generate_options::main(&generate_options::Args { mode: args.mode })?;
+    generate_json_schema::main(&generate_json_schema::Args { mode: args.mode })?;
     Ok(())
 }
@@ -0,0 +1,264 @@
+//! Generate a JSON Schema for `fortitude.toml`, from the metadata of the options.
+#![allow(clippy::print_stdout)]
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use pretty_assertions::StrComparison;
+use serde_json::{json, Map, Value};
+use strum::IntoEnumIterator;
+
+use fortitude::options::Options;
+use fortitude::options_base::{OptionField, OptionSet, OptionsMetadata, Visit};
+use fortitude::registry::{Category, Rule, RuleNamespace};
+use fortitude::rules::RuleCodePrefix;
+
+use crate::generate_all::{Mode, REGENERATE_ALL_COMMAND};
+use crate::ROOT_DIR;
+
+const SCHEMA_FILE: &str = "fortitude.schema.json";
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    #[arg(long, default_value_t, value_enum)]
+    pub(crate) mode: Mode,
+}
+
+pub(crate) fn main(args: &Args) -> Result<()> {
+    let new = generate()?;
+
+    if args.mode.is_dry_run() {
+        print!("{new}");
+        return Ok(());
+    }
+
+    let file = PathBuf::from(ROOT_DIR).join(SCHEMA_FILE);
+    let existing = fs::read_to_string(&file).unwrap_or_default();
+
+    match args.mode {
+        Mode::Check => {
+            if existing == new {
+                println!("up-to-date: {SCHEMA_FILE}");
+            } else {
+                let comparison = StrComparison::new(&existing, &new);
+                bail!(
+                    "{SCHEMA_FILE} changed, please run `{REGENERATE_ALL_COMMAND}`:\n{comparison}"
+                );
+            }
+        }
+        _ => {
+            fs::write(file, new)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn generate() -> Result<String> {
+    let mut schema = set_schema(Options::metadata())?;
+    let schema_object = schema.as_object_mut().unwrap();
+    schema_object.insert(
+        "$schema".to_string(),
+        json!("http://json-schema.org/draft-07/schema#"),
+    );
+    schema_object.insert("title".to_string(), json!("Options"));
+    schema_object.insert(
+        "definitions".to_string(),
+        json!({
+            "RuleSelector": rule_selector_schema(),
+            "CustomRuleOptions": custom_rule_schema(),
+        }),
+    );
+
+    sort_keys(&mut schema);
+    let mut output = serde_json::to_string_pretty(&schema)?;
+    output.push('\n');
+    Ok(output)
+}
+
+/// Sort the keys of every object, so that the output doesn't depend on whether
+/// `serde_json` preserves insertion order
+fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            object.sort_keys();
+            object.values_mut().for_each(sort_keys);
+        }
+        Value::Array(array) => array.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
+
+/// Schema for a table of options, such as `[check]`
+fn set_schema(set: OptionSet) -> Result<Value> {
+    let mut visitor = CollectOptionsVisitor::default();
+    set.record(&mut visitor);
+
+    let mut properties = Map::new();
+    for (name, field) in visitor.fields {
+        properties.insert(name.clone(), field_schema(&name, &field)?);
+    }
+    for (name, group) in visitor.groups {
+        properties.insert(name, set_schema(group)?);
+    }
+
+    let mut schema = json!({
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    });
+    if let Some(documentation) = set.documentation() {
+        schema["description"] = json!(documentation);
+    }
+    Ok(schema)
+}
+
+/// Schema for a single option, with its type taken from `value_type`
+fn field_schema(name: &str, field: &OptionField) -> Result<Value> {
+    let mut schema = type_schema(field.value_type)
+        .with_context(|| format!("Failed to generate schema for option `{name}`"))?;
+    schema["description"] = json!(field.doc);
+    // Not all defaults are written as TOML values that are also valid JSON, such as
+    // `off`, so only include those that are
+    if let Ok(default) = serde_json::from_str::<Value>(field.default) {
+        schema["default"] = default;
+    }
+    if field.deprecated.is_some() {
+        schema["deprecated"] = json!(true);
+    }
+    Ok(schema)
+}
+
+/// Convert the Python-like types used by `value_type`, such as `list[str]` or
+/// `"single" | "double"`, into a schema
+fn type_schema(value_type: &str) -> Result<Value> {
+    let value_type = value_type.trim();
+
+    if value_type.starts_with('"') {
+        let values = value_type
+            .split('|')
+            .map(|value| {
+                let value = value.trim();
+                value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .with_context(|| format!("Expected a quoted string, found `{value}`"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        return Ok(json!({ "type": "string", "enum": values }));
+    }
+
+    if let Some(item) = value_type
+        .strip_prefix("list[")
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        return Ok(json!({ "type": "array", "items": type_schema(item)? }));
+    }
+
+    if let Some(value) = value_type
+        .strip_prefix("dict[str,")
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        return Ok(json!({ "type": "object", "additionalProperties": type_schema(value)? }));
+    }
+
+    if let Some(value) = value_type
+        .strip_prefix("dict[RuleSelector,")
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        return Ok(json!({
+            "type": "object",
+            "propertyNames": type_schema("RuleSelector")?,
+            "additionalProperties": type_schema(value)?,
+        }));
+    }
+
+    let schema = match value_type {
+        "bool" => json!({ "type": "boolean" }),
+        "int" => json!({ "type": "integer", "minimum": 0 }),
+        "str" => json!({ "type": "string" }),
+        "RuleSelector" | "CustomRuleOptions" => {
+            json!({ "$ref": format!("#/definitions/{value_type}") })
+        }
+        _ => bail!("Unknown value type `{value_type}`"),
+    };
+    Ok(schema)
+}
+
+/// Every code, code prefix, category, and rule name that can be used to select rules.
+/// Custom rules can also be selected by their own codes and names, which can't be
+/// known in advance.
+fn rule_selector_schema() -> Value {
+    let codes = std::iter::once("ALL".to_string())
+        .chain(Category::iter().map(|category| category.common_prefix().to_string()))
+        .chain(RuleCodePrefix::iter().map(|prefix| {
+            format!(
+                "{}{}",
+                prefix.category().common_prefix(),
+                prefix.short_code()
+            )
+        }))
+        .chain(Category::iter().map(|category| category.as_ref().to_string()))
+        .chain(Rule::iter().map(|rule| rule.as_ref().to_string()))
+        .unique()
+        .sorted()
+        .collect_vec();
+
+    json!({
+        "anyOf": [
+            { "type": "string", "enum": codes },
+            {
+                "description": "A custom rule, selected by code, code prefix, or name",
+                "type": "string",
+                "pattern": "^[A-Za-z0-9_-]+$",
+            },
+        ]
+    })
+}
+
+/// A custom rule in `check.custom-rules`. This mirrors `CustomRuleOptions`, which
+/// isn't a table of options with metadata of its own.
+fn custom_rule_schema() -> Value {
+    json!({
+        "description": "A custom rule, defined by a tree-sitter query",
+        "type": "object",
+        "properties": {
+            "code": {
+                "description": "The code used to select, ignore, or allow the rule",
+                "type": "string",
+                "pattern": "^[A-Z]+[0-9]+$",
+            },
+            "query": {
+                "description": "The tree-sitter query to match against each file",
+                "type": "string",
+            },
+            "message": {
+                "description": "The message to report, which can interpolate captures as `{capture}`",
+                "type": "string",
+            },
+            "replacement": {
+                "description": "Text to replace the reported node with, applied as an unsafe fix",
+                "type": "string",
+            },
+        },
+        "required": ["code", "query", "message"],
+        "additionalProperties": false,
+    })
+}
+
+#[derive(Default)]
+struct CollectOptionsVisitor {
+    groups: Vec<(String, OptionSet)>,
+    fields: Vec<(String, OptionField)>,
+}
+
+impl Visit for CollectOptionsVisitor {
+    fn record_set(&mut self, name: &str, group: OptionSet) {
+        self.groups.push((name.to_owned(), group));
+    }
+
+    fn record_field(&mut self, name: &str, field: OptionField) {
+        self.fields.push((name.to_owned(), field));
+    }
+}
@@ -9,6 +9,7 @@ use std::process::ExitCode;
 mod generate_all;
 mod generate_cli_help;
 mod generate_docs;
+mod generate_json_schema;
 mod generate_options;
 mod generate_rules_table;
 mod parse;
@@ -28,8 +29,8 @@ struct Args {
 enum Command {
     /// Run all code and documentation generation steps.
     GenerateAll(generate_all::Args),
-    // /// Generate JSON schema for the TOML configuration file.
-    // GenerateJSONSchema(generate_json_schema::Args),
+    /// Generate JSON schema for the TOML configuration file.
+    GenerateJSONSchema(generate_json_schema::Args),
     /// Generate a Markdown-compatible table of supported lint rules.
     GenerateRulesTable,
     /// Generate a Markdown-compatible listing of configuration options.
@@ -47,7 +48,7 @@ fn main() -> Result<ExitCode> {
     #[allow(clippy::print_stdout)]
     match command {
         Command::GenerateAll(args) => generate_all::main(&args)?,
-        // Command::GenerateJSONSchema(args) => generate_json_schema::main(&args)?,
+        Command::GenerateJSONSchema(args) => generate_json_schema::main(&args)?,
         Command::GenerateRulesTable => println!("{}", generate_rules_table::generate()),
         Command::GenerateOptions(args) => generate_options::main(&args)?,
         Command::GenerateCliHelp(args) => generate_cli_help::main(&args)?,
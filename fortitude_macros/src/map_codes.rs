@@ -299,7 +299,7 @@ fn generate_rule_to_code(
 
         let is_path = kind.is_ident("Path");
         let is_text = kind.is_ident("Text");
-        let is_ast = kind.is_ident("Ast");
+        let is_ast = kind.is_ident("Ast") || kind.is_ident("Semantic");
 
         rule_is_path_rule_match_arms.extend(quote! {
             #(#attrs)* Rule::#rule_name => #is_path,
@@ -528,7 +528,9 @@ fn register_rules<'a>(input: impl Iterator<Item = &'a RuleMeta>) -> TokenStream
             });
         }
 
-        if kind.is_ident("Ast") {
+        // Rules using the semantic model are AST rules, which are only passed the model
+        // when they need it, so that it's only built if one of them is enabled
+        if kind.is_ident("Ast") || kind.is_ident("Semantic") {
             ast_rule_variants.extend(quote! {
                 #(#attrs)*
                 #name,
@@ -538,9 +540,15 @@ fn register_rules<'a>(input: impl Iterator<Item = &'a RuleMeta>) -> TokenStream
                 #(#attrs)* Rule::#name => Ok(Self::#name),
             });
 
-            ast_rule_check_match_arms.extend(quote! {
-                #(#attrs)* Self::#name => #path::check(settings, node, source),
-            });
+            if kind.is_ident("Semantic") {
+                ast_rule_check_match_arms.extend(quote! {
+                    #(#attrs)* Self::#name => #path::check(settings, node, source, semantic.get()),
+                });
+            } else {
+                ast_rule_check_match_arms.extend(quote! {
+                    #(#attrs)* Self::#name => #path::check(settings, node, source),
+                });
+            }
 
             ast_rule_entrypoint_match_arms.extend(quote! {
                 #(#attrs)* Self::#name => #path::entrypoints(),
@@ -553,7 +561,8 @@ fn register_rules<'a>(input: impl Iterator<Item = &'a RuleMeta>) -> TokenStream
         use ruff_diagnostics::{Diagnostic, Violation};
         use ruff_source_file::SourceFile;
         use tree_sitter::Node;
-        use crate::{AstRule, PathRule, TextRule};
+        use crate::{AstRule, PathRule, SemanticAstRule, TextRule};
+        use crate::semantic::LazySemanticModel;
         use crate::settings::Settings;
 
 
@@ -720,7 +729,7 @@ fn register_rules<'a>(input: impl Iterator<Item = &'a RuleMeta>) -> TokenStream
         }
 
         impl AstRuleEnum {
-            pub fn check(&self, settings: &Settings, node: &Node, source: &SourceFile) -> Option<Vec<Diagnostic>> {
+            pub fn check(&self, settings: &Settings, node: &Node, source: &SourceFile, semantic: &LazySemanticModel) -> Option<Vec<Diagnostic>> {
                 match self {
                     #ast_rule_check_match_arms
                 }
@@ -754,6 +763,7 @@ impl Parse for RuleMeta {
         let kind_is_valid = kind.is_ident("Path")
             || kind.is_ident("Text")
             || kind.is_ident("Ast")
+            || kind.is_ident("Semantic")
             || kind.is_ident("None");
         if !kind_is_valid {
             // We better have an ident here, because I don't know what else to do
@@ -761,7 +771,7 @@ impl Parse for RuleMeta {
             return Err(syn::Error::new(
                 pat_tuple.span(),
                 format!(
-                    "Invalid checker kind '{kind}', expected one of 'Path', 'Text', 'Ast', 'None'"
+                    "Invalid checker kind '{kind}', expected one of 'Path', 'Text', 'Ast', 'Semantic', 'None'"
                 ),
             ));
         }